@@ -0,0 +1,224 @@
+-- Baseline schema. Every statement is idempotent so databases created before
+-- versioned migrations existed can adopt it in place.
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    username TEXT UNIQUE NOT NULL,
+    email TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    github_id BIGINT,
+    github_username TEXT,
+    avatar_url TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_users_username ON users(username);
+CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
+CREATE INDEX IF NOT EXISTS idx_users_github_id ON users(github_id);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    token TEXT UNIQUE NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_token ON sessions(token);
+CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_expires_at ON sessions(expires_at);
+
+-- Organizations table
+CREATE TABLE IF NOT EXISTS organizations (
+    id TEXT PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    display_name TEXT NOT NULL,
+    description TEXT,
+    avatar_url TEXT,
+    website TEXT,
+    owner_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_organizations_name ON organizations(name);
+CREATE INDEX IF NOT EXISTS idx_organizations_owner_id ON organizations(owner_id);
+
+-- Organization members table
+CREATE TABLE IF NOT EXISTS organization_members (
+    id TEXT PRIMARY KEY,
+    organization_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    role TEXT NOT NULL, -- 'owner', 'admin', 'member'
+    invited_by TEXT,
+    invited_at TEXT NOT NULL,
+    joined_at TEXT,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (invited_by) REFERENCES users (id) ON DELETE SET NULL,
+    UNIQUE(organization_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_org_members_org_id ON organization_members(organization_id);
+CREATE INDEX IF NOT EXISTS idx_org_members_user_id ON organization_members(user_id);
+
+-- Organization invites table
+CREATE TABLE IF NOT EXISTS organization_invites (
+    id TEXT PRIMARY KEY,
+    organization_id TEXT NOT NULL,
+    email TEXT NOT NULL,
+    role TEXT NOT NULL,
+    invited_by TEXT NOT NULL,
+    token TEXT UNIQUE NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    accepted_at TEXT,
+    FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE,
+    FOREIGN KEY (invited_by) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_org_invites_token ON organization_invites(token);
+CREATE INDEX IF NOT EXISTS idx_org_invites_email ON organization_invites(email);
+CREATE INDEX IF NOT EXISTS idx_org_invites_org_id ON organization_invites(organization_id);
+
+-- Crates table
+CREATE TABLE IF NOT EXISTS crates (
+    id TEXT PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    description TEXT,
+    homepage TEXT,
+    documentation TEXT,
+    repository TEXT,
+    keywords TEXT, -- JSON encoded Vec<String>
+    categories TEXT, -- JSON encoded Vec<String>
+    license TEXT,
+    owner_id TEXT NOT NULL,
+    organization_id TEXT,
+    downloads BIGINT NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_crates_name ON crates(name);
+CREATE INDEX IF NOT EXISTS idx_crates_owner_id ON crates(owner_id);
+CREATE INDEX IF NOT EXISTS idx_crates_organization_id ON crates(organization_id);
+CREATE INDEX IF NOT EXISTS idx_crates_downloads ON crates(downloads);
+
+-- Download metrics table
+CREATE TABLE IF NOT EXISTS download_metrics (
+    id TEXT PRIMARY KEY,
+    crate_id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    date TEXT NOT NULL, -- YYYY-MM-DD format
+    count BIGINT NOT NULL DEFAULT 0,
+    FOREIGN KEY (crate_id) REFERENCES crates (id) ON DELETE CASCADE,
+    UNIQUE(crate_id, version, date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_download_metrics_crate_id ON download_metrics(crate_id);
+CREATE INDEX IF NOT EXISTS idx_download_metrics_date ON download_metrics(date);
+
+-- crate_versions table
+CREATE TABLE IF NOT EXISTS crate_versions (
+    id TEXT PRIMARY KEY,
+    crate_id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    file_size BIGINT NOT NULL,
+    dependencies TEXT, -- JSON encoded Vec<Dependency>
+    features TEXT, -- JSON encoded HashMap<String, Vec<String>>
+    yanked BOOLEAN NOT NULL DEFAULT FALSE,
+    license TEXT,
+    readme TEXT,
+    links TEXT,
+    rust_version TEXT,
+    authors TEXT, -- JSON encoded Vec<String>
+    published_by TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (crate_id) REFERENCES crates (id) ON DELETE CASCADE,
+    UNIQUE(crate_id, version)
+);
+
+CREATE INDEX IF NOT EXISTS idx_crate_versions_crate_id ON crate_versions(crate_id);
+CREATE INDEX IF NOT EXISTS idx_crate_versions_version ON crate_versions(version);
+CREATE INDEX IF NOT EXISTS idx_crate_versions_yanked ON crate_versions(yanked);
+
+-- crate_owners table; each row is either a user or an organization
+CREATE TABLE IF NOT EXISTS crate_owners (
+    id TEXT PRIMARY KEY,
+    crate_id TEXT NOT NULL,
+    user_id TEXT,
+    organization_id TEXT,
+    added_by TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (crate_id) REFERENCES crates (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE,
+    CHECK ((user_id IS NULL) != (organization_id IS NULL)),
+    UNIQUE(crate_id, user_id),
+    UNIQUE(crate_id, organization_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_crate_owners_crate_id ON crate_owners(crate_id);
+CREATE INDEX IF NOT EXISTS idx_crate_owners_user_id ON crate_owners(user_id);
+CREATE INDEX IF NOT EXISTS idx_crate_owners_organization_id ON crate_owners(organization_id);
+
+-- api_tokens table; only a hash of each token is stored
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    scopes TEXT NOT NULL, -- JSON encoded Vec<TokenScope>
+    crate_patterns TEXT, -- JSON encoded Vec<String>
+    expires_at TEXT,
+    last_used_at TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id ON api_tokens(user_id);
+
+-- storage_migrations table; one row per artifact copied to (or
+-- failed for) a migration target, so an interrupted migration can resume
+CREATE TABLE IF NOT EXISTS storage_migrations (
+    target TEXT NOT NULL,
+    version_id TEXT NOT NULL,
+    status TEXT NOT NULL, -- 'copied' or 'failed'
+    error TEXT,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (target, version_id),
+    FOREIGN KEY (version_id) REFERENCES crate_versions (id) ON DELETE CASCADE
+);
+
+-- mirror_artifacts table; crates cached from upstream have no
+-- crate_versions row, so their checksums are kept here
+CREATE TABLE IF NOT EXISTS mirror_artifacts (
+    crate_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    file_size BIGINT NOT NULL,
+    cached_at TEXT NOT NULL,
+    PRIMARY KEY (crate_name, version)
+);
+
+-- integrity_issues table; one row per artifact that failed its
+-- last check, removed once it verifies again
+CREATE TABLE IF NOT EXISTS integrity_issues (
+    crate_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    origin TEXT NOT NULL, -- 'published' or 'mirror'
+    problem TEXT NOT NULL, -- 'missing' or 'corrupt'
+    expected_checksum TEXT NOT NULL,
+    actual_checksum TEXT,
+    detected_at TEXT NOT NULL,
+    checked_at TEXT NOT NULL,
+    PRIMARY KEY (crate_name, version)
+);
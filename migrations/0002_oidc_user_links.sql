@@ -0,0 +1,20 @@
+-- Links local users to the identities they sign in with through OIDC.
+-- OIDC-only users are stored with an empty password_hash.
+CREATE TABLE IF NOT EXISTS oidc_user_links (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    provider_id TEXT,
+    provider_type TEXT NOT NULL, -- 'entraid', 'github', 'google', 'generic'
+    external_id TEXT NOT NULL, -- Subject ID from the OIDC provider
+    email TEXT NOT NULL,
+    name TEXT,
+    avatar_url TEXT,
+    last_login TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    UNIQUE(provider_type, external_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_oidc_user_links_user_id ON oidc_user_links(user_id);
+CREATE INDEX IF NOT EXISTS idx_oidc_user_links_email ON oidc_user_links(email);
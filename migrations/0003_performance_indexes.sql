@@ -0,0 +1,5 @@
+-- Case-insensitive name lookups, as Cargo addresses index files
+CREATE INDEX IF NOT EXISTS idx_crates_name_lower ON crates(LOWER(name));
+CREATE INDEX IF NOT EXISTS idx_crates_updated_at ON crates(updated_at DESC);
+CREATE INDEX IF NOT EXISTS idx_crate_versions_created_at ON crate_versions(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_download_metrics_crate_id_date ON download_metrics(crate_id, date);
@@ -0,0 +1,20 @@
+-- Full-text index over each crate's name, keywords, description and latest
+-- readme, weighted in that order. Kept current by db::refresh_crate_search
+-- whenever a version is published.
+CREATE TABLE IF NOT EXISTS crate_search (
+    crate_id TEXT PRIMARY KEY,
+    document TSVECTOR NOT NULL,
+    FOREIGN KEY (crate_id) REFERENCES crates (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_crate_search_document ON crate_search USING GIN (document);
+
+INSERT INTO crate_search (crate_id, document)
+SELECT
+    c.id,
+    setweight(to_tsvector('simple', c.name), 'A') ||
+    setweight(to_tsvector('simple', COALESCE(c.keywords, '')), 'B') ||
+    setweight(to_tsvector('simple', COALESCE(c.description, '')), 'C') ||
+    setweight(to_tsvector('simple', COALESCE((SELECT cv.readme FROM crate_versions cv WHERE cv.crate_id = c.id ORDER BY cv.created_at DESC LIMIT 1), '')), 'D')
+FROM crates c
+ON CONFLICT (crate_id) DO NOTHING;
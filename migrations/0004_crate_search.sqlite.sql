@@ -0,0 +1,19 @@
+-- Full-text index over each crate's name, keywords, description and latest
+-- readme. Kept current by db::refresh_crate_search whenever a version is
+-- published; rows for deleted crates are dropped by the join at query time.
+CREATE VIRTUAL TABLE IF NOT EXISTS crate_search USING fts5(
+    crate_id UNINDEXED,
+    name,
+    keywords,
+    description,
+    readme
+);
+
+INSERT INTO crate_search (crate_id, name, keywords, description, readme)
+SELECT
+    c.id,
+    c.name,
+    COALESCE(c.keywords, ''),
+    COALESCE(c.description, ''),
+    COALESCE((SELECT cv.readme FROM crate_versions cv WHERE cv.crate_id = c.id ORDER BY cv.created_at DESC LIMIT 1), '')
+FROM crates c;
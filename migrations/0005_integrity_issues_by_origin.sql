@@ -0,0 +1,21 @@
+-- integrity_issues is keyed by origin as well, so a published crate and a
+-- mirrored crate with the same name and version are tracked separately
+CREATE TABLE integrity_issues_by_origin (
+    crate_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    origin TEXT NOT NULL, -- 'published' or 'mirror'
+    problem TEXT NOT NULL, -- 'missing' or 'corrupt'
+    expected_checksum TEXT NOT NULL,
+    actual_checksum TEXT,
+    detected_at TEXT NOT NULL,
+    checked_at TEXT NOT NULL,
+    PRIMARY KEY (crate_name, version, origin)
+);
+
+INSERT INTO integrity_issues_by_origin
+    (crate_name, version, origin, problem, expected_checksum, actual_checksum, detected_at, checked_at)
+SELECT crate_name, version, origin, problem, expected_checksum, actual_checksum, detected_at, checked_at
+FROM integrity_issues;
+
+DROP TABLE integrity_issues;
+ALTER TABLE integrity_issues_by_origin RENAME TO integrity_issues;
@@ -0,0 +1,58 @@
+-- Synthetic registry for scripts/bench-search.sh: 10,000 crates with five
+-- versions each (the newest yanked) and 30 days of download history spread
+-- over five months. SQLite only; run against a freshly migrated database.
+
+INSERT INTO users (id, username, email, password_hash, is_admin, created_at, updated_at)
+VALUES ('00000000-0000-4000-8000-000000000000', 'bench', 'bench@example.com', '!', FALSE,
+        strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 10000)
+INSERT INTO crates (id, name, description, keywords, categories, license, owner_id, downloads, created_at, updated_at)
+SELECT
+    printf('00000000-0000-4000-8000-%012d', i),
+    printf('bench-%s-%d', CASE i % 4 WHEN 0 THEN 'http' WHEN 1 THEN 'serde' WHEN 2 THEN 'async' ELSE 'cli' END, i),
+    printf('Benchmark crate %d for %s workloads', i, CASE i % 3 WHEN 0 THEN 'networking' WHEN 1 THEN 'parsing' ELSE 'storage' END),
+    printf('["%s","bench"]', CASE i % 5 WHEN 0 THEN 'web' WHEN 1 THEN 'json' WHEN 2 THEN 'tokio' WHEN 3 THEN 'cli' ELSE 'db' END),
+    '["development-tools"]',
+    'MIT',
+    '00000000-0000-4000-8000-000000000000',
+    (i * 7919) % 100000,
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now', printf('-%d minutes', i)),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now', printf('-%d minutes', i))
+FROM n;
+
+WITH RECURSIVE v(j) AS (SELECT 0 UNION ALL SELECT j + 1 FROM v WHERE j < 4)
+INSERT INTO crate_versions (id, crate_id, version, checksum, file_size, dependencies, features, yanked, readme, created_at)
+SELECT
+    printf('%s-%d', c.id, v.j),
+    c.id,
+    printf('0.%d.0', v.j),
+    printf('%064d', 0),
+    4096,
+    '[]',
+    '{}',
+    v.j = 4,
+    printf('# %s\n\nRelease notes for %s.', c.name, c.name),
+    strftime('%Y-%m-%dT%H:%M:%SZ', c.created_at, printf('+%d hours', v.j))
+FROM crates c, v;
+
+WITH RECURSIVE d(k) AS (SELECT 0 UNION ALL SELECT k + 1 FROM d WHERE k < 29)
+INSERT INTO download_metrics (id, crate_id, version, date, count)
+SELECT
+    printf('%s-%d', c.id, d.k),
+    c.id,
+    '0.3.0',
+    date('now', printf('-%d days', d.k * 5)),
+    (d.k * 31 + length(c.name)) % 50
+FROM crates c, d;
+
+-- Migration 0004 indexed the (then empty) crates table, so rebuild it
+DELETE FROM crate_search;
+INSERT INTO crate_search (crate_id, name, keywords, description, readme)
+SELECT
+    c.id,
+    c.name,
+    COALESCE(c.keywords, ''),
+    COALESCE(c.description, ''),
+    COALESCE((SELECT cv.readme FROM crate_versions cv WHERE cv.crate_id = c.id ORDER BY cv.created_at DESC LIMIT 1), '')
+FROM crates c;
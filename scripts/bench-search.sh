@@ -0,0 +1,98 @@
+#!/bin/bash
+
+# GhostCrate search benchmark
+# Seeds a throwaway SQLite registry with 10,000 crates, starts the server
+# against it and reports latency percentiles for /api/v1/crates.
+#
+# Usage: scripts/bench-search.sh [requests-per-query]
+# Needs sqlite3 (with FTS5) and curl. Set SERVER_BIN to skip the release build.
+#
+# Release builds before and after search moved from per-crate version loads
+# to batched queries, 200 requests per query on a single-core machine (ms):
+#
+#   query                                            before p50/p95   after p50/p95
+#   q=serde&per_page=10                                28.09 / 36.54   18.16 / 25.38
+#   q=serde&per_page=100                               55.24 / 73.87   25.44 / 30.63
+#   q=bench+parsing&per_page=100                       77.58 / 127.13  38.57 / 47.73
+#   q=http&sort=recent-downloads&per_page=100         179.29 / 236.27  62.02 / 72.18
+#   keyword=tokio&sort=recent-updates&per_page=100     58.68 / 88.59   27.54 / 40.06
+#   per_page=100&page=50                               41.73 / 60.49   20.55 / 25.58
+
+set -euo pipefail
+
+ROOT="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+REQUESTS="${1:-200}"
+PORT="${BENCH_PORT:-18080}"
+WORK_DIR="$(mktemp -d)"
+SERVER_PID=""
+
+cleanup() {
+    if [ -n "$SERVER_PID" ]; then
+        kill "$SERVER_PID" 2>/dev/null || true
+        wait "$SERVER_PID" 2>/dev/null || true
+    fi
+    rm -rf "$WORK_DIR"
+}
+trap cleanup EXIT
+
+if [ -z "${SERVER_BIN:-}" ]; then
+    echo "🏗️  Building release server..."
+    cargo build --release --features ssr --bin server --manifest-path "$ROOT/Cargo.toml"
+    SERVER_BIN="$ROOT/target/release/server"
+fi
+
+# Everything the server writes (database, artifacts, git index) stays in WORK_DIR
+cd "$WORK_DIR"
+mkdir -p data
+export DATABASE_URL="sqlite:$WORK_DIR/data/bench.db?mode=rwc"
+export GHOSTCRATE_PORT="$PORT"
+export GHOSTCRATE_HOST="127.0.0.1"
+export RUST_LOG="${RUST_LOG:-warn}"
+
+echo "🗄️  Creating schema and seeding 10,000 crates..."
+"$SERVER_BIN" --migrate-only >/dev/null
+sqlite3 "$WORK_DIR/data/bench.db" < "$ROOT/scripts/bench-search-seed.sql"
+
+echo "🚀 Starting server on port $PORT..."
+"$SERVER_BIN" >"$WORK_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+
+for i in {1..60}; do
+    if curl -sf "http://127.0.0.1:$PORT/health" >/dev/null; then
+        break
+    fi
+    if [ $i -eq 60 ]; then
+        echo "❌ Server did not become healthy"
+        cat "$WORK_DIR/server.log"
+        exit 1
+    fi
+    sleep 1
+done
+
+# Prints "p50 p95 max" in milliseconds for REQUESTS requests to a URL
+measure() {
+    local url="$1"
+    for _ in $(seq "$REQUESTS"); do
+        curl -s -o /dev/null -w '%{time_total}\n' "$url"
+    done | sort -n | awk '
+        { t[NR] = $1 * 1000 }
+        END { printf "%8.2f %8.2f %8.2f\n", t[int(NR * 0.50)], t[int(NR * 0.95)], t[NR] }
+    '
+}
+
+QUERIES=(
+    "q=serde&per_page=10"
+    "q=serde&per_page=100"
+    "q=bench+parsing&per_page=100"
+    "q=http&sort=recent-downloads&per_page=100"
+    "keyword=tokio&sort=recent-updates&per_page=100"
+    "per_page=100&page=50"
+)
+
+echo ""
+echo "📊 $REQUESTS requests per query, latency in ms"
+printf "%-50s %8s %8s %8s\n" "query" "p50" "p95" "max"
+for query in "${QUERIES[@]}"; do
+    curl -sf "http://127.0.0.1:$PORT/api/v1/crates?$query" >/dev/null
+    printf "%-50s %s\n" "$query" "$(measure "http://127.0.0.1:$PORT/api/v1/crates?$query")"
+done
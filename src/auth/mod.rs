@@ -1,19 +1,26 @@
 use axum::{
     extract::{Request, State},
-    http::{header, StatusCode},
+    http::{header, HeaderValue, Method},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use bcrypt::{hash, verify};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use chrono::{Duration, Utc};
 use anyhow::Result;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use crate::models::{User, LoginRequest, CreateUserRequest, LoginResponse, UserResponse};
+use crate::models::{
+    User, LoginRequest, CreateUserRequest, LoginResponse, UserResponse, AuthContext, TokenScope,
+    API_TOKEN_PREFIX,
+};
 use crate::config::AuthConfig;
 use crate::db;
+use crate::error::ApiError;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -37,6 +44,17 @@ pub fn generate_session_token() -> String {
     Uuid::new_v4().to_string()
 }
 
+/// Generates a new API token; two v4 UUIDs give it 244 bits of randomness.
+pub fn generate_api_token() -> String {
+    format!("{}{}{}", API_TOKEN_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// API tokens are long and random, so a fast hash is enough to keep them
+/// useless if the database leaks, and it keeps lookups by hash possible.
+pub fn hash_api_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
 pub fn create_jwt_token(user: &User, config: &AuthConfig) -> Result<String> {
     let now = Utc::now();
     let expires_at = now + Duration::hours(config.session_duration_hours);
@@ -68,7 +86,7 @@ pub fn verify_jwt_token(token: &str, config: &AuthConfig) -> Result<Claims> {
 }
 
 pub async fn authenticate_user(
-    pool: &sqlx::SqlitePool,
+    pool: &db::DbPool,
     login_request: LoginRequest,
     config: &AuthConfig,
 ) -> Result<LoginResponse> {
@@ -94,7 +112,7 @@ pub async fn authenticate_user(
 }
 
 pub async fn register_user(
-    pool: &sqlx::SqlitePool,
+    pool: &db::DbPool,
     create_request: CreateUserRequest,
     config: &AuthConfig,
 ) -> Result<UserResponse> {
@@ -111,35 +129,169 @@ pub async fn register_user(
 }
 
 // Middleware to require authentication
+//
+// Accepts either a web session (`Bearer <session token>`) or an API token.
+// Cargo sends the configured token verbatim, so both `<token>` and
+// `Bearer <token>` are understood. Git clients fetching the git index send
+// `Basic` credentials instead, with the token as the password.
 pub async fn auth_middleware(
     State(app_state): State<crate::AppState>,
     mut request: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     let auth_header = request
         .headers()
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok())
-        .and_then(|header| header.strip_prefix("Bearer "));
+        .and_then(header_token);
     
     let token = match auth_header {
         Some(token) => token,
-        None => return Err(StatusCode::UNAUTHORIZED),
+        None => {
+            let mut response = ApiError::unauthorized(
+                "this request requires authentication; pass a token in the Authorization header",
+            ).into_response();
+
+            // Git only asks its credential helpers after a Basic challenge
+            if request.uri().path().starts_with("/git/") {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Basic realm=\"GhostCrate\""),
+                );
+            }
+            return Ok(response);
+        }
     };
-    
-    let session = match db::get_session_by_token(&app_state.pool, token).await {
-        Ok(Some(session)) => session,
-        _ => return Err(StatusCode::UNAUTHORIZED),
+    let token = token.as_str();
+
+    let (user_id, context) = if token.starts_with(API_TOKEN_PREFIX) {
+        let api_token = db::get_api_token_by_hash(&app_state.pool, &hash_api_token(token))
+            .await?
+            .ok_or_else(invalid_token)?;
+
+        if let Err(e) = db::touch_api_token(&app_state.pool, api_token.id).await {
+            tracing::warn!("Failed to record API token use: {}", e);
+        }
+
+        (api_token.user_id, AuthContext::for_token(&api_token))
+    } else {
+        let session = db::get_session_by_token(&app_state.pool, token)
+            .await?
+            .ok_or_else(invalid_token)?;
+
+        (session.user_id, AuthContext::session())
     };
+
+    if !token_may_use(request.method(), request.uri().path(), &context) {
+        return Err(ApiError::forbidden(
+            "API tokens can only be used with the registry API, or with the `read` scope to read your account and organizations",
+        ));
+    }
     
     // Get user details
-    let user = match db::get_user_by_id(&app_state.pool, session.user_id).await {
-        Ok(Some(user)) => user,
-        _ => return Err(StatusCode::UNAUTHORIZED),
-    };
+    let user = db::get_user_by_id(&app_state.pool, user_id)
+        .await?
+        .ok_or_else(invalid_token)?;
     
-    // Add user to request extensions
+    // Add user and how they authenticated to request extensions
     request.extensions_mut().insert(user);
+    request.extensions_mut().insert(context);
     
     Ok(next.run(request).await)
-}
\ No newline at end of file
+}
+
+/// Session routes an API token with the `read` scope may `GET`.
+const TOKEN_READABLE_ROUTES: &[&str] = &["/api/auth/me", "/api/organizations"];
+
+/// Routes only a web session may use, whatever the token's scopes.
+const SESSION_ONLY_ROUTES: &[&str] = &["/admin", "/api/tokens"];
+
+/// Whether a request may go ahead with the given credential. Tokens are for
+/// the registry API and index, where handlers check their scopes; elsewhere
+/// they can only read a few account routes, and never manage tokens or the
+/// registry itself.
+fn token_may_use(method: &Method, path: &str, context: &AuthContext) -> bool {
+    let under = |route: &&str| {
+        path.strip_prefix(*route)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    };
+
+    if context.is_session() {
+        return true;
+    }
+    if SESSION_ONLY_ROUTES.iter().any(under) {
+        return false;
+    }
+    if path.starts_with("/api/v1/") || path.starts_with("/index/") || path.starts_with("/git/index/") {
+        return true;
+    }
+    *method == Method::GET && context.has_scope(TokenScope::Read) && TOKEN_READABLE_ROUTES.iter().any(under)
+}
+
+/// Extracts the token from an `Authorization` header value: `Bearer <token>`,
+/// a bare `<token>`, or `Basic` credentials carrying the token as the password
+/// (or as the user name when the password is empty).
+fn header_token(value: &str) -> Option<String> {
+    let token = match value.strip_prefix("Basic ") {
+        Some(credentials) => {
+            let credentials = String::from_utf8(BASE64.decode(credentials.trim()).ok()?).ok()?;
+            let (user, password) = credentials.split_once(':')?;
+            if password.is_empty() { user.to_string() } else { password.to_string() }
+        }
+        None => value.strip_prefix("Bearer ").unwrap_or(value).to_string(),
+    };
+
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+fn invalid_token() -> ApiError {
+    ApiError::unauthorized("the token is invalid, expired or revoked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(scopes: Vec<TokenScope>) -> AuthContext {
+        AuthContext {
+            token_id: Some(Uuid::new_v4()),
+            scopes,
+            crate_patterns: None,
+        }
+    }
+
+    #[test]
+    fn tokens_are_limited_to_the_registry_and_whitelisted_reads() {
+        let session = AuthContext::session();
+        let reader = token(vec![TokenScope::Read]);
+        let publisher = token(vec![TokenScope::PublishNew, TokenScope::PublishUpdate]);
+        let everything = token(TokenScope::all());
+
+        // (method, path, session, read token, publish token, all-scope token)
+        let matrix = [
+            (Method::PUT, "/api/v1/crates/new", true, true, true, true),
+            (Method::GET, "/api/v1/crates/foo/1.0.0/download", true, true, true, true),
+            (Method::GET, "/index/3/f/foo", true, true, true, true),
+            (Method::POST, "/git/index/git-upload-pack", true, true, true, true),
+            (Method::GET, "/api/auth/me", true, true, false, true),
+            (Method::GET, "/api/organizations/42", true, true, false, true),
+            (Method::GET, "/api/organizations/42/members", true, true, false, true),
+            (Method::POST, "/api/organizations/42/invite", true, false, false, false),
+            (Method::POST, "/api/auth/logout", true, false, false, false),
+            (Method::GET, "/api/github/link", true, false, false, false),
+            (Method::GET, "/api/tokens", true, false, false, false),
+            (Method::POST, "/api/tokens", true, false, false, false),
+            (Method::DELETE, "/api/tokens/42", true, false, false, false),
+            (Method::GET, "/admin", true, false, false, false),
+            (Method::GET, "/admin/api/users", true, false, false, false),
+            (Method::GET, "/api/auth/meta", true, false, false, false),
+        ];
+
+        for (method, path, by_session, by_reader, by_publisher, by_everything) in matrix {
+            let allowed = [&session, &reader, &publisher, &everything]
+                .map(|context| token_may_use(&method, path, context));
+            assert_eq!(allowed, [by_session, by_reader, by_publisher, by_everything], "{} {}", method, path);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// Database snapshots for backups
+//
+// SQLite is copied with `VACUUM INTO`, which reads one consistent snapshot
+// while other connections keep writing. PostgreSQL is dumped with `pg_dump`,
+// which does the same inside a single transaction, and loaded back with
+// `pg_restore`; both tools must be on the PATH.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use tokio::fs;
+use tokio::process::Command;
+
+use crate::db::{DbBackend, DbPool};
+
+/// Name of the snapshot file inside an archive.
+pub fn snapshot_file_name(backend: DbBackend) -> &'static str {
+    match backend {
+        DbBackend::Sqlite => "ghostcrate.db",
+        DbBackend::Postgres => "ghostcrate.pgdump",
+    }
+}
+
+/// Writes a consistent copy of the live database to `dest`, which must not
+/// exist yet.
+pub async fn snapshot(pool: &DbPool, database_url: &str, dest: &Path) -> Result<()> {
+    match DbBackend::of(pool) {
+        DbBackend::Sqlite => {
+            sqlx::query("VACUUM INTO $1")
+                .bind(dest.to_string_lossy().into_owned())
+                .execute(pool)
+                .await
+                .context("failed to snapshot the SQLite database")?;
+        }
+        DbBackend::Postgres => {
+            run_tool(
+                Command::new("pg_dump")
+                    .arg("--format=custom")
+                    .arg("--no-owner")
+                    .arg("--file")
+                    .arg(dest)
+                    .arg("--dbname")
+                    .arg(database_url),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that a snapshot can be read back, and for SQLite that it is intact
+/// and at the schema version the manifest claims.
+pub async fn verify_snapshot(backend: DbBackend, path: &Path, schema_version: i64) -> Result<()> {
+    match backend {
+        DbBackend::Sqlite => {
+            let mut conn = SqliteConnectOptions::new()
+                .filename(path)
+                .read_only(true)
+                .connect()
+                .await
+                .context("the database snapshot is not a readable SQLite database")?;
+
+            let check: String = sqlx::query_scalar("PRAGMA integrity_check")
+                .fetch_one(&mut conn)
+                .await?;
+            if check != "ok" {
+                bail!("the database snapshot failed its integrity check: {}", check);
+            }
+
+            let version: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+                .fetch_one(&mut conn)
+                .await
+                .context("the database snapshot has no schema_migrations table")?;
+            if version != schema_version {
+                bail!(
+                    "the database snapshot is at schema version {} but the manifest says {}",
+                    version, schema_version
+                );
+            }
+
+            conn.close().await?;
+        }
+        DbBackend::Postgres => {
+            run_tool(Command::new("pg_restore").arg("--list").arg(path)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the database at `database_url` with a verified snapshot. The
+/// server must not be running. A replaced SQLite file is kept next to the
+/// original, with `.pre-restore-<timestamp>` appended to its name.
+pub async fn restore_snapshot(database_url: &str, path: &Path) -> Result<()> {
+    match DbBackend::of_url(database_url) {
+        DbBackend::Sqlite => {
+            let target = SqliteConnectOptions::from_str(database_url)?.get_filename().to_path_buf();
+            if let Some(parent) = target.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).await?;
+            }
+
+            // Copied next to the target first, so the final rename is atomic
+            let incoming = with_suffix(&target, ".restoring");
+            fs::copy(path, &incoming).await?;
+
+            let suffix = format!(".pre-restore-{}", Utc::now().format("%Y%m%dT%H%M%SZ"));
+            for file in [target.clone(), with_suffix(&target, "-wal"), with_suffix(&target, "-shm")] {
+                if fs::try_exists(&file).await? {
+                    fs::rename(&file, with_suffix(&file, &suffix)).await?;
+                }
+            }
+
+            fs::rename(&incoming, &target).await?;
+            tracing::info!("Restored SQLite database to {}", target.display());
+        }
+        DbBackend::Postgres => {
+            // One transaction, so a failed restore leaves the database as it was
+            run_tool(
+                Command::new("pg_restore")
+                    .arg("--clean")
+                    .arg("--if-exists")
+                    .arg("--no-owner")
+                    .arg("--single-transaction")
+                    .arg("--dbname")
+                    .arg(database_url)
+                    .arg(path),
+            )
+            .await?;
+            tracing::info!("Restored PostgreSQL database");
+        }
+    }
+
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+async fn run_tool(command: &mut Command) -> Result<()> {
+    let program = command.as_std().get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .await
+        .with_context(|| format!("failed to run {}; is it installed and on the PATH?", program))?;
+
+    if !output.status.success() {
+        bail!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(())
+}
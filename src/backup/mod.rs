@@ -0,0 +1,725 @@
+// Backups of the database and stored artifacts
+//
+// A backup is a single `.tar.gz` archive:
+//
+//     manifest.json               what the archive holds, with checksums
+//     database/<snapshot>         see `database::snapshot_file_name`
+//     artifacts/<key>             each artifact under its storage key, if included
+//     artifacts/keys/<key>.key    its wrapped data key, if it is encrypted
+//
+// The manifest lists every artifact the database refers to, included or
+// not, so a restore can tell what storage has to hold for the restored
+// database to be complete.
+//
+// With storage encryption enabled, artifacts are archived as stored: the
+// ciphertext and its wrapped data key, never the decrypted contents. Such
+// an archive can only be restored with the master key that wrapped them.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::config::AppConfig;
+use crate::db::{self, DbBackend, DbPool};
+use crate::storage::cache::clear_cache_dir;
+use crate::storage::encryption::data_key_path;
+use crate::storage::{blob_key, sha256_hex, sha256_stream, ArtifactStore, Storage};
+
+pub mod database;
+
+/// Version of the archive layout, bumped on incompatible changes. Version 2
+/// added encrypted artifacts; version 1 archives are still read.
+const FORMAT_VERSION: u32 = 2;
+
+const ARCHIVE_PREFIX: &str = "ghostcrate-backup-";
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Release that took the backup
+    pub ghostcrate_version: String,
+    pub database: DatabaseEntry,
+    pub artifacts: Vec<ArtifactEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseEntry {
+    pub backend: DbBackend,
+    /// Path of the snapshot inside the archive
+    pub file: String,
+    pub sha256: String,
+    pub size: u64,
+    /// Newest migration applied when the snapshot was taken
+    pub schema_version: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactEntry {
+    /// Storage key, see [`blob_key`]
+    pub key: String,
+    /// Hex-encoded SHA-256 of the artifact's contents
+    pub sha256: String,
+    pub size: u64,
+    /// The artifact itself is in the archive, under `artifacts/<key>`
+    pub included: bool,
+    /// Set when the included artifact is the ciphertext an encrypted store
+    /// holds rather than its contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<SealedArtifact>,
+}
+
+/// An encrypted artifact as archived. `sha256` and `size` on its entry still
+/// describe the decrypted contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedArtifact {
+    /// SHA-256 of the ciphertext under `artifacts/<key>`
+    pub sha256: String,
+    pub size: u64,
+    /// SHA-256 of the wrapped data key under `artifacts/keys/<key>.key`
+    pub data_key_sha256: String,
+}
+
+/// What a backup wrote.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupReport {
+    pub archive: PathBuf,
+    pub archive_size: u64,
+    /// Artifacts the database refers to
+    pub artifacts: usize,
+    pub artifacts_included: usize,
+    /// Artifacts that should have been included but could not be read from
+    /// storage or did not match their checksum
+    pub artifacts_failed: usize,
+    /// Older archives deleted to stay within `retain`
+    pub pruned: usize,
+}
+
+/// What a restore did, or with `dry_run` would do.
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub created_at: DateTime<Utc>,
+    pub schema_version: i64,
+    pub artifacts: usize,
+    /// Artifacts copied from the archive into storage
+    pub artifacts_restored: usize,
+    /// Artifacts storage already held
+    pub artifacts_present: usize,
+    /// Artifacts neither in the archive nor in storage; the restored
+    /// database refers to them but they can't be downloaded
+    pub artifacts_missing: usize,
+}
+
+/// Writes a new archive to the backup directory, then deletes the oldest
+/// ones beyond `retain`. Runs alongside normal traffic: the database is
+/// snapshotted first, and artifacts are listed from the live database after
+/// that, so everything the snapshot refers to is covered.
+pub async fn create_backup(pool: &DbPool, storage: &Storage, config: &AppConfig) -> Result<BackupReport> {
+    let dir = Path::new(&config.backup.path);
+    fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("failed to create backup directory {}", dir.display()))?;
+    let work = WorkDir::create(dir).await?;
+
+    let created_at = Utc::now();
+    let backend = DbBackend::of(pool);
+    let schema_version = db::schema_version(pool).await?;
+
+    let db_file = format!("database/{}", database::snapshot_file_name(backend));
+    let db_path = work.path().join(&db_file);
+    fs::create_dir_all(work.path().join("database")).await?;
+    database::snapshot(pool, &config.database.url, &db_path).await?;
+    let (db_sha256, db_size) = hash_file(&db_path).await?;
+
+    // Reads go to the backend itself, not the cache
+    let storage = storage.uncached();
+    let mut artifacts = Vec::new();
+    let mut artifacts_failed = 0;
+    for (digest, size) in referenced_artifacts(pool).await? {
+        let key = blob_key(&digest);
+        let mut entry = ArtifactEntry { key, sha256: digest, size, included: false, sealed: None };
+
+        if config.backup.include_artifacts {
+            match include_artifact(&storage, &entry.sha256, &work.path().join("artifacts")).await {
+                Ok((size, sealed)) => {
+                    entry.size = size;
+                    entry.included = true;
+                    entry.sealed = sealed;
+                }
+                Err(e) => {
+                    tracing::warn!("Backing up without artifact {}: {:#}", entry.key, e);
+                    artifacts_failed += 1;
+                }
+            }
+        }
+        artifacts.push(entry);
+    }
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        created_at,
+        ghostcrate_version: env!("CARGO_PKG_VERSION").to_string(),
+        database: DatabaseEntry {
+            backend,
+            file: db_file,
+            sha256: db_sha256,
+            size: db_size,
+            schema_version,
+        },
+        artifacts,
+    };
+    fs::write(work.path().join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?).await?;
+
+    // Packed under a temporary name, so a finished archive is never partial
+    let name = format!("{}{}{}", ARCHIVE_PREFIX, created_at.format("%Y%m%dT%H%M%SZ"), ARCHIVE_SUFFIX);
+    let archive = dir.join(&name);
+    let partial = dir.join(format!("{}.partial", name));
+    let (source, dest) = (work.path().to_path_buf(), partial.clone());
+    if let Err(e) = tokio::task::spawn_blocking(move || write_archive(&source, &dest)).await? {
+        let _ = fs::remove_file(&partial).await;
+        return Err(e.context("failed to write backup archive"));
+    }
+    fs::rename(&partial, &archive).await?;
+    drop(work);
+
+    let pruned = prune_backups(dir, config.backup.retain).await?;
+
+    let report = BackupReport {
+        archive_size: fs::metadata(&archive).await?.len(),
+        archive,
+        artifacts: manifest.artifacts.len(),
+        artifacts_included: manifest.artifacts.iter().filter(|a| a.included).count(),
+        artifacts_failed,
+        pruned,
+    };
+    tracing::info!(
+        "Backup written to {} ({} bytes, {} of {} artifact(s) included)",
+        report.archive.display(),
+        report.archive_size,
+        report.artifacts_included,
+        report.artifacts
+    );
+    Ok(report)
+}
+
+/// Backs up every `interval_hours`. The first backup is taken one interval
+/// after startup, so restarts don't each leave an archive behind.
+pub fn spawn_backups(pool: DbPool, storage: Storage, config: AppConfig) {
+    let period = Duration::from_secs(config.backup.interval_hours.max(1) * 60 * 60);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = create_backup(&pool, &storage, &config).await {
+                tracing::error!("Scheduled backup failed: {:#}", e);
+            }
+        }
+    });
+}
+
+/// Checks an archive completely (manifest, database snapshot and every
+/// included artifact) and only then restores it: missing artifacts are put
+/// back into storage first, then the database is replaced and the local
+/// storage cache emptied. With `dry_run` nothing is changed. The server
+/// must not be running.
+pub async fn restore_backup(archive: &Path, storage: &Storage, config: &AppConfig, dry_run: bool) -> Result<RestoreReport> {
+    let dir = Path::new(&config.backup.path);
+    fs::create_dir_all(dir).await?;
+    let work = WorkDir::create(dir).await?;
+
+    let (source, dest) = (archive.to_path_buf(), work.path().to_path_buf());
+    tokio::task::spawn_blocking(move || unpack_archive(&source, &dest))
+        .await?
+        .with_context(|| format!("failed to unpack {}", archive.display()))?;
+
+    let manifest = read_manifest(work.path()).await?;
+    validate(&manifest, work.path(), config).await?;
+
+    let storage = storage.uncached();
+    let mut report = RestoreReport {
+        created_at: manifest.created_at,
+        schema_version: manifest.database.schema_version,
+        artifacts: manifest.artifacts.len(),
+        artifacts_restored: 0,
+        artifacts_present: 0,
+        artifacts_missing: 0,
+    };
+
+    for entry in &manifest.artifacts {
+        if storage.blob_exists(&entry.sha256).await {
+            report.artifacts_present += 1;
+        } else if !entry.included {
+            tracing::warn!("Artifact {} is neither in the backup nor in storage", entry.key);
+            report.artifacts_missing += 1;
+        } else if dry_run {
+            report.artifacts_restored += 1;
+        } else {
+            restore_artifact(&storage, entry, &work.path().join("artifacts"))
+                .await
+                .with_context(|| format!("failed to restore artifact {}", entry.key))?;
+            report.artifacts_restored += 1;
+        }
+    }
+
+    if !dry_run {
+        database::restore_snapshot(&config.database.url, &work.path().join(&manifest.database.file)).await?;
+
+        // The cache may hold blobs the restored storage doesn't
+        let cleared = clear_cache_dir(Path::new(&config.storage_cache.path))
+            .await
+            .context("failed to empty the storage cache")?;
+        if cleared > 0 {
+            tracing::info!("Emptied the storage cache of {} blob(s)", cleared);
+        }
+        tracing::info!("Restored backup taken at {}", manifest.created_at);
+    }
+
+    Ok(report)
+}
+
+/// Archive file names in `dir`, oldest first. Names embed the creation
+/// time, so they sort chronologically.
+pub async fn list_backups(dir: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+        Err(e) => return Err(e.into()),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_SUFFIX) {
+            names.push(name);
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+/// Deletes all but the newest `retain` archives; 0 keeps them all.
+async fn prune_backups(dir: &Path, retain: usize) -> Result<usize> {
+    if retain == 0 {
+        return Ok(0);
+    }
+
+    let names = list_backups(dir).await?;
+    let excess = names.len().saturating_sub(retain);
+    for name in &names[..excess] {
+        fs::remove_file(dir.join(name)).await?;
+        tracing::info!("Deleted old backup {}", name);
+    }
+
+    Ok(excess)
+}
+
+/// Digest and recorded size of every artifact the database refers to, each
+/// listed once even when several versions share it.
+async fn referenced_artifacts(pool: &DbPool) -> Result<BTreeMap<String, u64>> {
+    let mut artifacts = BTreeMap::new();
+    for artifact in db::get_stored_artifacts(pool).await? {
+        artifacts.insert(artifact.checksum, artifact.file_size as u64);
+    }
+    for artifact in db::get_mirror_artifacts(pool).await? {
+        artifacts.insert(artifact.checksum, artifact.file_size as u64);
+    }
+
+    Ok(artifacts)
+}
+
+/// Copies an artifact out of storage into `dir`, verifying it against its
+/// digest on the way, and returns its size. From encrypted storage the
+/// ciphertext and its wrapped data key are copied as stored; the decrypted
+/// contents are only hashed.
+async fn include_artifact(storage: &Storage, digest: &str, dir: &Path) -> Result<(u64, Option<SealedArtifact>)> {
+    let key = blob_key(digest);
+    let mut artifact = storage
+        .open_blob(digest)
+        .await?
+        .ok_or_else(|| anyhow!("not found in storage"))?;
+
+    let wrapped_key = match storage.sealed_store() {
+        Some(sealed_store) => read_object(sealed_store.as_ref(), &data_key_path(&key)).await?,
+        None => None,
+    };
+    let (Some(sealed_store), Some(wrapped_key)) = (storage.sealed_store(), wrapped_key) else {
+        // Not encrypted, or written before encryption was enabled
+        let (actual, size) = write_file(&mut artifact.reader, &dir.join(&key)).await?;
+        if actual != digest {
+            fs::remove_file(dir.join(&key)).await?;
+            bail!("contents hash to {}", actual);
+        }
+        return Ok((size, None));
+    };
+
+    let (actual, size) = sha256_stream(&mut artifact.reader).await?;
+    if actual != digest {
+        bail!("contents hash to {}", actual);
+    }
+
+    let mut ciphertext = sealed_store
+        .get(&key)
+        .await?
+        .ok_or_else(|| anyhow!("not found in storage"))?;
+    let (sha256, sealed_size) = write_file(&mut ciphertext.reader, &dir.join(&key)).await?;
+
+    let key_dest = dir.join(data_key_path(&key));
+    if let Some(parent) = key_dest.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&key_dest, &wrapped_key).await?;
+
+    Ok((size, Some(SealedArtifact { sha256, size: sealed_size, data_key_sha256: sha256_hex(&wrapped_key) })))
+}
+
+/// Puts an archived artifact back into storage. An encrypted one goes into
+/// the store below encryption as it was archived, data key first, and is
+/// then read back to check the configured master keys can decrypt it.
+async fn restore_artifact(storage: &Storage, entry: &ArtifactEntry, dir: &Path) -> Result<()> {
+    let path = dir.join(&entry.key);
+    let (Some(_), Some(sealed_store)) = (&entry.sealed, storage.sealed_store()) else {
+        return storage.store().put_file(&entry.key, &path).await;
+    };
+
+    sealed_store.put_file(&data_key_path(&entry.key), &dir.join(data_key_path(&entry.key))).await?;
+    sealed_store.put_file(&entry.key, &path).await?;
+
+    let read_back = async {
+        let mut artifact = storage
+            .open_blob(&entry.sha256)
+            .await?
+            .ok_or_else(|| anyhow!("not found in storage"))?;
+        let (actual, _) = sha256_stream(&mut artifact.reader).await?;
+        if actual != entry.sha256 {
+            bail!("contents hash to {}", actual);
+        }
+        Ok(())
+    };
+    if let Err(e) = read_back.await {
+        // Removed again, so a later restore doesn't take it for present
+        storage.store().delete(&entry.key).await?;
+        return Err(e.context("the restored artifact can't be read back; is the master key that wrapped it configured?"));
+    }
+
+    Ok(())
+}
+
+/// Reads a small object, such as a wrapped data key, whole.
+async fn read_object(store: &dyn ArtifactStore, key: &str) -> Result<Option<Vec<u8>>> {
+    let Some(mut object) = store.get(key).await? else { return Ok(None) };
+    let mut data = Vec::new();
+    object.reader.read_to_end(&mut data).await?;
+    Ok(Some(data))
+}
+
+/// Writes a stream to a new file, returning the digest and size of what
+/// was written.
+async fn write_file(reader: &mut (dyn AsyncRead + Send + Unpin), dest: &Path) -> Result<(String, u64)> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let mut file = fs::File::create(dest).await?;
+
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        file.write_all(&buf[..read]).await?;
+        size += read as u64;
+    }
+    file.flush().await?;
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+async fn hash_file(path: &Path) -> Result<(String, u64)> {
+    let mut file = fs::File::open(path).await?;
+    sha256_stream(&mut file).await
+}
+
+async fn read_manifest(dir: &Path) -> Result<BackupManifest> {
+    let data = fs::read(dir.join(MANIFEST_FILE))
+        .await
+        .context("the archive has no manifest; is it a GhostCrate backup?")?;
+    serde_json::from_slice(&data).context("the archive manifest is not valid")
+}
+
+/// Everything a restore relies on, checked before anything is changed.
+async fn validate(manifest: &BackupManifest, dir: &Path, config: &AppConfig) -> Result<()> {
+    if !(1..=FORMAT_VERSION).contains(&manifest.format_version) {
+        bail!(
+            "the archive has format version {}, but this build reads versions up to {}",
+            manifest.format_version, FORMAT_VERSION
+        );
+    }
+
+    let backend = DbBackend::of_url(&config.database.url);
+    if manifest.database.backend != backend {
+        bail!(
+            "the archive holds a {:?} database, but DATABASE_URL points at {:?}",
+            manifest.database.backend, backend
+        );
+    }
+
+    if manifest.database.schema_version > db::latest_schema_version() {
+        bail!(
+            "the archive is at schema version {}, which this build does not know; restore it with the release that took it ({})",
+            manifest.database.schema_version, manifest.ghostcrate_version
+        );
+    }
+
+    // Paths come from the manifest, so only the expected ones are accepted
+    if manifest.database.file != format!("database/{}", database::snapshot_file_name(backend)) {
+        bail!("unexpected database file {} in the manifest", manifest.database.file);
+    }
+    let db_path = dir.join(&manifest.database.file);
+    let (sha256, size) = hash_file(&db_path)
+        .await
+        .context("the database snapshot is missing from the archive")?;
+    if sha256 != manifest.database.sha256 || size != manifest.database.size {
+        bail!("the database snapshot does not match the checksum in the manifest");
+    }
+    database::verify_snapshot(backend, &db_path, manifest.database.schema_version).await?;
+
+    for entry in manifest.artifacts.iter().filter(|entry| entry.included) {
+        if entry.key != blob_key(&entry.sha256) {
+            bail!("unexpected artifact key {} in the manifest", entry.key);
+        }
+        let (sha256, size) = hash_file(&dir.join("artifacts").join(&entry.key))
+            .await
+            .with_context(|| format!("artifact {} is missing from the archive", entry.key))?;
+        let expected = match &entry.sealed {
+            Some(sealed) => (&sealed.sha256, sealed.size),
+            None => (&entry.sha256, entry.size),
+        };
+        if (&sha256, size) != expected {
+            bail!("artifact {} does not match the checksum in the manifest", entry.key);
+        }
+
+        if let Some(sealed) = &entry.sealed {
+            if !config.storage_encryption.enabled {
+                bail!("artifact {} is encrypted; enable storage encryption with the master key that wrapped it", entry.key);
+            }
+            let (sha256, _) = hash_file(&dir.join("artifacts").join(data_key_path(&entry.key)))
+                .await
+                .with_context(|| format!("the data key of artifact {} is missing from the archive", entry.key))?;
+            if sha256 != sealed.data_key_sha256 {
+                bail!("the data key of artifact {} does not match the checksum in the manifest", entry.key);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Packs a prepared backup directory. The manifest goes first, so it can be
+/// read without unpacking everything else.
+fn write_archive(source: &Path, dest: &Path) -> Result<()> {
+    let file = std::fs::File::create(dest)?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    archive.append_path_with_name(source.join(MANIFEST_FILE), MANIFEST_FILE)?;
+    archive.append_dir_all("database", source.join("database"))?;
+    if source.join("artifacts").exists() {
+        archive.append_dir_all("artifacts", source.join("artifacts"))?;
+    }
+
+    archive.into_inner()?.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Unpacks an archive. Entries that would land outside `dest` are skipped
+/// by the `tar` crate.
+fn unpack_archive(source: &Path, dest: &Path) -> Result<()> {
+    let file = std::fs::File::open(source)?;
+    tar::Archive::new(GzDecoder::new(file)).unpack(dest)?;
+    Ok(())
+}
+
+/// A scratch directory next to the archives, removed with everything in it
+/// when dropped.
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    async fn create(parent: &Path) -> Result<Self> {
+        let path = parent.join(format!(".work-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&path).await?;
+        Ok(Self(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use crate::config::MasterKeyConfig;
+    use crate::models::PublishRequest;
+
+    const CRATE_DATA: &[u8] = b"not really a .crate file, but it has a digest";
+
+    /// A registry under `root`; registries in the same root share the
+    /// backup directory.
+    fn registry_config(root: &Path, name: &str, encrypted: bool) -> AppConfig {
+        let mut config = AppConfig::default();
+        config.database.url = format!("sqlite:{}?mode=rwc", root.join(format!("{}.db", name)).display());
+        config.storage.local_path = root.join(format!("{}-storage", name)).display().to_string();
+        config.storage_cache.path = root.join(format!("{}-cache", name)).display().to_string();
+        config.backup.path = root.join("backups").display().to_string();
+        config.storage_encryption.enabled = encrypted;
+        config.storage_encryption.master_key = Some(MasterKeyConfig {
+            id: "test".to_string(),
+            key: Some(BASE64.encode([7u8; 32])),
+            key_file: None,
+        });
+        config
+    }
+
+    async fn open_storage(config: &AppConfig) -> Storage {
+        Storage::new(config.storage.clone())
+            .await
+            .unwrap()
+            .with_encryption(&config.storage_encryption)
+            .await
+            .unwrap()
+    }
+
+    /// Publishes one crate, returning the digest of its artifact.
+    async fn seed(pool: &DbPool, storage: &Storage) -> String {
+        let user = db::create_user(pool, "alice", "alice@example.com", "not-a-hash").await.unwrap();
+        let request: PublishRequest = serde_json::from_value(serde_json::json!({
+            "name": "backed-up", "vers": "1.0.0", "deps": [], "features": {}, "authors": [],
+            "keywords": [], "categories": [], "badges": {},
+        }))
+        .unwrap();
+
+        let digest = storage.store_blob(CRATE_DATA).await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        let crate_model = db::create_crate(&mut tx, &request, user.id).await.unwrap();
+        db::create_crate_version(&mut *tx, crate_model.id, &request, &digest, CRATE_DATA.len() as i64, user.id)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        digest
+    }
+
+    async fn read_blob(storage: &Storage, digest: &str) -> Option<Vec<u8>> {
+        let mut artifact = storage.open_blob(digest).await.unwrap()?;
+        let mut data = Vec::new();
+        artifact.reader.read_to_end(&mut data).await.unwrap();
+        Some(data)
+    }
+
+    fn temp_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("ghostcrate-backup-test-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[tokio::test]
+    async fn restores_what_it_backed_up() {
+        for encrypted in [false, true] {
+            let root = temp_root();
+            let source = registry_config(&root, "source", encrypted);
+            let pool = db::initialize_database(&source.database.url).await.unwrap();
+            let storage = open_storage(&source).await;
+            let digest = seed(&pool, &storage).await;
+
+            let report = create_backup(&pool, &storage, &source).await.unwrap();
+            assert_eq!((report.artifacts, report.artifacts_included, report.artifacts_failed), (1, 1, 0));
+
+            // Encrypted storage is archived as ciphertext, next to its data key
+            let unpacked = root.join("unpacked");
+            unpack_archive(&report.archive, &unpacked).unwrap();
+            let key = blob_key(&digest);
+            let archived = std::fs::read(unpacked.join("artifacts").join(&key)).unwrap();
+            assert_eq!(archived == CRATE_DATA, !encrypted, "encrypted: {}", encrypted);
+            assert_eq!(unpacked.join("artifacts").join(data_key_path(&key)).exists(), encrypted);
+
+            let target = registry_config(&root, "target", encrypted);
+            let stale = Path::new(&target.storage_cache.path).join(&digest);
+            fs::create_dir_all(stale.parent().unwrap()).await.unwrap();
+            fs::write(&stale, b"stale").await.unwrap();
+
+            let target_storage = open_storage(&target).await;
+            let restored = restore_backup(&report.archive, &target_storage, &target, false).await.unwrap();
+            assert_eq!((restored.artifacts_restored, restored.artifacts_missing), (1, 0));
+
+            assert_eq!(read_blob(&target_storage, &digest).await.as_deref(), Some(CRATE_DATA));
+            let target_pool = db::connect_database(&target.database.url).await.unwrap();
+            assert!(db::get_crate_by_name(&target_pool, "backed-up").await.unwrap().is_some());
+            assert!(!stale.exists());
+
+            pool.close().await;
+            target_pool.close().await;
+            let _ = fs::remove_dir_all(&root).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_archives() {
+        let root = temp_root();
+        let source = registry_config(&root, "source", false);
+        let pool = db::initialize_database(&source.database.url).await.unwrap();
+        let storage = open_storage(&source).await;
+        let digest = seed(&pool, &storage).await;
+        let report = create_backup(&pool, &storage, &source).await.unwrap();
+
+        let unpacked = root.join("unpacked");
+        unpack_archive(&report.archive, &unpacked).unwrap();
+        std::fs::write(unpacked.join("artifacts").join(blob_key(&digest)), b"tampered").unwrap();
+        let tampered = root.join("tampered.tar.gz");
+        write_archive(&unpacked, &tampered).unwrap();
+
+        let target = registry_config(&root, "target", false);
+        let target_storage = open_storage(&target).await;
+        let error = restore_backup(&tampered, &target_storage, &target, false).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("does not match the checksum"), "{:#}", error);
+
+        // Nothing was restored
+        assert!(!target_storage.blob_exists(&digest).await);
+        assert!(!root.join("target.db").exists());
+
+        // Nor can encrypted artifacts be restored without encryption
+        let encrypted_source = registry_config(&root, "encrypted", true);
+        let encrypted_pool = db::initialize_database(&encrypted_source.database.url).await.unwrap();
+        let encrypted_storage = open_storage(&encrypted_source).await;
+        seed(&encrypted_pool, &encrypted_storage).await;
+        let report = create_backup(&encrypted_pool, &encrypted_storage, &encrypted_source).await.unwrap();
+
+        let error = restore_backup(&report.archive, &target_storage, &target, true).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("enable storage encryption"), "{:#}", error);
+
+        pool.close().await;
+        encrypted_pool.close().await;
+        let _ = fs::remove_dir_all(&root).await;
+    }
+}
@@ -8,6 +8,21 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
     pub storage: StorageConfig,
+    /// Backend that `migrate-storage` copies artifacts into
+    #[serde(default)]
+    pub storage_migration_target: Option<StorageConfig>,
+    #[serde(default)]
+    pub integrity: IntegrityConfig,
+    #[serde(default)]
+    pub storage_gc: GcConfig,
+    #[serde(default)]
+    pub storage_cache: CacheConfig,
+    #[serde(default)]
+    pub storage_encryption: EncryptionConfig,
+    #[serde(default)]
+    pub storage_replication: ReplicationConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
     pub auth: AuthConfig,
     pub github: GitHubConfig,
     pub registry: RegistryConfig,
@@ -25,6 +40,7 @@ pub struct ServerConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
+    /// `sqlite:` or `postgres://` URL; the scheme picks the database backend
     pub url: String,
     pub max_connections: u32,
     pub min_connections: u32,
@@ -54,6 +70,159 @@ pub struct S3Config {
     pub path_style: bool, // For MinIO - should be true
     pub use_ssl: bool,    // Whether to use HTTPS
     pub public_url: Option<String>, // For MinIO public access
+    /// Send downloads a 302 to a presigned URL instead of streaming them
+    #[serde(default)]
+    pub download_redirect: bool,
+    /// How long presigned download URLs stay valid
+    #[serde(default = "default_presign_expiry_secs")]
+    pub presign_expiry_secs: u64,
+}
+
+fn default_presign_expiry_secs() -> u64 {
+    300
+}
+
+/// Background re-verification of stored artifacts against their checksums.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityConfig {
+    pub scrub_enabled: bool,
+    pub scrub_interval_hours: u64,
+    /// Re-download mirrored crates that fail verification from upstream
+    pub repair_mirrored: bool,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            scrub_enabled: true,
+            scrub_interval_hours: 24,
+            repair_mirrored: false,
+        }
+    }
+}
+
+/// Removal of stored artifacts that no database row refers to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcConfig {
+    /// Run the collector on a schedule; it can always be started from the admin API
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// Artifacts modified more recently than this are never collected, so
+    /// publishes in flight are left alone
+    pub grace_period_hours: u64,
+    /// Move orphans to `quarantine/` instead of deleting them
+    pub dry_run: bool,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            grace_period_hours: 24,
+            dry_run: true,
+        }
+    }
+}
+
+/// Local disk cache in front of remote storage. With storage encryption
+/// enabled it holds decrypted crates, so `path` needs the same protection as
+/// the encryption key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub path: String,
+    /// Least recently used crates are evicted once the cache grows past this
+    pub max_bytes: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "./data/cache".to_string(),
+            max_bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Asynchronous copying of every stored artifact to a second backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    pub enabled: bool,
+    /// Backend the replica is kept in, configured like the main one
+    pub target: Option<StorageConfig>,
+    /// Attempts per change before it is given up until the next reconciliation
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further one
+    pub retry_delay_secs: u64,
+    /// How often the replica is compared with the primary to catch up on
+    /// anything missed, e.g. across restarts
+    pub reconcile_interval_hours: u64,
+    /// Read from the replica when the primary returns an error
+    pub failover_reads: bool,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: None,
+            max_attempts: 5,
+            retry_delay_secs: 30,
+            reconcile_interval_hours: 24,
+            failover_reads: true,
+        }
+    }
+}
+
+/// Archives of the database and artifacts, taken on a schedule or with
+/// `server backup`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConfig {
+    /// Take backups on a schedule; `server backup` always works
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// Directory archives are written to. Keep it off the volume being backed up.
+    pub path: String,
+    /// Copy the artifacts themselves into the archive, not just their checksums
+    pub include_artifacts: bool,
+    /// Newest archives kept after each backup; 0 keeps them all
+    pub retain: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            path: "./backups".to_string(),
+            include_artifacts: true,
+            retain: 7,
+        }
+    }
+}
+
+/// Envelope encryption of stored artifacts. Keys are 32 random bytes,
+/// base64-encoded, e.g. from `openssl rand -base64 32`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    pub enabled: bool,
+    /// Wraps the data key of every artifact written from now on
+    pub master_key: Option<MasterKeyConfig>,
+    /// Keys `master_key` replaced, still used to unwrap data keys until
+    /// `rotate-storage-keys` has re-wrapped them
+    #[serde(default)]
+    pub previous_master_keys: Vec<MasterKeyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MasterKeyConfig {
+    /// Recorded with every data key this key wraps
+    pub id: String,
+    pub key: Option<String>,
+    /// File holding the key, read when `key` is unset
+    pub key_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,8 +254,30 @@ pub struct RegistryConfig {
     pub url: String,
     pub description: String,
     pub crates_io_mirror: CratesIoMirrorConfig,
+    pub git_index: GitIndexConfig,
     pub organizations_enabled: bool,
     pub public_registration: bool,
+    /// Largest `.crate` file accepted on publish, checked before it is read
+    #[serde(default = "default_max_crate_size")]
+    pub max_crate_size: u64,
+    /// Whether index and download requests need a token. Advertised to Cargo
+    /// as `auth-required` in config.json.
+    #[serde(default = "default_auth_required")]
+    pub auth_required: bool,
+}
+
+fn default_max_crate_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_auth_required() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitIndexConfig {
+    pub enabled: bool,
+    pub path: String, // Holds the bare repository and its work tree
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -124,6 +315,13 @@ impl Default for AppConfig {
                 local_path: "./data".to_string(),
                 s3: None,
             },
+            storage_migration_target: None,
+            integrity: IntegrityConfig::default(),
+            storage_gc: GcConfig::default(),
+            storage_cache: CacheConfig::default(),
+            storage_encryption: EncryptionConfig::default(),
+            storage_replication: ReplicationConfig::default(),
+            backup: BackupConfig::default(),
             auth: AuthConfig {
                 jwt_secret: env::var("GHOSTCRATE_AUTH_JWT_SECRET")
                     .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string()),
@@ -151,8 +349,14 @@ impl Default for AppConfig {
                     sync_interval_hours: 24,
                     cache_duration_hours: 6,
                 },
+                git_index: GitIndexConfig {
+                    enabled: false,
+                    path: "./data/git-index".to_string(),
+                },
                 organizations_enabled: true,
                 public_registration: true,
+                max_crate_size: default_max_crate_size(),
+                auth_required: default_auth_required(),
             },
             monitoring: MonitoringConfig {
                 metrics_enabled: true,
@@ -163,6 +367,55 @@ impl Default for AppConfig {
     }
 }
 
+impl StorageConfig {
+    /// Overrides the backend settings from `{prefix}STORAGE_BACKEND`,
+    /// `{prefix}STORAGE_LOCAL_PATH` and the `{prefix}S3_*` variables.
+    fn apply_env(&mut self, prefix: &str) -> Result<()> {
+        let var = |name: &str| env::var(format!("{}{}", prefix, name));
+
+        if let Ok(backend) = var("STORAGE_BACKEND") {
+            self.backend = match backend.to_lowercase().as_str() {
+                "s3" => StorageBackend::S3,
+                _ => StorageBackend::Local,
+            };
+        }
+
+        if let Ok(path) = var("STORAGE_LOCAL_PATH") {
+            self.local_path = path;
+        }
+
+        // S3 configuration
+        if self.backend == StorageBackend::S3 {
+            self.s3 = Some(S3Config {
+                bucket: var("S3_BUCKET")?,
+                region: var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                endpoint: var("S3_ENDPOINT").ok(),
+                access_key: var("S3_ACCESS_KEY")?,
+                secret_key: var("S3_SECRET_KEY")?,
+                path_style: var("S3_PATH_STYLE").unwrap_or_else(|_| "true".to_string()).parse().unwrap_or(true), // Default true for MinIO
+                use_ssl: var("S3_USE_SSL").unwrap_or_else(|_| "true".to_string()).parse().unwrap_or(true),
+                public_url: var("S3_PUBLIC_URL").ok(),
+                download_redirect: var("S3_DOWNLOAD_REDIRECT").unwrap_or_else(|_| "false".to_string()).parse().unwrap_or(false),
+                presign_expiry_secs: var("S3_PRESIGN_EXPIRY_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or_else(default_presign_expiry_secs),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Identifies where artifacts end up, e.g. `local:./data` or
+    /// `s3://bucket@endpoint`, so two configs can be told apart.
+    pub fn location(&self) -> String {
+        match (&self.backend, &self.s3) {
+            (StorageBackend::S3, Some(s3)) => match &s3.endpoint {
+                Some(endpoint) => format!("s3://{}@{}", s3.bucket, endpoint),
+                None => format!("s3://{}@{}", s3.bucket, s3.region),
+            },
+            _ => format!("local:{}", self.local_path),
+        }
+    }
+}
+
 impl AppConfig {
     pub fn from_env() -> Result<Self> {
         let mut config = Self::default();
@@ -184,30 +437,125 @@ impl AppConfig {
         }
 
         // Storage configuration
-        if let Ok(backend) = env::var("STORAGE_BACKEND") {
-            config.storage.backend = match backend.to_lowercase().as_str() {
-                "s3" => StorageBackend::S3,
-                _ => StorageBackend::Local,
+        config.storage.apply_env("")?;
+
+        // Destination for `migrate-storage`, configured like the main backend
+        if env::var("MIGRATION_TARGET_STORAGE_BACKEND").is_ok() {
+            let mut target = StorageConfig {
+                backend: StorageBackend::Local,
+                local_path: "./data-migrated".to_string(),
+                s3: None,
             };
+            target.apply_env("MIGRATION_TARGET_")?;
+            config.storage_migration_target = Some(target);
         }
 
-        if let Ok(path) = env::var("STORAGE_LOCAL_PATH") {
-            config.storage.local_path = path;
+        // Integrity scrubber configuration
+        if let Ok(enabled) = env::var("INTEGRITY_SCRUB_ENABLED") {
+            config.integrity.scrub_enabled = enabled.parse().unwrap_or(true);
+        }
+        if let Ok(hours) = env::var("INTEGRITY_SCRUB_INTERVAL_HOURS") {
+            config.integrity.scrub_interval_hours = hours.parse()?;
+        }
+        if let Ok(repair) = env::var("INTEGRITY_REPAIR_MIRRORED") {
+            config.integrity.repair_mirrored = repair.parse().unwrap_or(false);
         }
 
-        // S3 configuration
-        if config.storage.backend == StorageBackend::S3 {
-            config.storage.s3 = Some(S3Config {
-                bucket: env::var("S3_BUCKET")?,
-                region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
-                endpoint: env::var("S3_ENDPOINT").ok(),
-                access_key: env::var("S3_ACCESS_KEY")?,
-                secret_key: env::var("S3_SECRET_KEY")?,
-                path_style: env::var("S3_PATH_STYLE").unwrap_or_else(|_| "true".to_string()).parse().unwrap_or(true), // Default true for MinIO
-                use_ssl: env::var("S3_USE_SSL").unwrap_or_else(|_| "true".to_string()).parse().unwrap_or(true),
-                public_url: env::var("S3_PUBLIC_URL").ok(),
+        // Orphan artifact collection configuration
+        if let Ok(enabled) = env::var("STORAGE_GC_ENABLED") {
+            config.storage_gc.enabled = enabled.parse().unwrap_or(false);
+        }
+        if let Ok(hours) = env::var("STORAGE_GC_INTERVAL_HOURS") {
+            config.storage_gc.interval_hours = hours.parse()?;
+        }
+        if let Ok(hours) = env::var("STORAGE_GC_GRACE_PERIOD_HOURS") {
+            config.storage_gc.grace_period_hours = hours.parse()?;
+        }
+        if let Ok(dry_run) = env::var("STORAGE_GC_DRY_RUN") {
+            config.storage_gc.dry_run = dry_run.parse().unwrap_or(true);
+        }
+
+        // Storage cache configuration
+        if let Ok(enabled) = env::var("STORAGE_CACHE_ENABLED") {
+            config.storage_cache.enabled = enabled.parse().unwrap_or(false);
+        }
+        if let Ok(path) = env::var("STORAGE_CACHE_PATH") {
+            config.storage_cache.path = path;
+        }
+        if let Ok(max_bytes) = env::var("STORAGE_CACHE_MAX_BYTES") {
+            config.storage_cache.max_bytes = max_bytes.parse()?;
+        }
+
+        // Storage replication configuration
+        if let Ok(enabled) = env::var("STORAGE_REPLICATION_ENABLED") {
+            config.storage_replication.enabled = enabled.parse().unwrap_or(false);
+        }
+        if env::var("REPLICA_STORAGE_BACKEND").is_ok() {
+            let mut target = StorageConfig {
+                backend: StorageBackend::Local,
+                local_path: "./data-replica".to_string(),
+                s3: None,
+            };
+            target.apply_env("REPLICA_")?;
+            config.storage_replication.target = Some(target);
+        }
+        if let Ok(attempts) = env::var("STORAGE_REPLICATION_MAX_ATTEMPTS") {
+            config.storage_replication.max_attempts = attempts.parse()?;
+        }
+        if let Ok(secs) = env::var("STORAGE_REPLICATION_RETRY_DELAY_SECS") {
+            config.storage_replication.retry_delay_secs = secs.parse()?;
+        }
+        if let Ok(hours) = env::var("STORAGE_REPLICATION_RECONCILE_INTERVAL_HOURS") {
+            config.storage_replication.reconcile_interval_hours = hours.parse()?;
+        }
+        if let Ok(failover) = env::var("STORAGE_REPLICATION_FAILOVER_READS") {
+            config.storage_replication.failover_reads = failover.parse().unwrap_or(true);
+        }
+
+        // Storage encryption configuration
+        if let Ok(enabled) = env::var("STORAGE_ENCRYPTION_ENABLED") {
+            config.storage_encryption.enabled = enabled.parse().unwrap_or(false);
+        }
+        let key = env::var("STORAGE_ENCRYPTION_KEY").ok();
+        let key_file = env::var("STORAGE_ENCRYPTION_KEY_FILE").ok();
+        if key.is_some() || key_file.is_some() {
+            config.storage_encryption.master_key = Some(MasterKeyConfig {
+                id: env::var("STORAGE_ENCRYPTION_KEY_ID").unwrap_or_else(|_| "primary".to_string()),
+                key,
+                key_file,
             });
         }
+        // Comma-separated `id=key` and `id=path` pairs
+        for (name, is_file) in [("STORAGE_ENCRYPTION_PREVIOUS_KEYS", false), ("STORAGE_ENCRYPTION_PREVIOUS_KEY_FILES", true)] {
+            let Ok(value) = env::var(name) else { continue };
+            for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+                let (id, key) = entry
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("{} entries must look like id=key", name))?;
+                config.storage_encryption.previous_master_keys.push(MasterKeyConfig {
+                    id: id.to_string(),
+                    key: (!is_file).then(|| key.to_string()),
+                    key_file: is_file.then(|| key.to_string()),
+                });
+            }
+        }
+
+        // Backup configuration
+        if let Ok(enabled) = env::var("BACKUP_ENABLED") {
+            config.backup.enabled = enabled.parse().unwrap_or(false);
+        }
+        if let Ok(hours) = env::var("BACKUP_INTERVAL_HOURS") {
+            config.backup.interval_hours = hours.parse()?;
+        }
+        if let Ok(path) = env::var("BACKUP_PATH") {
+            config.backup.path = path;
+        }
+        if let Ok(include) = env::var("BACKUP_INCLUDE_ARTIFACTS") {
+            config.backup.include_artifacts = include.parse().unwrap_or(true);
+        }
+        if let Ok(retain) = env::var("BACKUP_RETAIN") {
+            config.backup.retain = retain.parse()?;
+        }
 
         // Auth configuration
         if let Ok(secret) = env::var("JWT_SECRET") {
@@ -240,11 +588,28 @@ impl AppConfig {
         if let Ok(description) = env::var("REGISTRY_DESCRIPTION") {
             config.registry.description = description;
         }
+        if let Ok(max_crate_size) = env::var("REGISTRY_MAX_CRATE_SIZE") {
+            config.registry.max_crate_size = max_crate_size.parse()?;
+        }
+        if let Ok(auth_required) = env::var("REGISTRY_AUTH_REQUIRED") {
+            config.registry.auth_required = auth_required.parse()?;
+        }
 
         // Crates.io mirror configuration
         if let Ok(enabled) = env::var("CRATESIO_MIRROR_ENABLED") {
             config.registry.crates_io_mirror.enabled = enabled.parse().unwrap_or(false);
         }
+        if let Ok(url) = env::var("CRATESIO_MIRROR_UPSTREAM_URL") {
+            config.registry.crates_io_mirror.upstream_url = url;
+        }
+
+        // Git index configuration
+        if let Ok(enabled) = env::var("GIT_INDEX_ENABLED") {
+            config.registry.git_index.enabled = enabled.parse().unwrap_or(false);
+        }
+        if let Ok(path) = env::var("GIT_INDEX_PATH") {
+            config.registry.git_index.path = path;
+        }
 
         Ok(config)
     }
@@ -0,0 +1,231 @@
+// Versioned schema migrations for db/mod.rs
+//
+// Migrations are the SQL scripts in `migrations/`, embedded at build time and
+// applied in version order. Where the dialects differ, a migration has one
+// script per backend (`NNNN_name.sqlite.sql` and `NNNN_name.postgres.sql`).
+// Each applied version is recorded in `schema_migrations` together with a
+// checksum of its script, so a script edited after release is reported
+// instead of silently diverging.
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use std::collections::HashMap;
+
+use super::{DbBackend, DbPool};
+
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    sqlite: &'static str,
+    postgres: &'static str,
+}
+
+impl Migration {
+    /// A migration whose script runs unchanged on both backends.
+    const fn shared(version: i64, description: &'static str, sql: &'static str) -> Self {
+        Self { version, description, sqlite: sql, postgres: sql }
+    }
+
+    fn sql(&self, backend: DbBackend) -> &'static str {
+        match backend {
+            DbBackend::Sqlite => self.sqlite,
+            DbBackend::Postgres => self.postgres,
+        }
+    }
+
+    fn checksum(&self, backend: DbBackend) -> String {
+        format!("{:x}", Sha256::digest(self.sql(backend).as_bytes()))
+    }
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration::shared(1, "initial schema", include_str!("../../migrations/0001_initial_schema.sql")),
+    Migration::shared(2, "oidc user links", include_str!("../../migrations/0002_oidc_user_links.sql")),
+    Migration::shared(3, "performance indexes", include_str!("../../migrations/0003_performance_indexes.sql")),
+    Migration {
+        version: 4,
+        description: "crate search index",
+        sqlite: include_str!("../../migrations/0004_crate_search.sqlite.sql"),
+        postgres: include_str!("../../migrations/0004_crate_search.postgres.sql"),
+    },
+    Migration::shared(5, "integrity issues by origin", include_str!("../../migrations/0005_integrity_issues_by_origin.sql")),
+];
+
+/// Columns that were added to `CREATE TABLE` statements in place before
+/// migrations were versioned. Databases created back then may lack them, so
+/// they are backfilled before the baseline migration is recorded.
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("crate_versions", "links", "TEXT"),
+    ("crate_versions", "rust_version", "TEXT"),
+    ("crate_versions", "authors", "TEXT"),
+    ("crate_versions", "published_by", "TEXT"),
+];
+
+/// The newest schema version this build knows how to run against.
+pub fn latest_schema_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// The newest migration applied to the database, or 0 for a fresh one.
+pub async fn schema_version(pool: &DbPool) -> Result<i64> {
+    if !table_exists(pool, "schema_migrations").await? {
+        return Ok(0);
+    }
+
+    let version: i64 = sqlx::query_scalar("SELECT CAST(COALESCE(MAX(version), 0) AS BIGINT) FROM schema_migrations")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(version)
+}
+
+/// Migrations not yet applied to the database, in the order they would run.
+/// Reads only, so it is safe to call for a dry run.
+pub async fn pending_migrations(pool: &DbPool) -> Result<Vec<&'static Migration>> {
+    let applied = if table_exists(pool, "schema_migrations").await? {
+        applied_migrations(pool).await?
+    } else {
+        HashMap::new()
+    };
+
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|migration| !applied.contains_key(&migration.version))
+        .collect())
+}
+
+/// Applies every pending migration, each in its own transaction, and returns
+/// the ones that ran.
+pub async fn run_migrations(pool: &DbPool) -> Result<Vec<&'static Migration>> {
+    sqlx::raw_sql(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            description TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    let applied = applied_migrations(pool).await?;
+
+    if !applied.contains_key(&1) {
+        adopt_legacy_schema(pool).await?;
+    }
+
+    let backend = DbBackend::of(pool);
+    let mut ran = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| !applied.contains_key(&migration.version)) {
+        let mut tx = pool.begin().await?;
+
+        sqlx::raw_sql(migration.sql(backend))
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("migration {} ({}) failed", migration.version, migration.description))?;
+
+        sqlx::query(
+            "INSERT INTO schema_migrations (version, description, checksum, applied_at) VALUES ($1, $2, $3, $4)"
+        )
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(migration.checksum(backend))
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        tracing::info!("Applied migration {} ({})", migration.version, migration.description);
+        ran.push(migration);
+    }
+
+    Ok(ran)
+}
+
+/// Recorded migrations by version, with their checksums. Fails if the
+/// database was migrated by a newer build, and warns about scripts that
+/// changed after they were applied.
+async fn applied_migrations(pool: &DbPool) -> Result<HashMap<i64, String>> {
+    let rows = sqlx::query("SELECT version, checksum FROM schema_migrations")
+        .fetch_all(pool)
+        .await?;
+
+    let applied: HashMap<i64, String> = rows
+        .iter()
+        .map(|row| (row.get("version"), row.get("checksum")))
+        .collect();
+
+    for (version, checksum) in &applied {
+        match MIGRATIONS.iter().find(|migration| migration.version == *version) {
+            Some(migration) if migration.checksum(DbBackend::of(pool)) != *checksum => {
+                tracing::warn!(
+                    "Migration {} ({}) was changed after it was applied; the database keeps the original",
+                    migration.version, migration.description
+                );
+            }
+            Some(_) => {}
+            None => bail!(
+                "database has schema version {}, which this build does not know; refusing to run an older release against it",
+                version
+            ),
+        }
+    }
+
+    Ok(applied)
+}
+
+/// Brings a database created by the old inline `CREATE TABLE` setup up to the
+/// baseline schema, so the baseline migration only has to fill in what is
+/// missing.
+async fn adopt_legacy_schema(pool: &DbPool) -> Result<()> {
+    for (table, column, definition) in LEGACY_COLUMNS {
+        if table_exists(pool, table).await? {
+            ensure_column(pool, table, column, definition).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn table_exists(pool: &DbPool, table: &str) -> Result<bool> {
+    let query = match DbBackend::of(pool) {
+        DbBackend::Sqlite => "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = $1",
+        DbBackend::Postgres => {
+            "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = $1"
+        }
+    };
+    let count: i64 = sqlx::query_scalar(query)
+        .bind(table)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count > 0)
+}
+
+async fn ensure_column(pool: &DbPool, table: &str, column: &str, definition: &str) -> Result<()> {
+    let query = match DbBackend::of(pool) {
+        DbBackend::Sqlite => "SELECT COUNT(*) FROM pragma_table_info($1) WHERE name = $2",
+        DbBackend::Postgres => {
+            "SELECT COUNT(*) FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2"
+        }
+    };
+    let count: i64 = sqlx::query_scalar(query)
+        .bind(table)
+        .bind(column)
+        .fetch_one(pool)
+        .await?;
+
+    if count == 0 {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(pool)
+            .await?;
+        tracing::info!("Added missing column {}.{}", table, column);
+    }
+
+    Ok(())
+}
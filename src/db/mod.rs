@@ -1,216 +1,99 @@
-use sqlx::{SqlitePool, Row};
+use sqlx::{Any, AnyConnection, AnyPool, Executor, Row};
+use sqlx::any::AnyRow;
 use anyhow::Result;
 use uuid::Uuid;
 use chrono::Utc;
-use crate::models::{User, Session, Crate, CrateVersion, PublishRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::models::{User, Session, Crate, CrateVersion, DependentVersion, PublishRequest};
 
+mod migrations;
 mod organization_functions;
 mod oidc_functions;
+mod owner_functions;
+mod search_functions;
+mod storage_functions;
+mod token_functions;
+pub use migrations::*;
 pub use organization_functions::*;
 pub use oidc_functions::*;
+pub use owner_functions::*;
+pub use search_functions::*;
+pub use storage_functions::*;
+pub use token_functions::*;
 
-pub async fn initialize_database(database_url: &str) -> Result<SqlitePool> {
-    let pool = SqlitePool::connect(database_url).await?;
-    
-    // Create tables manually since we're not using migrations initially
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id TEXT PRIMARY KEY,
-            username TEXT UNIQUE NOT NULL,
-            email TEXT UNIQUE NOT NULL,
-            password_hash TEXT NOT NULL,
-            is_admin BOOLEAN NOT NULL DEFAULT FALSE,
-            github_id INTEGER,
-            github_username TEXT,
-            avatar_url TEXT,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_users_username ON users(username);
-        CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
-        CREATE INDEX IF NOT EXISTS idx_users_github_id ON users(github_id);
-        "#
-    )
-    .execute(&pool)
-    .await?;
-    
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS sessions (
-            id TEXT PRIMARY KEY,
-            user_id TEXT NOT NULL,
-            token TEXT UNIQUE NOT NULL,
-            expires_at TEXT NOT NULL,
-            created_at TEXT NOT NULL,
-            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_sessions_token ON sessions(token);
-        CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id);
-        CREATE INDEX IF NOT EXISTS idx_sessions_expires_at ON sessions(expires_at);
-        "#
-    )
-    .execute(&pool)
-    .await?;
+/// Connection pool for whichever database `DATABASE_URL` points at. Queries
+/// are written once for both SQLite and PostgreSQL: `$N` placeholders, text
+/// RFC3339 timestamps, and booleans read back through [`get_bool`].
+pub type DbPool = AnyPool;
+pub type DbConnection = AnyConnection;
+pub type DbRow = AnyRow;
 
-    // Create organizations table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS organizations (
-            id TEXT PRIMARY KEY,
-            name TEXT UNIQUE NOT NULL,
-            display_name TEXT NOT NULL,
-            description TEXT,
-            avatar_url TEXT,
-            website TEXT,
-            owner_id TEXT NOT NULL,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            FOREIGN KEY (owner_id) REFERENCES users (id) ON DELETE CASCADE
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_organizations_name ON organizations(name);
-        CREATE INDEX IF NOT EXISTS idx_organizations_owner_id ON organizations(owner_id);
-        "#
-    )
-    .execute(&pool)
-    .await?;
+/// The database engine behind a pool, picked from the `DATABASE_URL` scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackend {
+    Sqlite,
+    Postgres,
+}
 
-    // Create organization members table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS organization_members (
-            id TEXT PRIMARY KEY,
-            organization_id TEXT NOT NULL,
-            user_id TEXT NOT NULL,
-            role TEXT NOT NULL, -- 'owner', 'admin', 'member'
-            invited_by TEXT,
-            invited_at TEXT NOT NULL,
-            joined_at TEXT,
-            is_active BOOLEAN NOT NULL DEFAULT TRUE,
-            FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE,
-            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
-            FOREIGN KEY (invited_by) REFERENCES users (id) ON DELETE SET NULL,
-            UNIQUE(organization_id, user_id)
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_org_members_org_id ON organization_members(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_org_members_user_id ON organization_members(user_id);
-        "#
-    )
-    .execute(&pool)
-    .await?;
+impl DbBackend {
+    pub fn of(pool: &DbPool) -> Self {
+        Self::of_url(pool.connect_options().database_url.as_str())
+    }
 
-    // Create organization invites table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS organization_invites (
-            id TEXT PRIMARY KEY,
-            organization_id TEXT NOT NULL,
-            email TEXT NOT NULL,
-            role TEXT NOT NULL,
-            invited_by TEXT NOT NULL,
-            token TEXT UNIQUE NOT NULL,
-            expires_at TEXT NOT NULL,
-            created_at TEXT NOT NULL,
-            accepted_at TEXT,
-            FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE,
-            FOREIGN KEY (invited_by) REFERENCES users (id) ON DELETE CASCADE
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_org_invites_token ON organization_invites(token);
-        CREATE INDEX IF NOT EXISTS idx_org_invites_email ON organization_invites(email);
-        CREATE INDEX IF NOT EXISTS idx_org_invites_org_id ON organization_invites(organization_id);
-        "#
-    )
-    .execute(&pool)
-    .await?;
-    
-    // Create crates table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS crates (
-            id TEXT PRIMARY KEY,
-            name TEXT UNIQUE NOT NULL,
-            description TEXT,
-            homepage TEXT,
-            documentation TEXT,
-            repository TEXT,
-            keywords TEXT, -- JSON encoded Vec<String>
-            categories TEXT, -- JSON encoded Vec<String>
-            license TEXT,
-            owner_id TEXT NOT NULL,
-            organization_id TEXT,
-            downloads INTEGER NOT NULL DEFAULT 0,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            FOREIGN KEY (owner_id) REFERENCES users (id) ON DELETE CASCADE,
-            FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE SET NULL
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_crates_name ON crates(name);
-        CREATE INDEX IF NOT EXISTS idx_crates_owner_id ON crates(owner_id);
-        CREATE INDEX IF NOT EXISTS idx_crates_organization_id ON crates(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_crates_downloads ON crates(downloads);
-        "#
-    )
-    .execute(&pool)
-    .await?;
+    pub fn of_url(database_url: &str) -> Self {
+        match database_url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("postgres" | "postgresql") => Self::Postgres,
+            _ => Self::Sqlite,
+        }
+    }
+
+    pub fn of_connection(conn: &DbConnection) -> Self {
+        match conn.backend_name() {
+            "PostgreSQL" => Self::Postgres,
+            _ => Self::Sqlite,
+        }
+    }
+}
+
+/// Reads a boolean column selected as `CAST(column AS INTEGER)`. SQLite has
+/// no boolean type, so its `BOOLEAN` columns can't be decoded as `bool`
+/// through the `Any` driver; every boolean is read back as an integer instead.
+pub fn get_bool(row: &DbRow, column: &str) -> bool {
+    row.get::<i64, _>(column) != 0
+}
+
+/// Whether a query failed on a `UNIQUE` or primary key constraint, on either
+/// backend.
+pub fn is_unique_violation(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<sqlx::Error>()
+        .and_then(|error| error.as_database_error())
+        .is_some_and(|error| error.is_unique_violation())
+}
+
+/// Connects and brings the schema up to date.
+pub async fn initialize_database(database_url: &str) -> Result<DbPool> {
+    let pool = connect_database(database_url).await?;
+
+    run_migrations(&pool).await?;
+    backfill_crate_owners(&pool).await?;
+
+    Ok(pool)
+}
+
+/// Connects without touching the schema.
+pub async fn connect_database(database_url: &str) -> Result<DbPool> {
+    sqlx::any::install_default_drivers();
+    let pool = DbPool::connect(database_url).await?;
+    tracing::info!("Connected to {:?} database", DbBackend::of(&pool));
 
-    // Create download metrics table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS download_metrics (
-            id TEXT PRIMARY KEY,
-            crate_id TEXT NOT NULL,
-            version TEXT NOT NULL,
-            date TEXT NOT NULL, -- YYYY-MM-DD format
-            count INTEGER NOT NULL DEFAULT 0,
-            FOREIGN KEY (crate_id) REFERENCES crates (id) ON DELETE CASCADE,
-            UNIQUE(crate_id, version, date)
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_download_metrics_crate_id ON download_metrics(crate_id);
-        CREATE INDEX IF NOT EXISTS idx_download_metrics_date ON download_metrics(date);
-        "#
-    )
-    .execute(&pool)
-    .await?;
-    
-    // Create crate_versions table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS crate_versions (
-            id TEXT PRIMARY KEY,
-            crate_id TEXT NOT NULL,
-            version TEXT NOT NULL,
-            checksum TEXT NOT NULL,
-            file_size INTEGER NOT NULL,
-            dependencies TEXT, -- JSON encoded Vec<Dependency>
-            features TEXT, -- JSON encoded HashMap<String, Vec<String>>
-            yanked BOOLEAN NOT NULL DEFAULT FALSE,
-            license TEXT,
-            readme TEXT,
-            created_at TEXT NOT NULL,
-            FOREIGN KEY (crate_id) REFERENCES crates (id) ON DELETE CASCADE,
-            UNIQUE(crate_id, version)
-        );
-        
-        CREATE INDEX IF NOT EXISTS idx_crate_versions_crate_id ON crate_versions(crate_id);
-        CREATE INDEX IF NOT EXISTS idx_crate_versions_version ON crate_versions(version);
-        CREATE INDEX IF NOT EXISTS idx_crate_versions_yanked ON crate_versions(yanked);
-        "#
-    )
-    .execute(&pool)
-    .await?;
-    
     Ok(pool)
 }
 
 pub async fn create_user(
-    pool: &SqlitePool,
+    pool: &DbPool,
     username: &str,
     email: &str,
     password_hash: &str,
@@ -219,7 +102,7 @@ pub async fn create_user(
     let now = Utc::now();
     
     sqlx::query(
-        "INSERT INTO users (id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+        "INSERT INTO users (id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
     )
     .bind(id.to_string())
     .bind(username)
@@ -250,36 +133,17 @@ pub async fn create_user(
     Ok(user)
 }
 
-pub async fn get_user_by_username(pool: &SqlitePool, username: &str) -> Result<Option<User>> {
-    let row = sqlx::query(
-        "SELECT id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at FROM users WHERE username = ?1"
-    )
-    .bind(username)
-    .fetch_optional(pool)
-    .await?;
-    
-    match row {
-        Some(row) => {
-            let user = User {
-                id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-                username: row.get("username"),
-                email: row.get("email"),
-                password_hash: row.get("password_hash"),
-                is_admin: row.get("is_admin"),
-                github_id: row.get("github_id"),
-                github_username: row.get("github_username"),
-                avatar_url: row.get("avatar_url"),
-                created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
-                updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&chrono::Utc),
-            };
-            Ok(Some(user))
-        }
-        None => Ok(None),
-    }
+pub async fn get_user_by_username(pool: &DbPool, username: &str) -> Result<Option<User>> {
+    let row = sqlx::query(&format!("SELECT {} FROM users WHERE username = $1", USER_COLUMNS))
+        .bind(username)
+        .fetch_optional(pool)
+        .await?;
+
+    row.as_ref().map(user_from_row).transpose()
 }
 
 pub async fn create_session(
-    pool: &SqlitePool,
+    pool: &DbPool,
     user_id: Uuid,
     token: &str,
     expires_at: chrono::DateTime<Utc>,
@@ -288,7 +152,7 @@ pub async fn create_session(
     let now = Utc::now();
     
     sqlx::query(
-        "INSERT INTO sessions (id, user_id, token, expires_at, created_at) VALUES (?1, ?2, ?3, ?4, ?5)"
+        "INSERT INTO sessions (id, user_id, token, expires_at, created_at) VALUES ($1, $2, $3, $4, $5)"
     )
     .bind(id.to_string())
     .bind(user_id.to_string())
@@ -309,9 +173,9 @@ pub async fn create_session(
     Ok(session)
 }
 
-pub async fn get_session_by_token(pool: &SqlitePool, token: &str) -> Result<Option<Session>> {
+pub async fn get_session_by_token(pool: &DbPool, token: &str) -> Result<Option<Session>> {
     let row = sqlx::query(
-        "SELECT id, user_id, token, expires_at, created_at FROM sessions WHERE token = ?1 AND expires_at > ?2"
+        "SELECT id, user_id, token, expires_at, created_at FROM sessions WHERE token = $1 AND expires_at > $2"
     )
     .bind(token)
     .bind(Utc::now().to_rfc3339())
@@ -333,8 +197,8 @@ pub async fn get_session_by_token(pool: &SqlitePool, token: &str) -> Result<Opti
     }
 }
 
-pub async fn delete_session(pool: &SqlitePool, token: &str) -> Result<()> {
-    sqlx::query("DELETE FROM sessions WHERE token = ?1")
+pub async fn delete_session(pool: &DbPool, token: &str) -> Result<()> {
+    sqlx::query("DELETE FROM sessions WHERE token = $1")
         .bind(token)
         .execute(pool)
         .await?;
@@ -342,36 +206,59 @@ pub async fn delete_session(pool: &SqlitePool, token: &str) -> Result<()> {
     Ok(())
 }
 
-pub async fn get_user_by_id(pool: &SqlitePool, user_id: Uuid) -> Result<Option<User>> {
-    let row = sqlx::query(
-        "SELECT id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at FROM users WHERE id = ?1"
-    )
-    .bind(user_id.to_string())
-    .fetch_optional(pool)
+pub async fn get_user_by_id(pool: &DbPool, user_id: Uuid) -> Result<Option<User>> {
+    let row = sqlx::query(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
+        .bind(user_id.to_string())
+        .fetch_optional(pool)
+        .await?;
+
+    row.as_ref().map(user_from_row).transpose()
+}
+
+/// Users newest first, for the admin pages.
+pub async fn list_users(pool: &DbPool, limit: i64, offset: i64) -> Result<Vec<User>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM users ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+        USER_COLUMNS
+    ))
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
     .await?;
-    
-    match row {
-        Some(row) => {
-            let user = User {
-                id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-                username: row.get("username"),
-                email: row.get("email"),
-                password_hash: row.get("password_hash"),
-                is_admin: row.get("is_admin"),
-                github_id: row.get("github_id"),
-                github_username: row.get("github_username"),
-                avatar_url: row.get("avatar_url"),
-                created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
-                updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&chrono::Utc),
-            };
-            Ok(Some(user))
-        }
-        None => Ok(None),
-    }
+
+    rows.iter().map(user_from_row).collect()
 }
 
+pub async fn delete_user(pool: &DbPool, user_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id.to_string())
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub(crate) const USER_COLUMNS: &str = "id, username, email, password_hash, CAST(is_admin AS INTEGER) AS is_admin, github_id, github_username, avatar_url, created_at, updated_at";
+
+pub(crate) fn user_from_row(row: &DbRow) -> Result<User> {
+    Ok(User {
+        id: Uuid::parse_str(&row.get::<String, _>("id"))?,
+        username: row.get("username"),
+        email: row.get("email"),
+        password_hash: row.get("password_hash"),
+        is_admin: get_bool(row, "is_admin"),
+        github_id: row.get("github_id"),
+        github_username: row.get("github_username"),
+        avatar_url: row.get("avatar_url"),
+        created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
+        updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&chrono::Utc),
+    })
+}
+
+/// Creates a crate and records its publisher as the first owner. Takes a
+/// connection so both inserts can run inside the caller's transaction.
 pub async fn create_crate(
-    pool: &SqlitePool,
+    conn: &mut DbConnection,
     publish_req: &PublishRequest,
     owner_id: Uuid,
 ) -> Result<Crate> {
@@ -384,7 +271,7 @@ pub async fn create_crate(
     sqlx::query(
         r#"
         INSERT INTO crates (id, name, description, homepage, documentation, repository, keywords, categories, license, owner_id, downloads, created_at, updated_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
         "#
     )
     .bind(id.to_string())
@@ -400,8 +287,10 @@ pub async fn create_crate(
     .bind(0i64)
     .bind(now.to_rfc3339())
     .bind(now.to_rfc3339())
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
+
+    add_crate_owner_user(&mut *conn, id, owner_id, owner_id).await?;
     
     let crate_model = Crate {
         id,
@@ -422,54 +311,93 @@ pub async fn create_crate(
     Ok(crate_model)
 }
 
-pub async fn get_crate_by_name(pool: &SqlitePool, name: &str) -> Result<Option<Crate>> {
+pub async fn get_crate_by_name(pool: &DbPool, name: &str) -> Result<Option<Crate>> {
+    let row = sqlx::query(&format!("SELECT {} FROM crates c WHERE c.name = $1", CRATE_COLUMNS))
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+
+    row.as_ref().map(crate_from_row).transpose()
+}
+
+pub(crate) const CRATE_COLUMNS: &str = "c.id, c.name, c.description, c.homepage, c.documentation, c.repository, c.keywords, c.categories, c.license, c.owner_id, c.downloads, c.created_at, c.updated_at";
+
+pub(crate) fn crate_from_row(row: &DbRow) -> Result<Crate> {
+    Ok(Crate {
+        id: Uuid::parse_str(&row.get::<String, _>("id"))?,
+        name: row.get("name"),
+        description: row.get("description"),
+        homepage: row.get("homepage"),
+        documentation: row.get("documentation"),
+        repository: row.get("repository"),
+        keywords: row.get("keywords"),
+        categories: row.get("categories"),
+        license: row.get("license"),
+        owner_id: Uuid::parse_str(&row.get::<String, _>("owner_id"))?,
+        downloads: row.get("downloads"),
+        created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
+        updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&chrono::Utc),
+    })
+}
+
+/// Looks a crate up ignoring ASCII case, the way Cargo addresses index files.
+pub async fn get_crate_by_name_case_insensitive(pool: &DbPool, name: &str) -> Result<Option<Crate>> {
     let row = sqlx::query(
-        "SELECT id, name, description, homepage, documentation, repository, keywords, categories, license, owner_id, downloads, created_at, updated_at FROM crates WHERE name = ?1"
+        "SELECT name FROM crates WHERE LOWER(name) = LOWER($1)"
     )
     .bind(name)
     .fetch_optional(pool)
     .await?;
-    
+
     match row {
-        Some(row) => {
-            let crate_model = Crate {
-                id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-                name: row.get("name"),
-                description: row.get("description"),
-                homepage: row.get("homepage"),
-                documentation: row.get("documentation"),
-                repository: row.get("repository"),
-                keywords: row.get("keywords"),
-                categories: row.get("categories"),
-                license: row.get("license"),
-                owner_id: Uuid::parse_str(&row.get::<String, _>("owner_id"))?,
-                downloads: row.get("downloads"),
-                created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
-                updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&chrono::Utc),
-            };
-            Ok(Some(crate_model))
-        }
+        Some(row) => get_crate_by_name(pool, &row.get::<String, _>("name")).await,
         None => Ok(None),
     }
 }
 
-pub async fn create_crate_version(
-    pool: &SqlitePool,
+/// Finds an existing crate whose name differs from `name` only in case or
+/// in `-` versus `_`, which Cargo would treat as the same crate.
+pub async fn find_conflicting_crate_name(pool: &DbPool, name: &str) -> Result<Option<String>> {
+    let row = sqlx::query(
+        "SELECT name FROM crates WHERE REPLACE(LOWER(name), '-', '_') = REPLACE(LOWER($1), '-', '_') AND name != $1"
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| row.get("name")))
+}
+
+pub async fn get_all_crate_names(pool: &DbPool) -> Result<Vec<String>> {
+    let rows = sqlx::query("SELECT name FROM crates ORDER BY name ASC")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get("name")).collect())
+}
+
+pub async fn create_crate_version<'e, E>(
+    executor: E,
     crate_id: Uuid,
     publish_req: &PublishRequest,
     checksum: &str,
     file_size: i64,
-) -> Result<CrateVersion> {
+    published_by: Uuid,
+) -> Result<CrateVersion>
+where
+    E: Executor<'e, Database = Any>,
+{
     let id = Uuid::new_v4();
     let now = Utc::now();
     
     let dependencies_json = serde_json::to_string(&publish_req.deps)?;
     let features_json = serde_json::to_string(&publish_req.features)?;
+    let authors_json = serde_json::to_string(&publish_req.authors)?;
     
     sqlx::query(
         r#"
-        INSERT INTO crate_versions (id, crate_id, version, checksum, file_size, dependencies, features, yanked, license, readme, created_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        INSERT INTO crate_versions (id, crate_id, version, checksum, file_size, dependencies, features, yanked, license, readme, links, rust_version, authors, published_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
         "#
     )
     .bind(id.to_string())
@@ -482,8 +410,12 @@ pub async fn create_crate_version(
     .bind(false)
     .bind(&publish_req.license)
     .bind(&publish_req.readme)
+    .bind(&publish_req.links)
+    .bind(&publish_req.rust_version)
+    .bind(&authors_json)
+    .bind(published_by.to_string())
     .bind(now.to_rfc3339())
-    .execute(pool)
+    .execute(executor)
     .await?;
     
     let version = CrateVersion {
@@ -497,43 +429,124 @@ pub async fn create_crate_version(
         yanked: false,
         license: publish_req.license.clone(),
         readme: publish_req.readme.clone(),
+        links: publish_req.links.clone(),
+        rust_version: publish_req.rust_version.clone(),
+        authors: Some(authors_json),
+        published_by: Some(published_by),
         created_at: now,
     };
     
     Ok(version)
 }
 
-pub async fn get_crate_versions(pool: &SqlitePool, crate_id: Uuid) -> Result<Vec<CrateVersion>> {
-    let rows = sqlx::query(
-        "SELECT id, crate_id, version, checksum, file_size, dependencies, features, yanked, license, readme, created_at FROM crate_versions WHERE crate_id = ?1 ORDER BY created_at DESC"
-    )
+const CRATE_VERSION_COLUMNS: &str = "id, crate_id, version, checksum, file_size, dependencies, features, CAST(yanked AS INTEGER) AS yanked, license, readme, links, rust_version, authors, published_by, created_at";
+
+pub async fn get_crate_versions(pool: &DbPool, crate_id: Uuid) -> Result<Vec<CrateVersion>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM crate_versions WHERE crate_id = $1 ORDER BY created_at DESC",
+        CRATE_VERSION_COLUMNS
+    ))
     .bind(crate_id.to_string())
     .fetch_all(pool)
     .await?;
     
-    let mut versions = Vec::new();
-    for row in rows {
-        let version = CrateVersion {
-            id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-            crate_id: Uuid::parse_str(&row.get::<String, _>("crate_id"))?,
-            version: row.get("version"),
-            checksum: row.get("checksum"),
-            file_size: row.get("file_size"),
-            dependencies: row.get("dependencies"),
-            features: row.get("features"),
-            yanked: row.get("yanked"),
-            license: row.get("license"),
-            readme: row.get("readme"),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
-        };
-        versions.push(version);
-    }
-    
-    Ok(versions)
+    rows.iter().map(crate_version_from_row).collect()
+}
+
+pub async fn get_crate_version(pool: &DbPool, crate_id: Uuid, version: &str) -> Result<Option<CrateVersion>> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM crate_versions WHERE crate_id = $1 AND version = $2",
+        CRATE_VERSION_COLUMNS
+    ))
+    .bind(crate_id.to_string())
+    .bind(version)
+    .fetch_optional(pool)
+    .await?;
+
+    row.as_ref().map(crate_version_from_row).transpose()
 }
 
-pub async fn increment_download_count(pool: &SqlitePool, crate_id: Uuid) -> Result<()> {
-    sqlx::query("UPDATE crates SET downloads = downloads + 1 WHERE id = ?1")
+/// Every version of the crates other than `crate_id` that have a version
+/// whose dependency list may mention `name`, with its download total and
+/// publisher. The dependency match is a cheap textual prefilter; callers must
+/// parse `dependencies` to confirm it.
+pub async fn get_dependent_versions(pool: &DbPool, crate_id: Uuid, name: &str) -> Result<Vec<DependentVersion>> {
+    let rows = sqlx::query(&format!(
+        r#"
+        SELECT {}, crate_name, downloads, publisher_login, publisher_avatar
+        FROM (
+            SELECT cv.*, c.name AS crate_name, u.username AS publisher_login, u.avatar_url AS publisher_avatar,
+                   (SELECT CAST(COALESCE(SUM(d.count), 0) AS BIGINT) FROM download_metrics d
+                    WHERE d.crate_id = cv.crate_id AND d.version = cv.version) AS downloads
+            FROM crate_versions cv
+            JOIN crates c ON c.id = cv.crate_id
+            LEFT JOIN users u ON u.id = cv.published_by
+            WHERE cv.crate_id <> $1
+              AND cv.crate_id IN (SELECT crate_id FROM crate_versions WHERE dependencies LIKE $2)
+        ) AS dependents
+        ORDER BY crate_name
+        "#,
+        CRATE_VERSION_COLUMNS
+    ))
+    .bind(crate_id.to_string())
+    .bind(format!("%\"{}\"%", name))
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(DependentVersion {
+                crate_name: row.get("crate_name"),
+                version: crate_version_from_row(row)?,
+                downloads: row.get("downloads"),
+                publisher_login: row.get("publisher_login"),
+                publisher_avatar: row.get("publisher_avatar"),
+            })
+        })
+        .collect()
+}
+
+fn crate_version_from_row(row: &DbRow) -> Result<CrateVersion> {
+    Ok(CrateVersion {
+        id: Uuid::parse_str(&row.get::<String, _>("id"))?,
+        crate_id: Uuid::parse_str(&row.get::<String, _>("crate_id"))?,
+        version: row.get("version"),
+        checksum: row.get("checksum"),
+        file_size: row.get("file_size"),
+        dependencies: row.get("dependencies"),
+        features: row.get("features"),
+        yanked: get_bool(row, "yanked"),
+        license: row.get("license"),
+        readme: row.get("readme"),
+        links: row.get("links"),
+        rust_version: row.get("rust_version"),
+        authors: row.get("authors"),
+        published_by: row.get::<Option<String>, _>("published_by")
+            .map(|id| Uuid::parse_str(&id))
+            .transpose()?,
+        created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
+    })
+}
+
+/// Sets the yanked flag on a version, returning `false` if the version doesn't exist.
+pub async fn set_version_yanked(
+    pool: &DbPool,
+    crate_id: Uuid,
+    version: &str,
+    yanked: bool,
+) -> Result<bool> {
+    let result = sqlx::query("UPDATE crate_versions SET yanked = $1 WHERE crate_id = $2 AND version = $3")
+        .bind(yanked)
+        .bind(crate_id.to_string())
+        .bind(version)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn increment_download_count(pool: &DbPool, crate_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE crates SET downloads = downloads + 1 WHERE id = $1")
         .bind(crate_id.to_string())
         .execute(pool)
         .await?;
@@ -541,67 +554,63 @@ pub async fn increment_download_count(pool: &SqlitePool, crate_id: Uuid) -> Resu
     Ok(())
 }
 
-pub async fn search_crates(
-    pool: &SqlitePool,
-    query: &str,
-    limit: i64,
-    offset: i64,
-) -> Result<Vec<Crate>> {
-    let search_pattern = format!("%{}%", query);
-    
-    let rows = sqlx::query(
+/// Bumps today's download counter for a single version.
+pub async fn record_version_download(pool: &DbPool, crate_id: Uuid, version: &str) -> Result<()> {
+    sqlx::query(
         r#"
-        SELECT id, name, description, homepage, documentation, repository, keywords, categories, license, owner_id, downloads, created_at, updated_at 
-        FROM crates 
-        WHERE name LIKE ?1 OR description LIKE ?1 
-        ORDER BY downloads DESC, name ASC 
-        LIMIT ?2 OFFSET ?3
+        INSERT INTO download_metrics (id, crate_id, version, date, count)
+        VALUES ($1, $2, $3, $4, 1)
+        ON CONFLICT(crate_id, version, date) DO UPDATE SET count = download_metrics.count + 1
         "#
     )
-    .bind(&search_pattern)
-    .bind(limit)
-    .bind(offset)
+    .bind(Uuid::new_v4().to_string())
+    .bind(crate_id.to_string())
+    .bind(version)
+    .bind(Utc::now().format("%Y-%m-%d").to_string())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Total downloads per version of a crate, keyed by version number.
+pub async fn get_version_download_totals(pool: &DbPool, crate_id: Uuid) -> Result<HashMap<String, i64>> {
+    let rows = sqlx::query(
+        "SELECT version, CAST(SUM(count) AS BIGINT) AS total FROM download_metrics WHERE crate_id = $1 GROUP BY version"
+    )
+    .bind(crate_id.to_string())
     .fetch_all(pool)
     .await?;
-    
-    let mut crates = Vec::new();
-    for row in rows {
-        let crate_model = Crate {
-            id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-            name: row.get("name"),
-            description: row.get("description"),
-            homepage: row.get("homepage"),
-            documentation: row.get("documentation"),
-            repository: row.get("repository"),
-            keywords: row.get("keywords"),
-            categories: row.get("categories"),
-            license: row.get("license"),
-            owner_id: Uuid::parse_str(&row.get::<String, _>("owner_id"))?,
-            downloads: row.get("downloads"),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?.with_timezone(&chrono::Utc),
-        };
-        crates.push(crate_model);
-    }
-    
-    Ok(crates)
+
+    Ok(rows.iter().map(|row| (row.get("version"), row.get("total"))).collect())
 }
 
-pub async fn count_search_results(pool: &SqlitePool, query: &str) -> Result<i64> {
-    let search_pattern = format!("%{}%", query);
-    
-    let row = sqlx::query(
-        "SELECT COUNT(*) as count FROM crates WHERE name LIKE ?1 OR description LIKE ?1"
+/// Daily download counts for a crate since `since` (YYYY-MM-DD), optionally
+/// limited to one version, as `(version, date, count)` newest first.
+pub async fn get_daily_downloads(
+    pool: &DbPool,
+    crate_id: Uuid,
+    version: Option<&str>,
+    since: &str,
+) -> Result<Vec<(String, String, i64)>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT version, date, count FROM download_metrics
+        WHERE crate_id = $1 AND ($2 IS NULL OR version = $2) AND date >= $3
+        ORDER BY date DESC, version ASC
+        "#
     )
-    .bind(&search_pattern)
-    .fetch_one(pool)
+    .bind(crate_id.to_string())
+    .bind(version)
+    .bind(since)
+    .fetch_all(pool)
     .await?;
-    
-    Ok(row.get("count"))
+
+    Ok(rows.iter().map(|row| (row.get("version"), row.get("date"), row.get("count"))).collect())
 }
 
 // Health check functions
-pub async fn count_total_crates(pool: &SqlitePool) -> Result<i64> {
+pub async fn count_total_crates(pool: &DbPool) -> Result<i64> {
     let row = sqlx::query("SELECT COUNT(*) as count FROM crates")
         .fetch_one(pool)
         .await?;
@@ -609,7 +618,7 @@ pub async fn count_total_crates(pool: &SqlitePool) -> Result<i64> {
     Ok(row.get("count"))
 }
 
-pub async fn count_total_versions(pool: &SqlitePool) -> Result<i64> {
+pub async fn count_total_versions(pool: &DbPool) -> Result<i64> {
     let row = sqlx::query("SELECT COUNT(*) as count FROM crate_versions")
         .fetch_one(pool)
         .await?;
@@ -617,15 +626,15 @@ pub async fn count_total_versions(pool: &SqlitePool) -> Result<i64> {
     Ok(row.get("count"))
 }
 
-pub async fn count_total_downloads(pool: &SqlitePool) -> Result<i64> {
-    let row = sqlx::query("SELECT COALESCE(SUM(downloads), 0) as total FROM crates")
+pub async fn count_total_downloads(pool: &DbPool) -> Result<i64> {
+    let row = sqlx::query("SELECT CAST(COALESCE(SUM(downloads), 0) AS BIGINT) as total FROM crates")
         .fetch_one(pool)
         .await?;
     
     Ok(row.get("total"))
 }
 
-pub async fn count_total_users(pool: &SqlitePool) -> Result<i64> {
+pub async fn count_total_users(pool: &DbPool) -> Result<i64> {
     let row = sqlx::query("SELECT COUNT(*) as count FROM users")
         .fetch_one(pool)
         .await?;
@@ -634,7 +643,7 @@ pub async fn count_total_users(pool: &SqlitePool) -> Result<i64> {
 }
 
 // Additional helper functions for health stats
-pub async fn count_total_organizations(pool: &SqlitePool) -> Result<i64> {
+pub async fn count_total_organizations(pool: &DbPool) -> Result<i64> {
     let row = sqlx::query("SELECT COUNT(*) as count FROM organizations")
         .fetch_one(pool)
         .await?;
@@ -642,10 +651,10 @@ pub async fn count_total_organizations(pool: &SqlitePool) -> Result<i64> {
     Ok(row.get("count"))
 }
 
-pub async fn count_downloads_last_days(pool: &SqlitePool, days: i64) -> Result<i64> {
+pub async fn count_downloads_last_days(pool: &DbPool, days: i64) -> Result<i64> {
     let cutoff_date = (chrono::Utc::now() - chrono::Duration::days(days)).format("%Y-%m-%d").to_string();
     
-    let row = sqlx::query("SELECT COALESCE(SUM(count), 0) as total FROM download_metrics WHERE date >= ?1")
+    let row = sqlx::query("SELECT CAST(COALESCE(SUM(count), 0) AS BIGINT) as total FROM download_metrics WHERE date >= $1")
         .bind(cutoff_date)
         .fetch_one(pool)
         .await?;
@@ -653,10 +662,10 @@ pub async fn count_downloads_last_days(pool: &SqlitePool, days: i64) -> Result<i
     Ok(row.get("total"))
 }
 
-pub async fn count_new_crates_last_days(pool: &SqlitePool, days: i64) -> Result<i64> {
+pub async fn count_new_crates_last_days(pool: &DbPool, days: i64) -> Result<i64> {
     let cutoff_date = (chrono::Utc::now() - chrono::Duration::days(days)).to_rfc3339();
     
-    let row = sqlx::query("SELECT COUNT(*) as count FROM crates WHERE created_at >= ?1")
+    let row = sqlx::query("SELECT COUNT(*) as count FROM crates WHERE created_at >= $1")
         .bind(cutoff_date)
         .fetch_one(pool)
         .await?;
@@ -664,10 +673,10 @@ pub async fn count_new_crates_last_days(pool: &SqlitePool, days: i64) -> Result<
     Ok(row.get("count"))
 }
 
-pub async fn count_new_users_last_days(pool: &SqlitePool, days: i64) -> Result<i64> {
+pub async fn count_new_users_last_days(pool: &DbPool, days: i64) -> Result<i64> {
     let cutoff_date = (chrono::Utc::now() - chrono::Duration::days(days)).to_rfc3339();
     
-    let row = sqlx::query("SELECT COUNT(*) as count FROM users WHERE created_at >= ?1")
+    let row = sqlx::query("SELECT COUNT(*) as count FROM users WHERE created_at >= $1")
         .bind(cutoff_date)
         .fetch_one(pool)
         .await?;
@@ -675,7 +684,7 @@ pub async fn count_new_users_last_days(pool: &SqlitePool, days: i64) -> Result<i
     Ok(row.get("count"))
 }
 
-pub async fn get_top_crates(pool: &SqlitePool, limit: i64) -> Result<Vec<crate::models::TopCrateStats>> {
+pub async fn get_top_crates(pool: &DbPool, limit: i64) -> Result<Vec<crate::models::TopCrateStats>> {
     let rows = sqlx::query(
         r#"
         SELECT c.name, c.downloads, c.description,
@@ -687,7 +696,7 @@ pub async fn get_top_crates(pool: &SqlitePool, limit: i64) -> Result<Vec<crate::
                ) as latest_version
         FROM crates c 
         ORDER BY c.downloads DESC 
-        LIMIT ?1
+        LIMIT $1
         "#
     )
     .bind(limit)
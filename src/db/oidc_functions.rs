@@ -1,84 +1,53 @@
-use sqlx::{SqlitePool, Row};
+use sqlx::Row;
 use uuid::Uuid;
 use anyhow::Result;
 use chrono::Utc;
 
 use crate::models::{User, OidcUserLink};
+use super::{user_from_row, DbPool, USER_COLUMNS};
 
 /// Get user by OIDC external link
 pub async fn get_user_by_oidc_link(
-    pool: &SqlitePool,
+    pool: &DbPool,
     external_id: &str,
     provider: &str,
 ) -> Result<Option<User>> {
-    let query = r#"
-        SELECT u.* FROM users u
-        JOIN oidc_user_links oul ON u.id = oul.user_id
-        WHERE oul.external_id = ? AND oul.provider_type = ?
-    "#;
-
-    let row = sqlx::query(query)
+    let query = format!(
+        r#"
+        SELECT {} FROM users
+        WHERE id IN (
+            SELECT user_id FROM oidc_user_links WHERE external_id = $1 AND provider_type = $2
+        )
+        "#,
+        USER_COLUMNS
+    );
+
+    let row = sqlx::query(&query)
         .bind(external_id)
         .bind(provider)
         .fetch_optional(pool)
         .await?;
 
-    if let Some(row) = row {
-        let user = User {
-            id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-            username: row.get("username"),
-            email: row.get("email"),
-            password_hash: row.get("password_hash"),
-            is_admin: row.get("is_admin"),
-            github_id: row.get("github_id"),
-            github_username: row.get("github_username"),
-            avatar_url: row.get("avatar_url"),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?
-                .with_timezone(&chrono::Utc),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?
-                .with_timezone(&chrono::Utc),
-        };
-        Ok(Some(user))
-    } else {
-        Ok(None)
-    }
+    row.as_ref().map(user_from_row).transpose()
 }
 
 /// Get user by email
-pub async fn get_user_by_email(pool: &SqlitePool, email: &str) -> Result<Option<User>> {
-    let query = "SELECT * FROM users WHERE email = ?";
+pub async fn get_user_by_email(pool: &DbPool, email: &str) -> Result<Option<User>> {
+    let query = format!("SELECT {} FROM users WHERE email = $1", USER_COLUMNS);
 
-    let row = sqlx::query(query)
+    let row = sqlx::query(&query)
         .bind(email)
         .fetch_optional(pool)
         .await?;
 
-    if let Some(row) = row {
-        let user = User {
-            id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-            username: row.get("username"),
-            email: row.get("email"),
-            password_hash: row.get("password_hash"),
-            is_admin: row.get("is_admin"),
-            github_id: row.get("github_id"),
-            github_username: row.get("github_username"),
-            avatar_url: row.get("avatar_url"),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?
-                .with_timezone(&chrono::Utc),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at"))?
-                .with_timezone(&chrono::Utc),
-        };
-        Ok(Some(user))
-    } else {
-        Ok(None)
-    }
+    row.as_ref().map(user_from_row).transpose()
 }
 
 /// Create OIDC user (user created via OIDC authentication)
-pub async fn create_oidc_user(pool: &SqlitePool, user: &User) -> Result<()> {
+pub async fn create_oidc_user(pool: &DbPool, user: &User) -> Result<()> {
     let query = r#"
         INSERT INTO users (id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
     "#;
 
     sqlx::query(query)
@@ -100,7 +69,7 @@ pub async fn create_oidc_user(pool: &SqlitePool, user: &User) -> Result<()> {
 
 /// Create OIDC user link
 pub async fn create_oidc_user_link(
-    pool: &SqlitePool,
+    pool: &DbPool,
     user_id: Uuid,
     external_id: &str,
     provider_type: &str,
@@ -109,7 +78,7 @@ pub async fn create_oidc_user_link(
 ) -> Result<()> {
     let query = r#"
         INSERT INTO oidc_user_links (id, user_id, external_id, provider_type, email, name, created_at, updated_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
     "#;
 
     let now = Utc::now();
@@ -131,14 +100,14 @@ pub async fn create_oidc_user_link(
 
 /// Update OIDC user link last login
 pub async fn update_oidc_user_link_last_login(
-    pool: &SqlitePool,
+    pool: &DbPool,
     user_id: Uuid,
     provider_type: &str,
 ) -> Result<()> {
     let query = r#"
         UPDATE oidc_user_links 
-        SET last_login = ?, updated_at = ?
-        WHERE user_id = ? AND provider_type = ?
+        SET last_login = $1, updated_at = $2
+        WHERE user_id = $3 AND provider_type = $4
     "#;
 
     let now = Utc::now();
@@ -155,8 +124,8 @@ pub async fn update_oidc_user_link_last_login(
 }
 
 /// Get OIDC links for a user
-pub async fn get_user_oidc_links(pool: &SqlitePool, user_id: Uuid) -> Result<Vec<OidcUserLink>> {
-    let query = "SELECT * FROM oidc_user_links WHERE user_id = ?";
+pub async fn get_user_oidc_links(pool: &DbPool, user_id: Uuid) -> Result<Vec<OidcUserLink>> {
+    let query = "SELECT * FROM oidc_user_links WHERE user_id = $1";
 
     let rows = sqlx::query(query)
         .bind(user_id.to_string())
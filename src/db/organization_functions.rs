@@ -4,16 +4,16 @@ use crate::models::{
     user::User,
     organization::{Organization, OrganizationMember, OrganizationRole, OrganizationInvite},
     organization::{CreateOrganizationRequest, UpdateOrganizationRequest},
-    metrics::TopCrateStats,
 };
-use sqlx::{SqlitePool, Row};
+use sqlx::Row;
+use super::{get_bool, user_from_row, DbPool, USER_COLUMNS};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use anyhow::Result;
 
 // Organization functions
-pub async fn organization_exists(pool: &SqlitePool, name: &str) -> Result<bool> {
-    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM organizations WHERE name = ?1")
+pub async fn organization_exists(pool: &DbPool, name: &str) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM organizations WHERE name = $1")
         .bind(name)
         .fetch_one(pool)
         .await?;
@@ -21,7 +21,7 @@ pub async fn organization_exists(pool: &SqlitePool, name: &str) -> Result<bool>
 }
 
 pub async fn create_organization(
-    pool: &SqlitePool,
+    pool: &DbPool,
     request: &CreateOrganizationRequest,
     owner_id: Uuid,
 ) -> Result<Organization> {
@@ -31,7 +31,7 @@ pub async fn create_organization(
     sqlx::query(
         r#"
         INSERT INTO organizations (id, name, display_name, description, website, owner_id, created_at, updated_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#
     )
     .bind(id.to_string())
@@ -50,7 +50,7 @@ pub async fn create_organization(
     sqlx::query(
         r#"
         INSERT INTO organization_members (id, organization_id, user_id, role, invited_by, invited_at, joined_at, is_active)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#
     )
     .bind(member_id.to_string())
@@ -77,9 +77,9 @@ pub async fn create_organization(
     })
 }
 
-pub async fn get_organization_by_name(pool: &SqlitePool, name: &str) -> Result<Option<Organization>> {
+pub async fn get_organization_by_name(pool: &DbPool, name: &str) -> Result<Option<Organization>> {
     let row = sqlx::query(
-        "SELECT id, name, display_name, description, avatar_url, website, owner_id, created_at, updated_at FROM organizations WHERE name = ?1"
+        "SELECT id, name, display_name, description, avatar_url, website, owner_id, created_at, updated_at FROM organizations WHERE name = $1"
     )
     .bind(name)
     .fetch_optional(pool)
@@ -102,9 +102,9 @@ pub async fn get_organization_by_name(pool: &SqlitePool, name: &str) -> Result<O
     }
 }
 
-pub async fn get_organization_by_id(pool: &SqlitePool, org_id: Uuid) -> Result<Option<Organization>> {
+pub async fn get_organization_by_id(pool: &DbPool, org_id: Uuid) -> Result<Option<Organization>> {
     let row = sqlx::query(
-        "SELECT id, name, display_name, description, avatar_url, website, owner_id, created_at, updated_at FROM organizations WHERE id = ?1"
+        "SELECT id, name, display_name, description, avatar_url, website, owner_id, created_at, updated_at FROM organizations WHERE id = $1"
     )
     .bind(org_id.to_string())
     .fetch_optional(pool)
@@ -127,15 +127,15 @@ pub async fn get_organization_by_id(pool: &SqlitePool, org_id: Uuid) -> Result<O
     }
 }
 
-pub async fn list_user_organizations(pool: &SqlitePool, user_id: Uuid, limit: i64, offset: i64) -> Result<Vec<Organization>> {
+pub async fn list_user_organizations(pool: &DbPool, user_id: Uuid, limit: i64, offset: i64) -> Result<Vec<Organization>> {
     let rows = sqlx::query(
         r#"
         SELECT o.id, o.name, o.display_name, o.description, o.avatar_url, o.website, o.owner_id, o.created_at, o.updated_at
         FROM organizations o
         JOIN organization_members om ON o.id = om.organization_id
-        WHERE om.user_id = ?1 AND om.is_active = true
+        WHERE om.user_id = $1 AND om.is_active = true
         ORDER BY o.name ASC
-        LIMIT ?2 OFFSET ?3
+        LIMIT $2 OFFSET $3
         "#
     )
     .bind(user_id.to_string())
@@ -162,9 +162,9 @@ pub async fn list_user_organizations(pool: &SqlitePool, user_id: Uuid, limit: i6
     Ok(organizations)
 }
 
-pub async fn list_user_organization_invites(pool: &SqlitePool, user_email: &str) -> Result<Vec<OrganizationInvite>> {
+pub async fn list_user_organization_invites(pool: &DbPool, user_email: &str) -> Result<Vec<OrganizationInvite>> {
     let rows = sqlx::query(
-        "SELECT id, organization_id, email, role, invited_by, token, expires_at, created_at, accepted_at FROM organization_invites WHERE email = ?1 AND expires_at > ?2 AND accepted_at IS NULL"
+        "SELECT id, organization_id, email, role, invited_by, token, expires_at, created_at, accepted_at FROM organization_invites WHERE email = $1 AND expires_at > $2 AND accepted_at IS NULL"
     )
     .bind(user_email)
     .bind(Utc::now().to_rfc3339())
@@ -196,7 +196,7 @@ pub async fn list_user_organization_invites(pool: &SqlitePool, user_email: &str)
 }
 
 pub async fn update_organization(
-    pool: &SqlitePool,
+    pool: &DbPool,
     org_id: Uuid,
     request: &UpdateOrganizationRequest,
 ) -> Result<Organization> {
@@ -205,12 +205,12 @@ pub async fn update_organization(
     sqlx::query(
         r#"
         UPDATE organizations 
-        SET display_name = COALESCE(?1, display_name),
-            description = COALESCE(?2, description),
-            website = COALESCE(?3, website),
-            avatar_url = COALESCE(?4, avatar_url),
-            updated_at = ?5
-        WHERE id = ?6
+        SET display_name = COALESCE($1, display_name),
+            description = COALESCE($2, description),
+            website = COALESCE($3, website),
+            avatar_url = COALESCE($4, avatar_url),
+            updated_at = $5
+        WHERE id = $6
         "#
     )
     .bind(&request.display_name)
@@ -224,7 +224,7 @@ pub async fn update_organization(
 
     // Fetch and return updated organization
     let row = sqlx::query(
-        "SELECT id, name, display_name, description, avatar_url, website, owner_id, created_at, updated_at FROM organizations WHERE id = ?1"
+        "SELECT id, name, display_name, description, avatar_url, website, owner_id, created_at, updated_at FROM organizations WHERE id = $1"
     )
     .bind(org_id.to_string())
     .fetch_one(pool)
@@ -243,17 +243,17 @@ pub async fn update_organization(
     })
 }
 
-pub async fn delete_organization(pool: &SqlitePool, org_id: Uuid) -> Result<()> {
-    sqlx::query("DELETE FROM organizations WHERE id = ?1")
+pub async fn delete_organization(pool: &DbPool, org_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM organizations WHERE id = $1")
         .bind(org_id.to_string())
         .execute(pool)
         .await?;
     Ok(())
 }
 
-pub async fn get_organization_member_count(pool: &SqlitePool, org_id: Uuid) -> Result<i64> {
+pub async fn get_organization_member_count(pool: &DbPool, org_id: Uuid) -> Result<i64> {
     let count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM organization_members WHERE organization_id = ?1 AND is_active = true"
+        "SELECT COUNT(*) FROM organization_members WHERE organization_id = $1 AND is_active = true"
     )
     .bind(org_id.to_string())
     .fetch_one(pool)
@@ -261,9 +261,9 @@ pub async fn get_organization_member_count(pool: &SqlitePool, org_id: Uuid) -> R
     Ok(count)
 }
 
-pub async fn get_organization_crate_count(pool: &SqlitePool, org_id: Uuid) -> Result<i64> {
+pub async fn get_organization_crate_count(pool: &DbPool, org_id: Uuid) -> Result<i64> {
     let count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM crates WHERE organization_id = ?1"
+        "SELECT COUNT(*) FROM crates WHERE organization_id = $1"
     )
     .bind(org_id.to_string())
     .fetch_one(pool)
@@ -271,133 +271,22 @@ pub async fn get_organization_crate_count(pool: &SqlitePool, org_id: Uuid) -> Re
     Ok(count)
 }
 
-// Statistics functions
-pub async fn count_total_crates(pool: &SqlitePool) -> Result<i64> {
-    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM crates")
-        .fetch_one(pool)
-        .await?;
-    Ok(count)
-}
-
-pub async fn count_total_versions(pool: &SqlitePool) -> Result<i64> {
-    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM crate_versions")
-        .fetch_one(pool)
-        .await?;
-    Ok(count)
-}
-
-pub async fn count_total_downloads(pool: &SqlitePool) -> Result<i64> {
-    let count: i64 = sqlx::query_scalar("SELECT COALESCE(SUM(downloads), 0) FROM crates")
-        .fetch_one(pool)
-        .await?;
-    Ok(count)
-}
-
-pub async fn count_total_users(pool: &SqlitePool) -> Result<i64> {
-    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
-        .fetch_one(pool)
-        .await?;
-    Ok(count)
-}
-
-pub async fn count_total_organizations(pool: &SqlitePool) -> Result<i64> {
-    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM organizations")
-        .fetch_one(pool)
-        .await?;
-    Ok(count)
-}
-
-pub async fn count_downloads_last_days(pool: &SqlitePool, days: i32) -> Result<i64> {
-    // This would need a downloads tracking table
-    // For now, return 0
-    Ok(0)
-}
-
-pub async fn count_new_crates_last_days(pool: &SqlitePool, days: i32) -> Result<i64> {
-    let since = Utc::now() - chrono::Duration::days(days as i64);
-    let count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM crates WHERE created_at >= ?1"
-    )
-    .bind(since.to_rfc3339())
-    .fetch_one(pool)
-    .await?;
-    Ok(count)
-}
-
-pub async fn count_new_users_last_days(pool: &SqlitePool, days: i32) -> Result<i64> {
-    let since = Utc::now() - chrono::Duration::days(days as i64);
-    let count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM users WHERE created_at >= ?1"
-    )
-    .bind(since.to_rfc3339())
-    .fetch_one(pool)
-    .await?;
-    Ok(count)
-}
-
-pub async fn get_top_crates(pool: &SqlitePool, limit: i32) -> Result<Vec<TopCrateStats>> {
-    let rows = sqlx::query(
-        r#"
-        SELECT c.name, c.description, c.downloads, 
-               COALESCE(cv.version, '0.0.0') as latest_version
-        FROM crates c
-        LEFT JOIN crate_versions cv ON c.id = cv.crate_id
-        GROUP BY c.id
-        ORDER BY c.downloads DESC
-        LIMIT ?1
-        "#
-    )
-    .bind(limit)
-    .fetch_all(pool)
-    .await?;
-
-    let mut top_crates = Vec::new();
-    for row in rows {
-        top_crates.push(TopCrateStats {
-            name: row.get("name"),
-            total_downloads: row.get("downloads"),
-            downloads_last_30_days: 0, // Would need tracking
-            latest_version: row.get("latest_version"),
-            description: row.get("description"),
-        });
-    }
-
-    Ok(top_crates)
-}
-
 // GitHub-related functions
-pub async fn get_user_by_github_id(pool: &SqlitePool, github_id: i64) -> Result<Option<User>> {
-    let row = sqlx::query(
-        "SELECT id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at FROM users WHERE github_id = ?1"
-    )
-    .bind(github_id)
-    .fetch_optional(pool)
-    .await?;
+pub async fn get_user_by_github_id(pool: &DbPool, github_id: i64) -> Result<Option<User>> {
+    let row = sqlx::query(&format!("SELECT {} FROM users WHERE github_id = $1", USER_COLUMNS))
+        .bind(github_id)
+        .fetch_optional(pool)
+        .await?;
 
-    if let Some(row) = row {
-        Ok(Some(User {
-            id: Uuid::parse_str(&row.get::<String, _>("id"))?,
-            username: row.get("username"),
-            email: row.get("email"),
-            password_hash: row.get("password_hash"),
-            is_admin: row.get("is_admin"),
-            github_id: row.get("github_id"),
-            github_username: row.get("github_username"),
-            avatar_url: row.get("avatar_url"),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at")).unwrap().with_timezone(&chrono::Utc),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at")).unwrap().with_timezone(&chrono::Utc),
-        }))
-    } else {
-        Ok(None)
-    }
+    row.as_ref().map(user_from_row).transpose()
 }
 
 pub async fn create_github_user(
-    pool: &SqlitePool,
+    pool: &DbPool,
     username: &str,
     email: &str,
     github_id: i64,
-    name: Option<&str>,
+    _name: Option<&str>,
     avatar_url: Option<&str>,
 ) -> Result<User> {
     let id = Uuid::new_v4();
@@ -409,7 +298,7 @@ pub async fn create_github_user(
     sqlx::query(
         r#"
         INSERT INTO users (id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         "#
     )
     .bind(id.to_string())
@@ -439,9 +328,9 @@ pub async fn create_github_user(
     })
 }
 
-pub async fn disconnect_github_user(pool: &SqlitePool, user_id: Uuid) -> Result<()> {
+pub async fn disconnect_github_user(pool: &DbPool, user_id: Uuid) -> Result<()> {
     sqlx::query(
-        "UPDATE users SET github_id = NULL, github_username = NULL WHERE id = ?1"
+        "UPDATE users SET github_id = NULL, github_username = NULL WHERE id = $1"
     )
     .bind(user_id.to_string())
     .execute(pool)
@@ -450,7 +339,7 @@ pub async fn disconnect_github_user(pool: &SqlitePool, user_id: Uuid) -> Result<
 }
 
 pub async fn link_github_user(
-    pool: &SqlitePool,
+    pool: &DbPool,
     user_id: Uuid,
     github_id: i64,
     name: Option<&str>,
@@ -461,8 +350,8 @@ pub async fn link_github_user(
     sqlx::query(
         r#"
         UPDATE users 
-        SET github_id = ?1, github_username = ?2, avatar_url = ?3, updated_at = ?4 
-        WHERE id = ?5
+        SET github_id = $1, github_username = $2, avatar_url = $3, updated_at = $4 
+        WHERE id = $5
         "#
     )
     .bind(github_id)
@@ -477,7 +366,7 @@ pub async fn link_github_user(
 }
 
 // Organization membership functions
-pub async fn user_can_manage_organization(pool: &SqlitePool, user_id: Uuid, org_id: Uuid) -> Result<bool> {
+pub async fn user_can_manage_organization(pool: &DbPool, user_id: Uuid, org_id: Uuid) -> Result<bool> {
     let role = get_user_organization_role(pool, user_id, org_id).await?;
     match role {
         Some(OrganizationRole::Owner) | Some(OrganizationRole::Admin) => Ok(true),
@@ -486,7 +375,7 @@ pub async fn user_can_manage_organization(pool: &SqlitePool, user_id: Uuid, org_
 }
 
 pub async fn get_organization_members(
-    pool: &SqlitePool, 
+    pool: &DbPool, 
     org_id: Uuid, 
     limit: i64, 
     offset: i64
@@ -494,13 +383,13 @@ pub async fn get_organization_members(
     let rows = sqlx::query(
         r#"
         SELECT 
-            om.id, om.organization_id, om.user_id, om.role, om.invited_by, om.invited_at, om.joined_at, om.is_active,
-            u.id as user_id, u.username, u.email, u.password_hash, u.is_admin, u.github_id, u.github_username, u.avatar_url, u.created_at as user_created_at, u.updated_at as user_updated_at
+            om.id, om.organization_id, om.user_id, om.role, om.invited_by, om.invited_at, om.joined_at, CAST(om.is_active AS INTEGER) AS is_active,
+            u.id as user_id, u.username, u.email, u.password_hash, CAST(u.is_admin AS INTEGER) AS is_admin, u.github_id, u.github_username, u.avatar_url, u.created_at as user_created_at, u.updated_at as user_updated_at
         FROM organization_members om
         JOIN users u ON om.user_id = u.id
-        WHERE om.organization_id = ?1 AND om.is_active = true
+        WHERE om.organization_id = $1 AND om.is_active = true
         ORDER BY om.joined_at DESC
-        LIMIT ?2 OFFSET ?3
+        LIMIT $2 OFFSET $3
         "#
     )
     .bind(org_id.to_string())
@@ -524,7 +413,7 @@ pub async fn get_organization_members(
             invited_by: row.get::<Option<String>, _>("invited_by").map(|s| Uuid::parse_str(&s)).transpose()?,
             invited_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("invited_at")).unwrap().with_timezone(&chrono::Utc),
             joined_at: row.get::<Option<String>, _>("joined_at").map(|s| chrono::DateTime::parse_from_rfc3339(&s).unwrap().with_timezone(&chrono::Utc)),
-            is_active: row.get("is_active"),
+            is_active: get_bool(&row, "is_active"),
         };
 
         let user = User {
@@ -532,7 +421,7 @@ pub async fn get_organization_members(
             username: row.get("username"),
             email: row.get("email"),
             password_hash: row.get("password_hash"),
-            is_admin: row.get("is_admin"),
+            is_admin: get_bool(&row, "is_admin"),
             github_id: row.get("github_id"),
             github_username: row.get("github_username"),
             avatar_url: row.get("avatar_url"),
@@ -547,12 +436,12 @@ pub async fn get_organization_members(
 }
 
 pub async fn get_user_organization_role(
-    pool: &SqlitePool, 
+    pool: &DbPool, 
     user_id: Uuid, 
     org_id: Uuid
 ) -> Result<Option<OrganizationRole>> {
     let row = sqlx::query(
-        "SELECT role FROM organization_members WHERE user_id = ?1 AND organization_id = ?2 AND is_active = true"
+        "SELECT role FROM organization_members WHERE user_id = $1 AND organization_id = $2 AND is_active = true"
     )
     .bind(user_id.to_string())
     .bind(org_id.to_string())
@@ -572,7 +461,7 @@ pub async fn get_user_organization_role(
 }
 
 pub async fn is_user_organization_member(
-    pool: &SqlitePool, 
+    pool: &DbPool, 
     email: &str, 
     org_id: Uuid
 ) -> Result<bool> {
@@ -580,7 +469,7 @@ pub async fn is_user_organization_member(
         r#"
         SELECT COUNT(*) FROM organization_members om
         JOIN users u ON om.user_id = u.id
-        WHERE u.email = ?1 AND om.organization_id = ?2 AND om.is_active = true
+        WHERE u.email = $1 AND om.organization_id = $2 AND om.is_active = true
         "#
     )
     .bind(email)
@@ -592,7 +481,7 @@ pub async fn is_user_organization_member(
 }
 
 pub async fn create_organization_invite(
-    pool: &SqlitePool,
+    pool: &DbPool,
     org_id: Uuid,
     email: &str,
     role: OrganizationRole,
@@ -613,7 +502,7 @@ pub async fn create_organization_invite(
     sqlx::query(
         r#"
         INSERT INTO organization_invites (id, organization_id, email, role, invited_by, token, expires_at, created_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#
     )
     .bind(id.to_string())
@@ -641,11 +530,11 @@ pub async fn create_organization_invite(
 }
 
 pub async fn get_organization_invite_by_token(
-    pool: &SqlitePool, 
+    pool: &DbPool, 
     token: &str
 ) -> Result<Option<OrganizationInvite>> {
     let row = sqlx::query(
-        "SELECT id, organization_id, email, role, invited_by, token, expires_at, created_at, accepted_at FROM organization_invites WHERE token = ?1 AND expires_at > ?2"
+        "SELECT id, organization_id, email, role, invited_by, token, expires_at, created_at, accepted_at FROM organization_invites WHERE token = $1 AND expires_at > $2"
     )
     .bind(token)
     .bind(Utc::now().to_rfc3339())
@@ -676,12 +565,12 @@ pub async fn get_organization_invite_by_token(
 }
 
 pub async fn accept_organization_invite(
-    pool: &SqlitePool, 
+    pool: &DbPool, 
     invite_id: Uuid, 
     user_id: Uuid
 ) -> Result<OrganizationMember> {
     let invite = sqlx::query(
-        "SELECT organization_id, role, invited_by FROM organization_invites WHERE id = ?1"
+        "SELECT organization_id, role, invited_by FROM organization_invites WHERE id = $1"
     )
     .bind(invite_id.to_string())
     .fetch_one(pool)
@@ -702,7 +591,7 @@ pub async fn accept_organization_invite(
     sqlx::query(
         r#"
         INSERT INTO organization_members (id, organization_id, user_id, role, invited_by, invited_at, joined_at, is_active)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#
     )
     .bind(member_id.to_string())
@@ -723,7 +612,7 @@ pub async fn accept_organization_invite(
 
     // Mark invite as accepted
     sqlx::query(
-        "UPDATE organization_invites SET accepted_at = ?1 WHERE id = ?2"
+        "UPDATE organization_invites SET accepted_at = $1 WHERE id = $2"
     )
     .bind(now.to_rfc3339())
     .bind(invite_id.to_string())
@@ -743,11 +632,11 @@ pub async fn accept_organization_invite(
 }
 
 pub async fn get_organization_member(
-    pool: &SqlitePool, 
+    pool: &DbPool, 
     member_id: Uuid
 ) -> Result<Option<OrganizationMember>> {
     let row = sqlx::query(
-        "SELECT id, organization_id, user_id, role, invited_by, invited_at, joined_at, is_active FROM organization_members WHERE id = ?1"
+        "SELECT id, organization_id, user_id, role, invited_by, invited_at, joined_at, CAST(is_active AS INTEGER) AS is_active FROM organization_members WHERE id = $1"
     )
     .bind(member_id.to_string())
     .fetch_optional(pool)
@@ -768,15 +657,15 @@ pub async fn get_organization_member(
             invited_by: row.get::<Option<String>, _>("invited_by").map(|s| Uuid::parse_str(&s)).transpose()?,
             invited_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("invited_at")).unwrap().with_timezone(&chrono::Utc),
             joined_at: row.get::<Option<String>, _>("joined_at").map(|s| chrono::DateTime::parse_from_rfc3339(&s).unwrap().with_timezone(&chrono::Utc)),
-            is_active: row.get("is_active"),
+            is_active: get_bool(&row, "is_active"),
         }))
     } else {
         Ok(None)
     }
 }
 
-pub async fn remove_organization_member(pool: &SqlitePool, member_id: Uuid) -> Result<()> {
-    sqlx::query("UPDATE organization_members SET is_active = false WHERE id = ?1")
+pub async fn remove_organization_member(pool: &DbPool, member_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE organization_members SET is_active = false WHERE id = $1")
         .bind(member_id.to_string())
         .execute(pool)
         .await?;
@@ -784,12 +673,12 @@ pub async fn remove_organization_member(pool: &SqlitePool, member_id: Uuid) -> R
 }
 
 pub async fn get_user_organization_membership(
-    pool: &SqlitePool, 
+    pool: &DbPool, 
     user_id: Uuid, 
     org_id: Uuid
 ) -> Result<Option<OrganizationMember>> {
     let row = sqlx::query(
-        "SELECT id, organization_id, user_id, role, invited_by, invited_at, joined_at, is_active FROM organization_members WHERE user_id = ?1 AND organization_id = ?2 AND is_active = true"
+        "SELECT id, organization_id, user_id, role, invited_by, invited_at, joined_at, CAST(is_active AS INTEGER) AS is_active FROM organization_members WHERE user_id = $1 AND organization_id = $2 AND is_active = true"
     )
     .bind(user_id.to_string())
     .bind(org_id.to_string())
@@ -811,7 +700,7 @@ pub async fn get_user_organization_membership(
             invited_by: row.get::<Option<String>, _>("invited_by").map(|s| Uuid::parse_str(&s)).transpose()?,
             invited_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("invited_at")).unwrap().with_timezone(&chrono::Utc),
             joined_at: row.get::<Option<String>, _>("joined_at").map(|s| chrono::DateTime::parse_from_rfc3339(&s).unwrap().with_timezone(&chrono::Utc)),
-            is_active: row.get("is_active"),
+            is_active: get_bool(&row, "is_active"),
         }))
     } else {
         Ok(None)
@@ -0,0 +1,235 @@
+// Crate owner database functions for db/mod.rs
+
+use crate::models::{CrateOwner, OwnerKind, TEAM_LOGIN_PREFIX};
+use sqlx::{Any, Executor, Row};
+use super::DbPool;
+use uuid::Uuid;
+use chrono::Utc;
+use anyhow::Result;
+
+/// Seeds `crate_owners` for crates created before owners were tracked there,
+/// using the original `crates.owner_id` and `crates.organization_id` columns.
+/// Crates that already have any owner row are left alone, so removed owners
+/// are not resurrected on restart.
+pub async fn backfill_crate_owners(pool: &DbPool) -> Result<()> {
+    let rows = sqlx::query(
+        r#"
+        SELECT c.id, c.owner_id, c.organization_id FROM crates c
+        WHERE NOT EXISTS (SELECT 1 FROM crate_owners o WHERE o.crate_id = c.id)
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in &rows {
+        let crate_id = Uuid::parse_str(&row.get::<String, _>("id"))?;
+        let owner_id = Uuid::parse_str(&row.get::<String, _>("owner_id"))?;
+        add_crate_owner_user(pool, crate_id, owner_id, owner_id).await?;
+
+        if let Some(org_id) = row.get::<Option<String>, _>("organization_id") {
+            add_crate_owner_team(pool, crate_id, Uuid::parse_str(&org_id)?, owner_id).await?;
+        }
+    }
+
+    if !rows.is_empty() {
+        tracing::info!("Backfilled owners for {} crates", rows.len());
+    }
+
+    Ok(())
+}
+
+pub async fn get_crate_owners(pool: &DbPool, crate_id: Uuid) -> Result<Vec<CrateOwner>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT 'user' AS kind, u.id AS owner_id, u.username AS login, NULL AS name,
+               u.avatar_url AS avatar, co.created_at AS created_at
+        FROM crate_owners co
+        JOIN users u ON co.user_id = u.id
+        WHERE co.crate_id = $1
+        UNION ALL
+        SELECT 'team' AS kind, o.id AS owner_id, o.name AS login, o.display_name AS name,
+               o.avatar_url AS avatar, co.created_at AS created_at
+        FROM crate_owners co
+        JOIN organizations o ON co.organization_id = o.id
+        WHERE co.crate_id = $1
+        ORDER BY created_at ASC
+        "#
+    )
+    .bind(crate_id.to_string())
+    .fetch_all(pool)
+    .await?;
+
+    let mut owners = Vec::new();
+    for row in rows {
+        let kind = match row.get::<String, _>("kind").as_str() {
+            "team" => OwnerKind::Team,
+            _ => OwnerKind::User,
+        };
+
+        let login: String = row.get("login");
+        let login = match kind {
+            OwnerKind::User => login,
+            OwnerKind::Team => format!("{}{}", TEAM_LOGIN_PREFIX, login),
+        };
+
+        owners.push(CrateOwner {
+            kind,
+            owner_id: Uuid::parse_str(&row.get::<String, _>("owner_id"))?,
+            login,
+            name: row.get("name"),
+            avatar: row.get("avatar"),
+            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
+        });
+    }
+
+    Ok(owners)
+}
+
+/// Adds a user as an owner, returning `false` if they already were one.
+pub async fn add_crate_owner_user<'e, E>(
+    executor: E,
+    crate_id: Uuid,
+    user_id: Uuid,
+    added_by: Uuid,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Any>,
+{
+    let result = sqlx::query(
+        r#"
+        INSERT INTO crate_owners (id, crate_id, user_id, organization_id, added_by, created_at)
+        VALUES ($1, $2, $3, NULL, $4, $5)
+        ON CONFLICT DO NOTHING
+        "#
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(crate_id.to_string())
+    .bind(user_id.to_string())
+    .bind(added_by.to_string())
+    .bind(Utc::now().to_rfc3339())
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Adds an organization as an owner, returning `false` if it already was one.
+pub async fn add_crate_owner_team<'e, E>(
+    executor: E,
+    crate_id: Uuid,
+    org_id: Uuid,
+    added_by: Uuid,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Any>,
+{
+    let result = sqlx::query(
+        r#"
+        INSERT INTO crate_owners (id, crate_id, user_id, organization_id, added_by, created_at)
+        VALUES ($1, $2, NULL, $3, $4, $5)
+        ON CONFLICT DO NOTHING
+        "#
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(crate_id.to_string())
+    .bind(org_id.to_string())
+    .bind(added_by.to_string())
+    .bind(Utc::now().to_rfc3339())
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Takes the write lock on the crate's row for the rest of the transaction,
+/// so concurrent owner changes to the same crate run one after the other and
+/// each sees the owners the previous one left. A no-op update is used because
+/// SQLite has no `SELECT ... FOR UPDATE`.
+pub async fn lock_crate_owners<'e, E>(executor: E, crate_id: Uuid) -> Result<()>
+where
+    E: Executor<'e, Database = Any>,
+{
+    sqlx::query("UPDATE crates SET updated_at = updated_at WHERE id = $1")
+        .bind(crate_id.to_string())
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn remove_crate_owner_user<'e, E>(executor: E, crate_id: Uuid, user_id: Uuid) -> Result<bool>
+where
+    E: Executor<'e, Database = Any>,
+{
+    let result = sqlx::query("DELETE FROM crate_owners WHERE crate_id = $1 AND user_id = $2")
+        .bind(crate_id.to_string())
+        .bind(user_id.to_string())
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn remove_crate_owner_team<'e, E>(executor: E, crate_id: Uuid, org_id: Uuid) -> Result<bool>
+where
+    E: Executor<'e, Database = Any>,
+{
+    let result = sqlx::query("DELETE FROM crate_owners WHERE crate_id = $1 AND organization_id = $2")
+        .bind(crate_id.to_string())
+        .bind(org_id.to_string())
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether the user is listed as an owner in their own right, rather than
+/// through an organization. Only these owners may change a crate's owners.
+pub async fn is_crate_user_owner(pool: &DbPool, crate_id: Uuid, user_id: Uuid) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM crate_owners WHERE crate_id = $1 AND user_id = $2"
+    )
+    .bind(crate_id.to_string())
+    .bind(user_id.to_string())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count > 0)
+}
+
+/// Whether the user may publish new versions of, or yank, the crate: either
+/// directly as an owner, or as an active member of an owning organization
+/// whose role allows publishing.
+pub async fn user_can_publish(pool: &DbPool, crate_id: Uuid, user_id: Uuid) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM crate_owners co
+        LEFT JOIN organization_members om
+            ON co.organization_id = om.organization_id
+            AND om.user_id = $2
+            AND om.is_active = true
+            AND om.role IN ('owner', 'admin', 'member')
+        WHERE co.crate_id = $1 AND (co.user_id = $2 OR om.id IS NOT NULL)
+        "#
+    )
+    .bind(crate_id.to_string())
+    .bind(user_id.to_string())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count > 0)
+}
+
+pub async fn count_crate_user_owners<'e, E>(executor: E, crate_id: Uuid) -> Result<i64>
+where
+    E: Executor<'e, Database = Any>,
+{
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM crate_owners WHERE crate_id = $1 AND user_id IS NOT NULL"
+    )
+    .bind(crate_id.to_string())
+    .fetch_one(executor)
+    .await?;
+
+    Ok(count)
+}
@@ -0,0 +1,337 @@
+// Crate search database functions for db/mod.rs
+//
+// Text search uses an FTS5 table on SQLite and a weighted tsvector on
+// PostgreSQL (see migration 0004); both index a crate's name, keywords,
+// description and latest readme, in decreasing order of weight.
+
+use crate::models::{Crate, CrateSearch, SearchSort};
+use anyhow::Result;
+use sqlx::Row;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::{crate_from_row, get_bool, DbBackend, DbConnection, DbPool, CRATE_COLUMNS};
+
+/// Days of downloads counted by `sort=recent-downloads`, as on crates.io.
+const RECENT_DOWNLOAD_DAYS: i64 = 90;
+
+/// Re-indexes a crate from its metadata and latest readme. Takes a
+/// connection so it can run inside the publish transaction.
+pub async fn refresh_crate_search(conn: &mut DbConnection, crate_id: Uuid) -> Result<()> {
+    match DbBackend::of_connection(conn) {
+        DbBackend::Sqlite => {
+            sqlx::query("DELETE FROM crate_search WHERE crate_id = $1")
+                .bind(crate_id.to_string())
+                .execute(&mut *conn)
+                .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO crate_search (crate_id, name, keywords, description, readme)
+                SELECT c.id, c.name, COALESCE(c.keywords, ''), COALESCE(c.description, ''),
+                       COALESCE((SELECT cv.readme FROM crate_versions cv WHERE cv.crate_id = c.id ORDER BY cv.created_at DESC LIMIT 1), '')
+                FROM crates c
+                WHERE c.id = $1
+                "#
+            )
+            .bind(crate_id.to_string())
+            .execute(&mut *conn)
+            .await?;
+        }
+        DbBackend::Postgres => {
+            sqlx::query(
+                r#"
+                INSERT INTO crate_search (crate_id, document)
+                SELECT c.id,
+                       setweight(to_tsvector('simple', c.name), 'A') ||
+                       setweight(to_tsvector('simple', COALESCE(c.keywords, '')), 'B') ||
+                       setweight(to_tsvector('simple', COALESCE(c.description, '')), 'C') ||
+                       setweight(to_tsvector('simple', COALESCE((SELECT cv.readme FROM crate_versions cv WHERE cv.crate_id = c.id ORDER BY cv.created_at DESC LIMIT 1), '')), 'D')
+                FROM crates c
+                WHERE c.id = $1
+                ON CONFLICT (crate_id) DO UPDATE SET document = excluded.document
+                "#
+            )
+            .bind(crate_id.to_string())
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// One page of matching crates together with the total number of matches,
+/// counted in the same statement so a page costs a single round trip.
+pub async fn search_crates(
+    pool: &DbPool,
+    search: &CrateSearch,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Crate>, i64)> {
+    let mut sql = SearchSql::new(search, DbBackend::of(pool));
+    let order_by = sql.order_by(search);
+
+    // SQLite refuses bm25() alongside a window function, so the total is a
+    // scalar subquery over the same joins and arguments instead of COUNT(*) OVER ()
+    let query = format!(
+        "SELECT {}, (SELECT COUNT(*) FROM crates c {} {}) AS total_count FROM crates c {} {} ORDER BY {} LIMIT {} OFFSET {}",
+        CRATE_COLUMNS, sql.joins, sql.where_clause(), sql.joins, sql.where_clause(), order_by, limit, offset
+    );
+
+    let mut rows = sqlx::query(&query);
+    for arg in &sql.args {
+        rows = rows.bind(arg);
+    }
+    let rows = rows.fetch_all(pool).await?;
+
+    let total = match rows.first() {
+        Some(row) => row.get::<i64, _>("total_count"),
+        None if offset > 0 => count_search_results(pool, search).await?,
+        None => 0,
+    };
+
+    let crates = rows.iter().map(crate_from_row).collect::<Result<Vec<_>>>()?;
+    Ok((crates, total))
+}
+
+async fn count_search_results(pool: &DbPool, search: &CrateSearch) -> Result<i64> {
+    let sql = SearchSql::new(search, DbBackend::of(pool));
+
+    let query = format!("SELECT COUNT(*) FROM crates c {} {}", sql.joins, sql.where_clause());
+
+    let mut count = sqlx::query_scalar::<_, i64>(&query);
+    for arg in &sql.args {
+        count = count.bind(arg);
+    }
+
+    Ok(count.fetch_one(pool).await?)
+}
+
+/// `(version, yanked)` for every version of the given crates, in one query.
+/// Enough to work out a crate's max version without loading full versions.
+pub async fn get_version_summaries(pool: &DbPool, crate_ids: &[Uuid]) -> Result<HashMap<Uuid, Vec<(String, bool)>>> {
+    let mut summaries: HashMap<Uuid, Vec<(String, bool)>> = HashMap::new();
+    if crate_ids.is_empty() {
+        return Ok(summaries);
+    }
+
+    let query = format!(
+        "SELECT crate_id, version, CAST(yanked AS INTEGER) AS yanked FROM crate_versions WHERE crate_id IN ({})",
+        placeholders(1, crate_ids.len())
+    );
+
+    let mut rows = sqlx::query(&query);
+    for crate_id in crate_ids {
+        rows = rows.bind(crate_id.to_string());
+    }
+
+    for row in rows.fetch_all(pool).await? {
+        let crate_id = Uuid::parse_str(&row.get::<String, _>("crate_id"))?;
+        summaries
+            .entry(crate_id)
+            .or_default()
+            .push((row.get("version"), get_bool(&row, "yanked")));
+    }
+
+    Ok(summaries)
+}
+
+/// Downloads over the last 90 days for each of the given crates, in one
+/// query. Crates without recent downloads are left out.
+pub async fn get_recent_downloads(pool: &DbPool, crate_ids: &[Uuid]) -> Result<HashMap<Uuid, i64>> {
+    let mut downloads = HashMap::new();
+    if crate_ids.is_empty() {
+        return Ok(downloads);
+    }
+
+    let query = format!(
+        "SELECT crate_id, CAST(COALESCE(SUM(count), 0) AS BIGINT) AS recent FROM download_metrics WHERE date >= $1 AND crate_id IN ({}) GROUP BY crate_id",
+        placeholders(2, crate_ids.len())
+    );
+
+    let mut rows = sqlx::query(&query).bind(recent_downloads_since());
+    for crate_id in crate_ids {
+        rows = rows.bind(crate_id.to_string());
+    }
+
+    for row in rows.fetch_all(pool).await? {
+        let crate_id = Uuid::parse_str(&row.get::<String, _>("crate_id"))?;
+        downloads.insert(crate_id, row.get("recent"));
+    }
+
+    Ok(downloads)
+}
+
+/// First day counted towards recent downloads, as `YYYY-MM-DD`.
+fn recent_downloads_since() -> String {
+    (chrono::Utc::now() - chrono::Duration::days(RECENT_DOWNLOAD_DAYS))
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// `$first, $first+1, ...` for an `IN` list of `count` values.
+fn placeholders(first: usize, count: usize) -> String {
+    (first..first + count)
+        .map(|n| format!("${}", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A `LIKE` pattern, escaped with `\`, matching `value` as a whole string
+/// anywhere in a JSON array. The value is JSON-encoded first so it matches
+/// the stored form, quotes and backslashes included.
+fn json_element_pattern(value: &str) -> String {
+    let encoded = serde_json::Value::from(value).to_string();
+    let mut pattern = String::with_capacity(encoded.len() + 2);
+    pattern.push('%');
+    for c in encoded.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// The joins and conditions for a search, with its arguments bound as
+/// `$1..$N` in the order they were added.
+struct SearchSql {
+    joins: &'static str,
+    conditions: Vec<String>,
+    /// Text-match ordering for `sort=relevance`, when there is search text
+    rank: Option<String>,
+    args: Vec<String>,
+}
+
+impl SearchSql {
+    fn new(search: &CrateSearch, backend: DbBackend) -> Self {
+        let mut sql = Self {
+            joins: "",
+            conditions: Vec::new(),
+            rank: None,
+            args: Vec::new(),
+        };
+
+        // Terms are purely alphanumeric, so they need no escaping in either
+        // query syntax; each one matches as a prefix and all must match.
+        if !search.terms.is_empty() {
+            match backend {
+                DbBackend::Sqlite => {
+                    let terms = search.terms.iter().map(|term| format!("\"{}\"*", term)).collect::<Vec<_>>();
+                    let terms = sql.bind(terms.join(" "));
+                    sql.joins = "JOIN crate_search ON crate_search.crate_id = c.id";
+                    sql.conditions.push(format!("crate_search MATCH {}", terms));
+                    // Column weights: crate_id (unindexed), name, keywords, description, readme
+                    sql.rank = Some("bm25(crate_search, 0.0, 10.0, 5.0, 3.0, 1.0) ASC".to_string());
+                }
+                DbBackend::Postgres => {
+                    let terms = search.terms.iter().map(|term| format!("{}:*", term)).collect::<Vec<_>>();
+                    let terms = sql.bind(terms.join(" & "));
+                    sql.joins = "JOIN crate_search s ON s.crate_id = c.id";
+                    sql.conditions.push(format!("s.document @@ to_tsquery('simple', {})", terms));
+                    sql.rank = Some(format!("ts_rank(s.document, to_tsquery('simple', {})) DESC", terms));
+                }
+            }
+        }
+
+        // Keywords and categories are stored as JSON arrays of strings
+        for keyword in &search.keywords {
+            let keyword = sql.bind(json_element_pattern(keyword));
+            sql.conditions.push(format!("LOWER(c.keywords) LIKE {} ESCAPE '\\'", keyword));
+        }
+
+        for category in &search.categories {
+            let category = sql.bind(json_element_pattern(category));
+            sql.conditions.push(format!("LOWER(c.categories) LIKE {} ESCAPE '\\'", category));
+        }
+
+        for owner in &search.owners {
+            let owner = sql.bind(owner.clone());
+            sql.conditions.push(format!(
+                "EXISTS (SELECT 1 FROM crate_owners co JOIN users u ON co.user_id = u.id WHERE co.crate_id = c.id AND LOWER(u.username) = {})",
+                owner
+            ));
+        }
+
+        for organization in &search.organizations {
+            let organization = sql.bind(organization.clone());
+            sql.conditions.push(format!(
+                "EXISTS (SELECT 1 FROM crate_owners co JOIN organizations o ON co.organization_id = o.id WHERE co.crate_id = c.id AND LOWER(o.name) = {})",
+                organization
+            ));
+        }
+
+        sql
+    }
+
+    fn bind(&mut self, value: String) -> String {
+        self.args.push(value);
+        format!("${}", self.args.len())
+    }
+
+    fn where_clause(&self) -> String {
+        if self.conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", self.conditions.join(" AND "))
+        }
+    }
+
+    /// The `ORDER BY` list for the requested sort. May bind further
+    /// arguments, so it must be built after every condition.
+    fn order_by(&mut self, search: &CrateSearch) -> String {
+        let mut order = Vec::new();
+
+        match search.sort {
+            SearchSort::Relevance => {
+                // An exact name match comes first, treating `-` and `_` alike
+                if !search.text.is_empty() {
+                    let name = self.bind(search.text.to_lowercase().replace('-', "_"));
+                    order.push(format!("CASE WHEN REPLACE(LOWER(c.name), '-', '_') = {} THEN 0 ELSE 1 END", name));
+                }
+                order.extend(self.rank.clone());
+                order.push("c.downloads DESC".to_string());
+            }
+            SearchSort::Downloads => order.push("c.downloads DESC".to_string()),
+            SearchSort::RecentDownloads => {
+                let since = self.bind(recent_downloads_since());
+                order.push(format!(
+                    "(SELECT COALESCE(SUM(dm.count), 0) FROM download_metrics dm WHERE dm.crate_id = c.id AND dm.date >= {}) DESC",
+                    since
+                ));
+            }
+            SearchSort::RecentUpdates => order.push(
+                "COALESCE((SELECT MAX(cv.created_at) FROM crate_versions cv WHERE cv.crate_id = c.id), c.updated_at) DESC".to_string()
+            ),
+            SearchSort::New => order.push("c.created_at DESC".to_string()),
+            SearchSort::Alpha => {}
+        }
+
+        order.push("c.name ASC".to_string());
+        order.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_element_patterns_escape_like_wildcards() {
+        let cases = [
+            ("cli", r#"%"cli"%"#),
+            ("%", r#"%"\%"%"#),
+            ("serde_json", r#"%"serde\_json"%"#),
+            ("50%_off", r#"%"50\%\_off"%"#),
+            // JSON escapes come first, then their backslashes are escaped too
+            (r#"say "hi""#, r#"%"say \\"hi\\""%"#),
+            (r"back\slash", r#"%"back\\\\slash"%"#),
+        ];
+
+        for (value, pattern) in cases {
+            assert_eq!(json_element_pattern(value), pattern, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+// Storage bookkeeping database functions for db/mod.rs
+
+use crate::models::{ArtifactOrigin, IntegrityIssue, IntegrityProblem, MirrorArtifact, StoredArtifact};
+use sqlx::Row;
+use super::DbPool;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use anyhow::Result;
+use std::collections::HashSet;
+
+/// Every published version's artifact, oldest first.
+pub async fn get_stored_artifacts(pool: &DbPool) -> Result<Vec<StoredArtifact>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT cv.id, c.name, cv.version, cv.checksum, cv.file_size
+        FROM crate_versions cv
+        JOIN crates c ON cv.crate_id = c.id
+        ORDER BY cv.created_at ASC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(StoredArtifact {
+                version_id: Uuid::parse_str(&row.get::<String, _>("id"))?,
+                crate_name: row.get("name"),
+                version: row.get("version"),
+                checksum: row.get("checksum"),
+                file_size: row.get("file_size"),
+            })
+        })
+        .collect()
+}
+
+/// Versions already copied to `target` by an earlier run of the migration.
+pub async fn get_migrated_version_ids(pool: &DbPool, target: &str) -> Result<HashSet<Uuid>> {
+    let rows = sqlx::query("SELECT version_id FROM storage_migrations WHERE target = $1 AND status = 'copied'")
+        .bind(target)
+        .fetch_all(pool)
+        .await?;
+
+    rows.iter()
+        .map(|row| Ok(Uuid::parse_str(&row.get::<String, _>("version_id"))?))
+        .collect()
+}
+
+/// Records the outcome of copying one version to `target`; `error` is
+/// `None` when the copy was verified.
+pub async fn record_storage_migration(
+    pool: &DbPool,
+    target: &str,
+    version_id: Uuid,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO storage_migrations (target, version_id, status, error, updated_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT(target, version_id) DO UPDATE SET
+            status = excluded.status,
+            error = excluded.error,
+            updated_at = excluded.updated_at
+        "#
+    )
+    .bind(target)
+    .bind(version_id.to_string())
+    .bind(if error.is_some() { "failed" } else { "copied" })
+    .bind(error)
+    .bind(Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Remembers a crate cached from upstream, with the checksum of the bytes
+/// that were stored.
+pub async fn record_mirror_artifact(
+    pool: &DbPool,
+    crate_name: &str,
+    version: &str,
+    checksum: &str,
+    file_size: i64,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO mirror_artifacts (crate_name, version, checksum, file_size, cached_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT(crate_name, version) DO UPDATE SET
+            checksum = excluded.checksum,
+            file_size = excluded.file_size,
+            cached_at = excluded.cached_at
+        "#
+    )
+    .bind(crate_name)
+    .bind(version)
+    .bind(checksum)
+    .bind(file_size)
+    .bind(Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_mirror_artifacts(pool: &DbPool) -> Result<Vec<MirrorArtifact>> {
+    let rows = sqlx::query(
+        "SELECT crate_name, version, checksum, file_size, cached_at FROM mirror_artifacts ORDER BY cached_at ASC"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(MirrorArtifact {
+                crate_name: row.get("crate_name"),
+                version: row.get("version"),
+                checksum: row.get("checksum"),
+                file_size: row.get("file_size"),
+                cached_at: parse_timestamp(row.get("cached_at"))?,
+            })
+        })
+        .collect()
+}
+
+/// Whether a published version or a mirrored crate refers to the blob with
+/// this checksum.
+pub async fn is_blob_referenced(pool: &DbPool, checksum: &str) -> Result<bool> {
+    let row = sqlx::query(
+        r#"
+        SELECT 1 FROM crate_versions WHERE checksum = $1
+        UNION ALL
+        SELECT 1 FROM mirror_artifacts WHERE checksum = $2
+        LIMIT 1
+        "#
+    )
+    .bind(checksum)
+    .bind(checksum)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+pub async fn get_mirror_artifact(pool: &DbPool, crate_name: &str, version: &str) -> Result<Option<MirrorArtifact>> {
+    let row = sqlx::query(
+        "SELECT crate_name, version, checksum, file_size, cached_at FROM mirror_artifacts WHERE crate_name = $1 AND version = $2"
+    )
+    .bind(crate_name)
+    .bind(version)
+    .fetch_optional(pool)
+    .await?;
+
+    row.map(|row| {
+        Ok(MirrorArtifact {
+            crate_name: row.get("crate_name"),
+            version: row.get("version"),
+            checksum: row.get("checksum"),
+            file_size: row.get("file_size"),
+            cached_at: parse_timestamp(row.get("cached_at"))?,
+        })
+    })
+    .transpose()
+}
+
+/// Records a failed integrity check. `detected_at` keeps the time of the
+/// first failure while the artifact stays broken.
+pub async fn record_integrity_issue(
+    pool: &DbPool,
+    crate_name: &str,
+    version: &str,
+    origin: ArtifactOrigin,
+    problem: IntegrityProblem,
+    expected_checksum: &str,
+    actual_checksum: Option<&str>,
+) -> Result<()> {
+    let now = Utc::now().to_rfc3339();
+
+    sqlx::query(
+        r#"
+        INSERT INTO integrity_issues
+            (crate_name, version, origin, problem, expected_checksum, actual_checksum, detected_at, checked_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $7)
+        ON CONFLICT(crate_name, version, origin) DO UPDATE SET
+            problem = excluded.problem,
+            expected_checksum = excluded.expected_checksum,
+            actual_checksum = excluded.actual_checksum,
+            checked_at = excluded.checked_at
+        "#
+    )
+    .bind(crate_name)
+    .bind(version)
+    .bind(origin.as_str())
+    .bind(problem.as_str())
+    .bind(expected_checksum)
+    .bind(actual_checksum)
+    .bind(now)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Forgets any earlier failure for an artifact that has verified cleanly.
+pub async fn clear_integrity_issue(pool: &DbPool, crate_name: &str, version: &str, origin: ArtifactOrigin) -> Result<()> {
+    sqlx::query("DELETE FROM integrity_issues WHERE crate_name = $1 AND version = $2 AND origin = $3")
+        .bind(crate_name)
+        .bind(version)
+        .bind(origin.as_str())
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_integrity_issues(pool: &DbPool) -> Result<Vec<IntegrityIssue>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT crate_name, version, origin, problem, expected_checksum, actual_checksum, detected_at, checked_at
+        FROM integrity_issues
+        ORDER BY detected_at ASC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(IntegrityIssue {
+                crate_name: row.get("crate_name"),
+                version: row.get("version"),
+                origin: match row.get::<String, _>("origin").as_str() {
+                    "mirror" => ArtifactOrigin::Mirror,
+                    _ => ArtifactOrigin::Published,
+                },
+                problem: match row.get::<String, _>("problem").as_str() {
+                    "missing" => IntegrityProblem::Missing,
+                    _ => IntegrityProblem::Corrupt,
+                },
+                expected_checksum: row.get("expected_checksum"),
+                actual_checksum: row.get("actual_checksum"),
+                detected_at: parse_timestamp(row.get("detected_at"))?,
+                checked_at: parse_timestamp(row.get("checked_at"))?,
+            })
+        })
+        .collect()
+}
+
+pub async fn count_integrity_issues(pool: &DbPool) -> Result<i64> {
+    let row = sqlx::query("SELECT COUNT(*) as count FROM integrity_issues")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(row.get("count"))
+}
+
+fn parse_timestamp(value: String) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(&value)?.with_timezone(&Utc))
+}
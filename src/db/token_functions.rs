@@ -0,0 +1,122 @@
+// API token database functions for db/mod.rs
+
+use crate::models::{ApiToken, TokenScope};
+use sqlx::Row;
+use super::{DbPool, DbRow};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use anyhow::Result;
+
+const API_TOKEN_COLUMNS: &str = "id, user_id, name, token_hash, scopes, crate_patterns, expires_at, last_used_at, created_at";
+
+pub async fn create_api_token(
+    pool: &DbPool,
+    user_id: Uuid,
+    name: &str,
+    token_hash: &str,
+    scopes: &[TokenScope],
+    crate_patterns: Option<&[String]>,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<ApiToken> {
+    let id = Uuid::new_v4();
+    let now = Utc::now();
+
+    sqlx::query(
+        r#"
+        INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, crate_patterns, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#
+    )
+    .bind(id.to_string())
+    .bind(user_id.to_string())
+    .bind(name)
+    .bind(token_hash)
+    .bind(serde_json::to_string(scopes)?)
+    .bind(crate_patterns.map(serde_json::to_string).transpose()?)
+    .bind(expires_at.map(|at| at.to_rfc3339()))
+    .bind(now.to_rfc3339())
+    .execute(pool)
+    .await?;
+
+    Ok(ApiToken {
+        id,
+        user_id,
+        name: name.to_string(),
+        token_hash: token_hash.to_string(),
+        scopes: scopes.to_vec(),
+        crate_patterns: crate_patterns.map(|patterns| patterns.to_vec()),
+        expires_at,
+        last_used_at: None,
+        created_at: now,
+    })
+}
+
+/// Looks up an unexpired token by the hash of its plaintext value.
+pub async fn get_api_token_by_hash(pool: &DbPool, token_hash: &str) -> Result<Option<ApiToken>> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM api_tokens WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > $2)",
+        API_TOKEN_COLUMNS
+    ))
+    .bind(token_hash)
+    .bind(Utc::now().to_rfc3339())
+    .fetch_optional(pool)
+    .await?;
+
+    row.as_ref().map(api_token_from_row).transpose()
+}
+
+pub async fn list_user_api_tokens(pool: &DbPool, user_id: Uuid) -> Result<Vec<ApiToken>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM api_tokens WHERE user_id = $1 ORDER BY created_at DESC",
+        API_TOKEN_COLUMNS
+    ))
+    .bind(user_id.to_string())
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter().map(api_token_from_row).collect()
+}
+
+/// Deletes one of the user's tokens, returning `false` if they have no such token.
+pub async fn delete_api_token(pool: &DbPool, user_id: Uuid, token_id: Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM api_tokens WHERE id = $1 AND user_id = $2")
+        .bind(token_id.to_string())
+        .bind(user_id.to_string())
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn touch_api_token(pool: &DbPool, token_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE api_tokens SET last_used_at = $1 WHERE id = $2")
+        .bind(Utc::now().to_rfc3339())
+        .bind(token_id.to_string())
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+fn api_token_from_row(row: &DbRow) -> Result<ApiToken> {
+    let parse_time = |value: Option<String>| -> Result<Option<DateTime<Utc>>> {
+        Ok(match value {
+            Some(value) => Some(chrono::DateTime::parse_from_rfc3339(&value)?.with_timezone(&chrono::Utc)),
+            None => None,
+        })
+    };
+
+    Ok(ApiToken {
+        id: Uuid::parse_str(&row.get::<String, _>("id"))?,
+        user_id: Uuid::parse_str(&row.get::<String, _>("user_id"))?,
+        name: row.get("name"),
+        token_hash: row.get("token_hash"),
+        scopes: serde_json::from_str(&row.get::<String, _>("scopes"))?,
+        crate_patterns: row.get::<Option<String>, _>("crate_patterns")
+            .map(|patterns| serde_json::from_str(&patterns))
+            .transpose()?,
+        expires_at: parse_time(row.get("expires_at"))?,
+        last_used_at: parse_time(row.get("last_used_at"))?,
+        created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))?.with_timezone(&chrono::Utc),
+    })
+}
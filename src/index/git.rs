@@ -0,0 +1,228 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{ChildStdout, Command};
+use tokio::{fs, sync::Mutex};
+use tracing::{debug, info};
+
+use crate::config::GitIndexConfig;
+use crate::db::{self, DbPool};
+use crate::models::IndexConfig;
+
+const BRANCH: &str = "master";
+
+/// A bare git repository of index files, kept in sync with the database.
+///
+/// Files are rendered into a private work tree next to the bare repository and
+/// committed there, so every publish or yank leaves a commit behind that
+/// doubles as an audit trail of index changes.
+#[derive(Clone)]
+pub struct GitIndex {
+    repo_path: PathBuf,
+    work_tree: PathBuf,
+    config: IndexConfig,
+    // Serializes writers; git itself refuses concurrent index updates
+    lock: Arc<Mutex<()>>,
+}
+
+/// Who a commit is attributed to in the index history.
+pub struct CommitAuthor<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+}
+
+impl GitIndex {
+    pub async fn open(git_config: &GitIndexConfig, config: IndexConfig) -> Result<Self> {
+        let base = PathBuf::from(&git_config.path);
+        let index = Self {
+            repo_path: base.join("index.git"),
+            work_tree: base.join("worktree"),
+            config,
+            lock: Arc::new(Mutex::new(())),
+        };
+
+        fs::create_dir_all(&index.work_tree).await?;
+
+        if !index.repo_path.join("HEAD").exists() {
+            fs::create_dir_all(&index.repo_path).await?;
+            run_git(&index.repo_path, &["init", "--bare", "--quiet", "--initial-branch", BRANCH]).await?;
+            info!("Initialized git index repository at {}", index.repo_path.display());
+        } else {
+            // Bring the work tree in line with the last commit before writing to it
+            let has_commits = index.git(&["rev-parse", "--verify", "--quiet", BRANCH]).await.is_ok();
+            if has_commits {
+                index.git(&["checkout", "--force", "--quiet", BRANCH]).await?;
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    /// Output of `git upload-pack --advertise-refs`, the body of a smart HTTP
+    /// `info/refs?service=git-upload-pack` response (without the service header).
+    pub async fn advertise_refs(&self) -> Result<Vec<u8>> {
+        let output = Command::new("git")
+            .args(["upload-pack", "--stateless-rpc", "--advertise-refs"])
+            .arg(&self.repo_path)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("failed to run git upload-pack")?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "git upload-pack failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Runs one stateless-rpc round of `git upload-pack`, feeding it the client's
+    /// request and returning its stdout for streaming back to the client.
+    pub fn upload_pack(&self, request: Vec<u8>) -> Result<ChildStdout> {
+        let mut child = Command::new("git")
+            .args(["upload-pack", "--stateless-rpc"])
+            .arg(&self.repo_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context("failed to run git upload-pack")?;
+
+        let mut stdin = child.stdin.take().context("git upload-pack stdin unavailable")?;
+        let stdout = child.stdout.take().context("git upload-pack stdout unavailable")?;
+
+        // Write the request and reap the process in the background so a large
+        // pack never deadlocks against an unread pipe
+        tokio::spawn(async move {
+            if let Err(e) = stdin.write_all(&request).await {
+                debug!("Failed to write upload-pack request: {}", e);
+            }
+            drop(stdin);
+            if let Err(e) = child.wait().await {
+                debug!("git upload-pack did not exit cleanly: {}", e);
+            }
+        });
+
+        Ok(stdout)
+    }
+
+    /// Re-renders every crate from the database and commits whatever changed.
+    /// Run at startup so a missed commit or a fresh repository heals itself.
+    pub async fn sync_all(&self, pool: &DbPool) -> Result<()> {
+        let _guard = self.lock.lock().await;
+
+        self.write_config().await?;
+        for name in db::get_all_crate_names(pool).await? {
+            self.write_crate(pool, &name).await?;
+        }
+
+        let author = CommitAuthor { name: "GhostCrate", email: "ghostcrate@localhost" };
+        if self.commit("Synchronize index with database", &author).await? {
+            info!("Git index synchronized with database");
+        }
+
+        Ok(())
+    }
+
+    /// Re-renders one crate's index file and commits it.
+    pub async fn update_crate(
+        &self,
+        pool: &DbPool,
+        name: &str,
+        message: &str,
+        author: &CommitAuthor<'_>,
+    ) -> Result<()> {
+        let _guard = self.lock.lock().await;
+
+        self.write_crate(pool, name).await?;
+        self.commit(message, author).await?;
+
+        Ok(())
+    }
+
+    async fn write_config(&self) -> Result<()> {
+        let config = serde_json::to_string_pretty(&self.config)?;
+        fs::write(self.work_tree.join("config.json"), config + "\n").await?;
+        Ok(())
+    }
+
+    async fn write_crate(&self, pool: &DbPool, name: &str) -> Result<()> {
+        let path = self.work_tree.join(super::index_file_path(name));
+
+        match super::render_index_file(pool, name).await? {
+            Some(file) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(&path, file).await?;
+            }
+            None => {
+                if path.exists() {
+                    fs::remove_file(&path).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Commits all pending changes, returning `false` when there was nothing to commit.
+    async fn commit(&self, message: &str, author: &CommitAuthor<'_>) -> Result<bool> {
+        self.git(&["add", "--all"]).await?;
+
+        let status = self.git(&["status", "--porcelain"]).await?;
+        if status.trim().is_empty() {
+            return Ok(false);
+        }
+
+        let author = format!("{} <{}>", author.name, author.email);
+        self.git(&[
+            "-c", "user.name=GhostCrate",
+            "-c", "user.email=ghostcrate@localhost",
+            "commit", "--quiet", "--author", &author, "-m", message,
+        ]).await?;
+
+        debug!("Committed to git index: {}", message);
+        Ok(true)
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        let git_dir = format!("--git-dir={}", self.repo_path.display());
+        let work_tree = format!("--work-tree={}", self.work_tree.display());
+
+        let mut full_args = vec![git_dir.as_str(), work_tree.as_str()];
+        full_args.extend_from_slice(args);
+
+        run_git(&self.work_tree, &full_args).await
+    }
+}
+
+async fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,244 @@
+#[cfg(feature = "ssr")]
+pub mod git;
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config::AppConfig;
+use crate::db::{self, DbPool};
+use crate::models::{CrateVersion, DependencyKind, IndexConfig, IndexDependency, IndexEntry, PublishDependency};
+
+/// Builds the `config.json` document Cargo reads from the index root.
+pub fn registry_config(config: &AppConfig) -> IndexConfig {
+    let base_url = config.registry.url.trim_end_matches('/');
+
+    IndexConfig {
+        dl: format!("{}/api/v1/crates/{{crate}}/{{version}}/download", base_url),
+        api: base_url.to_string(),
+        auth_required: config.registry.auth_required,
+    }
+}
+
+/// Returns the relative path of a crate's index file using Cargo's layout:
+/// `1/{name}`, `2/{name}`, `3/{c}/{name}` and `{ab}/{cd}/{name}` for longer names.
+pub fn index_file_path(name: &str) -> String {
+    let name = name.to_lowercase();
+
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+/// Renders the newline-delimited index file for a crate, or `None` if the crate
+/// does not exist or has no published versions.
+pub async fn render_index_file(pool: &DbPool, name: &str) -> Result<Option<String>> {
+    let crate_model = match db::get_crate_by_name_case_insensitive(pool, name).await? {
+        Some(crate_model) => crate_model,
+        None => return Ok(None),
+    };
+
+    let versions = db::get_crate_versions(pool, crate_model.id).await?;
+    if versions.is_empty() {
+        return Ok(None);
+    }
+
+    index_file(&crate_model.name, versions).map(Some)
+}
+
+/// One index line per version, oldest first.
+fn index_file(crate_name: &str, mut versions: Vec<CrateVersion>) -> Result<String> {
+    // Index files list versions in publish order
+    versions.sort_by_key(|version| version.created_at);
+
+    let mut file = String::new();
+    for version in &versions {
+        let entry = index_entry(crate_name, version)?;
+        file.push_str(&serde_json::to_string(&entry)?);
+        file.push('\n');
+    }
+
+    Ok(file)
+}
+
+pub fn index_entry(crate_name: &str, version: &CrateVersion) -> Result<IndexEntry> {
+    let deps: Vec<PublishDependency> = match &version.dependencies {
+        Some(deps) => serde_json::from_str(deps)?,
+        None => Vec::new(),
+    };
+
+    let all_features: HashMap<String, Vec<String>> = match &version.features {
+        Some(features) => serde_json::from_str(features)?,
+        None => HashMap::new(),
+    };
+
+    // Features using `dep:` or `?/` syntax must go in `features2` so older
+    // Cargo versions that can't parse them simply ignore them.
+    let mut features = BTreeMap::new();
+    let mut features2 = BTreeMap::new();
+    for (feature, values) in all_features {
+        if values.iter().any(|v| v.starts_with("dep:") || v.contains("?/")) {
+            features2.insert(feature, values);
+        } else {
+            features.insert(feature, values);
+        }
+    }
+
+    let (features2, v) = if features2.is_empty() {
+        (None, 1)
+    } else {
+        (Some(features2), 2)
+    };
+
+    Ok(IndexEntry {
+        name: crate_name.to_string(),
+        vers: version.version.clone(),
+        deps: deps.into_iter().map(index_dependency).collect(),
+        cksum: version.checksum.clone(),
+        features,
+        features2,
+        yanked: version.yanked,
+        links: version.links.clone(),
+        v,
+        rust_version: version.rust_version.clone(),
+    })
+}
+
+fn index_dependency(dep: PublishDependency) -> IndexDependency {
+    let kind = match dep.kind {
+        DependencyKind::Normal => "normal",
+        DependencyKind::Dev => "dev",
+        DependencyKind::Build => "build",
+    };
+
+    // A renamed dependency is listed under its alias, with the real crate in `package`
+    let (name, package) = match dep.explicit_name_in_toml {
+        Some(alias) => (alias, Some(dep.name)),
+        None => (dep.name, None),
+    };
+
+    IndexDependency {
+        name,
+        req: dep.version_req,
+        features: dep.features,
+        optional: dep.optional,
+        default_features: dep.default_features,
+        target: dep.target,
+        kind: kind.to_string(),
+        registry: dep.registry,
+        package,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn version(vers: &str, age_days: i64, features: serde_json::Value) -> CrateVersion {
+        CrateVersion {
+            id: Uuid::new_v4(),
+            crate_id: Uuid::nil(),
+            version: vers.to_string(),
+            checksum: format!("{}-checksum", vers),
+            file_size: 1024,
+            dependencies: None,
+            features: Some(features.to_string()),
+            yanked: false,
+            license: None,
+            readme: None,
+            links: None,
+            rust_version: None,
+            authors: None,
+            published_by: None,
+            created_at: Utc::now() - Duration::days(age_days),
+        }
+    }
+
+    #[test]
+    fn index_files_are_laid_out_by_name_length() {
+        let cases = [
+            ("a", "1/a"),
+            ("ab", "2/ab"),
+            ("abc", "3/a/abc"),
+            ("abcd", "ab/cd/abcd"),
+            ("serde_json", "se/rd/serde_json"),
+            ("Serde", "se/rd/serde"),
+            ("X", "1/x"),
+        ];
+
+        for (name, path) in cases {
+            assert_eq!(index_file_path(name), path, "{}", name);
+        }
+    }
+
+    #[test]
+    fn index_files_list_versions_oldest_first() {
+        // Versions come back from the database newest first
+        let versions = vec![
+            version("1.0.0", 1, json!({})),
+            version("0.2.0", 2, json!({})),
+            version("0.1.0", 3, json!({})),
+        ];
+
+        let file = index_file("demo", versions).unwrap();
+        let lines: Vec<serde_json::Value> = file.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let versions: Vec<&str> = lines.iter().map(|line| line["vers"].as_str().unwrap()).collect();
+        assert_eq!(versions, ["0.1.0", "0.2.0", "1.0.0"]);
+        assert!(file.ends_with('\n'));
+    }
+
+    #[test]
+    fn new_feature_syntax_goes_in_features2() {
+        let cases = [
+            (json!({"default": ["std"], "std": []}), false),
+            (json!({"default": ["std"], "serde": ["dep:serde"]}), true),
+            (json!({"std": [], "derive": ["serde?/derive"]}), true),
+        ];
+
+        for (features, uses_features2) in cases {
+            let entry = index_entry("demo", &version("1.0.0", 0, features.clone())).unwrap();
+            let features2 = entry.features2.clone().unwrap_or_default();
+
+            assert_eq!(entry.v, if uses_features2 { 2 } else { 1 }, "{}", features);
+            assert_eq!(features2.is_empty(), !uses_features2, "{}", features);
+            assert_eq!(entry.features.len() + features2.len(), features.as_object().unwrap().len());
+            for values in features2.values() {
+                assert!(values.iter().any(|v| v.starts_with("dep:") || v.contains("?/")), "{}", features);
+            }
+            for values in entry.features.values() {
+                assert!(!values.iter().any(|v| v.starts_with("dep:") || v.contains("?/")), "{}", features);
+            }
+
+            // Entries without features2 leave the field out entirely
+            let line = serde_json::to_value(&entry).unwrap();
+            assert_eq!(line.get("features2").is_some(), uses_features2, "{}", features);
+        }
+    }
+
+    #[test]
+    fn renamed_dependencies_are_listed_under_their_alias() {
+        let mut version = version("1.0.0", 0, json!({}));
+        version.dependencies = Some(
+            json!([
+                {"name": "serde", "version_req": "^1", "features": [], "optional": false, "default_features": true,
+                 "target": null, "kind": "normal", "registry": null, "explicit_name_in_toml": null},
+                {"name": "tokio", "version_req": "^1", "features": ["rt"], "optional": true, "default_features": false,
+                 "target": null, "kind": "dev", "registry": null, "explicit_name_in_toml": "async-rt"},
+            ])
+            .to_string(),
+        );
+
+        let entry = index_entry("demo", &version).unwrap();
+        let deps: Vec<_> = entry
+            .deps
+            .iter()
+            .map(|dep| (dep.name.as_str(), dep.package.as_deref(), dep.kind.as_str()))
+            .collect();
+        assert_eq!(deps, [("serde", None, "normal"), ("async-rt", Some("tokio"), "dev")]);
+    }
+}
@@ -3,20 +3,29 @@ pub mod db;
 pub mod auth;
 pub mod web;
 pub mod storage;
+pub mod backup;
 pub mod config;
+pub mod index;
+pub mod validation;
+pub mod error;
 
 use leptos::*;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[cfg(feature = "ssr")]
-use sqlx::SqlitePool;
+use crate::db::DbPool;
 
 #[cfg(feature = "ssr")]
 #[derive(Clone)]
 pub struct AppState {
     pub config: config::AppConfig,
-    pub pool: SqlitePool,
+    pub pool: DbPool,
     pub storage: storage::Storage,
+    pub storage_migration: storage::migration::MigrationTracker,
+    pub integrity_scrub: storage::integrity::ScrubTracker,
+    pub storage_gc: storage::gc::GcTracker,
+    pub git_index: Option<index::git::GitIndex>,
+    pub upstream_client: reqwest::Client,
 }
 
 #[wasm_bindgen]
@@ -1,5 +1,6 @@
 use axum::{
-    routing::{get, post, delete},
+    routing::{get, post, put, delete},
+    extract::DefaultBodyLimit,
     Router,
     response::Html,
     middleware,
@@ -18,15 +19,31 @@ use ghostcrate::{
     web::{
         auth_handlers::*, 
         cargo_handlers::*, 
-        admin_handlers::{admin_dashboard_handler, admin_users_handler},
+        admin_handlers::{
+            admin_dashboard_handler, admin_users_handler,
+            admin_storage_migration_handler, admin_start_storage_migration_handler,
+            admin_integrity_handler, admin_start_integrity_scrub_handler,
+            admin_storage_gc_handler, admin_start_storage_gc_handler,
+            admin_storage_replication_handler, admin_reconcile_storage_replica_handler,
+        },
         github_handlers::*,
         oidc_handlers::*,
         organization_handlers::*,
-        health_handlers::{health_handler, admin_stats_handler},
+        health_handlers::{health_handler, prometheus_metrics_handler, admin_stats_handler},
         mirror_handlers::*,
+        index_handlers::*,
+        owner_handlers::*,
+        token_handlers::*,
     },
-    db::initialize_database,
-    storage::Storage,
+    db::{self, connect_database, initialize_database, DbPool},
+    backup::{self, spawn_backups},
+    storage::{
+        Storage,
+        gc::{spawn_collector, GcTracker},
+        integrity::{spawn_scrubber, upstream_client, ScrubTracker},
+        migration::{migrate_storage, MigrationTracker},
+    },
+    index::{self, git::GitIndex},
     AppState,
 };
 
@@ -46,31 +63,115 @@ async fn main() -> anyhow::Result<()> {
     let config = AppConfig::from_env()?;
     info!("Configuration loaded successfully");
 
+    // `server --migrate-only [--dry-run]` applies (or just lists) pending schema migrations and exits
+    if std::env::args().any(|arg| arg == "--migrate-only") {
+        return run_schema_migrations(&config, std::env::args().any(|arg| arg == "--dry-run")).await;
+    }
+
+    // `server restore <archive> [--dry-run]` validates a backup and (unless dry-running) restores it, then exits
+    if std::env::args().nth(1).as_deref() == Some("restore") {
+        let archive = std::env::args().nth(2).filter(|arg| arg != "--dry-run").ok_or_else(|| {
+            anyhow::anyhow!("Usage: server restore <archive> [--dry-run]")
+        })?;
+        return run_restore(&config, std::path::Path::new(&archive), std::env::args().any(|arg| arg == "--dry-run")).await;
+    }
+
     // Initialize database
     let pool = initialize_database(&config.database.url).await?;
     info!("Database initialized successfully");
 
     // Initialize storage
-    let mut storage = Storage::new(config.storage.clone())?;
-    storage.init().await?;
+    let storage = Storage::new(config.storage.clone())
+        .await?
+        .with_replication(&config.storage_replication)
+        .await?
+        .with_encryption(&config.storage_encryption)
+        .await?
+        .with_cache(&config.storage_cache)
+        .await?;
     info!("Storage initialized successfully");
+
+    // `server migrate-storage` copies every artifact to the configured target and exits
+    if std::env::args().nth(1).as_deref() == Some("migrate-storage") {
+        return run_storage_migration(&config, &pool, &storage).await;
+    }
+
+    // `server rotate-storage-keys` re-wraps every data key with the current master key and exits
+    if std::env::args().nth(1).as_deref() == Some("rotate-storage-keys") {
+        return run_key_rotation(&storage).await;
+    }
+
+    // `server backup` writes a backup archive and exits
+    if std::env::args().nth(1).as_deref() == Some("backup") {
+        return run_backup(&config, &pool, &storage).await;
+    }
+
+    // Initialize git index
+    let git_index = if config.registry.git_index.enabled {
+        let git_index = GitIndex::open(&config.registry.git_index, index::registry_config(&config)).await?;
+        git_index.sync_all(&pool).await?;
+        info!("Git index initialized successfully");
+        Some(git_index)
+    } else {
+        None
+    };
     
     // App state
     let app_state = AppState {
         pool: pool.clone(),
         config: config.clone(),
         storage,
+        storage_migration: MigrationTracker::default(),
+        integrity_scrub: ScrubTracker::default(),
+        storage_gc: GcTracker::default(),
+        git_index,
+        upstream_client: upstream_client(&config)?,
     };
 
+    if config.integrity.scrub_enabled {
+        spawn_scrubber(
+            pool.clone(),
+            app_state.storage.clone(),
+            &config,
+            &app_state.upstream_client,
+            app_state.integrity_scrub.clone(),
+        );
+        info!("Integrity scrubber scheduled every {}h", config.integrity.scrub_interval_hours);
+    }
+
+    if config.storage_gc.enabled {
+        spawn_collector(pool.clone(), app_state.storage.clone(), &config.storage_gc, app_state.storage_gc.clone());
+        info!("Storage garbage collection scheduled every {}h", config.storage_gc.interval_hours);
+    }
+
+    if config.backup.enabled {
+        spawn_backups(pool.clone(), app_state.storage.clone(), config.clone());
+        info!("Backups scheduled every {}h into {}", config.backup.interval_hours, config.backup.path);
+    }
+
     let addr = SocketAddr::from(([127, 0, 0, 1], config.server.port));
 
     // Protected routes that require authentication
     let protected_routes = Router::new()
         // Cargo Registry API
-        .route("/api/v1/crates/new", post(publish_handler))
+        .route(
+            "/api/v1/crates/new",
+            put(publish_handler)
+                .post(publish_handler)
+                .layer(DefaultBodyLimit::max(publish_body_limit(config.registry.max_crate_size))),
+        )
+        .route("/api/v1/crates/:name/:version/yank", delete(yank_handler))
+        .route("/api/v1/crates/:name/:version/unyank", put(unyank_handler))
+        .route(
+            "/api/v1/crates/:name/owners",
+            put(add_owners_handler).delete(remove_owners_handler),
+        )
         // Auth routes
         .route("/api/auth/logout", post(logout_handler))
         .route("/api/auth/me", get(me_handler))
+        // API token routes
+        .route("/api/tokens", get(list_api_tokens_handler).post(create_api_token_handler))
+        .route("/api/tokens/:token_id", delete(revoke_api_token_handler))
         // Organization routes
         .route("/api/organizations", post(create_organization_handler))
         .route("/api/organizations/:org_id", get(get_organization_handler))
@@ -87,8 +188,36 @@ async fn main() -> anyhow::Result<()> {
         .route("/admin", get(admin_dashboard_handler))
         .route("/admin/api/stats", get(admin_stats_handler))
         .route("/admin/api/users", get(admin_users_handler))
+        .route(
+            "/admin/api/storage/migration",
+            get(admin_storage_migration_handler).post(admin_start_storage_migration_handler),
+        )
+        .route(
+            "/admin/api/storage/integrity",
+            get(admin_integrity_handler).post(admin_start_integrity_scrub_handler),
+        )
+        .route(
+            "/admin/api/storage/gc",
+            get(admin_storage_gc_handler).post(admin_start_storage_gc_handler),
+        )
+        .route(
+            "/admin/api/storage/replication",
+            get(admin_storage_replication_handler).post(admin_reconcile_storage_replica_handler),
+        )
         .layer(middleware::from_fn_with_state(app_state.clone(), auth_middleware));
 
+    // Index and download routes, which need a token when config.json says so
+    let mut index_routes = Router::new()
+        // Sparse registry index (sparse+ protocol)
+        .route("/index/*path", get(sparse_index_handler))
+        // Git registry index (smart HTTP, read-only)
+        .route("/git/index/info/refs", get(git_info_refs_handler))
+        .route("/git/index/git-upload-pack", post(git_upload_pack_handler))
+        .route("/api/v1/crates/:name/:version/download", get(download_handler));
+    if config.registry.auth_required {
+        index_routes = index_routes.layer(middleware::from_fn_with_state(app_state.clone(), auth_middleware));
+    }
+
     // Build our application with routes
     let app = Router::new()
         // Root route with basic HTML
@@ -97,10 +226,21 @@ async fn main() -> anyhow::Result<()> {
         .route("/config.json", get(config_handler))
         // Health and metrics routes (public)
         .route("/health", get(health_handler))
+        .route("/metrics", get(prometheus_metrics_handler))
         // Public Cargo Registry API v1
-        .route("/api/v1/crates/:name/:version/download", get(download_handler))
         .route("/api/v1/crates", get(search_handler))
         .route("/api/v1/crates/:name", get(crate_info_handler))
+        .route("/api/v1/crates/:name/versions", get(versions_handler))
+        .route("/api/v1/crates/:name/downloads", get(crate_downloads_handler))
+        .route("/api/v1/crates/:name/reverse_dependencies", get(reverse_dependencies_handler))
+        .route("/api/v1/crates/:name/:version", get(version_info_handler))
+        .route("/api/v1/crates/:name/:version/dependencies", get(version_dependencies_handler))
+        .route("/api/v1/crates/:name/:version/downloads", get(version_downloads_handler))
+        .route("/api/v1/crates/:name/:version/authors", get(version_authors_handler))
+        .route("/api/v1/crates/:name/:version/readme", get(version_readme_handler))
+        .route("/api/v1/crates/:name/owners", get(list_owners_handler))
+        .route("/api/v1/crates/:name/owner_user", get(list_owner_users_handler))
+        .route("/api/v1/crates/:name/owner_team", get(list_owner_teams_handler))
         // Public Authentication API
         .route("/api/auth/login", post(login_handler))
         .route("/api/auth/register", post(register_handler))
@@ -114,6 +254,8 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/mirror/sync", post(start_mirror_sync_handler))
         .route("/api/mirror/search", get(proxy_crates_io_search_handler))
         .route("/api/mirror/crate/:name/:version", get(proxy_crate_download_handler))
+        // Index and downloads
+        .merge(index_routes)
         // Protected routes
         .merge(protected_routes)
         // Static files
@@ -130,6 +272,113 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "ssr")]
+async fn run_schema_migrations(config: &AppConfig, dry_run: bool) -> anyhow::Result<()> {
+    let pool = connect_database(&config.database.url).await?;
+
+    if dry_run {
+        let pending = db::pending_migrations(&pool).await?;
+        if pending.is_empty() {
+            info!("Database schema is up to date");
+        }
+        for migration in pending {
+            info!("Would apply migration {} ({})", migration.version, migration.description);
+        }
+        return Ok(());
+    }
+
+    let applied = db::run_migrations(&pool).await?;
+    info!("Schema migrations finished: {} applied", applied.len());
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+async fn run_storage_migration(config: &AppConfig, pool: &DbPool, storage: &Storage) -> anyhow::Result<()> {
+    let target_config = config.storage_migration_target.clone().ok_or_else(|| {
+        anyhow::anyhow!("No migration target configured: set MIGRATION_TARGET_STORAGE_BACKEND and the matching MIGRATION_TARGET_* storage variables")
+    })?;
+    let target = Storage::new(target_config)
+        .await?
+        .with_encryption(&config.storage_encryption)
+        .await?;
+
+    let tracker = MigrationTracker::default();
+    tracker.try_start(storage, &target);
+    let progress = migrate_storage(pool, storage, &target, &tracker).await;
+
+    println!(
+        "Storage migration finished: {} copied ({} bytes), {} already migrated, {} failed",
+        progress.copied, progress.bytes_copied, progress.already_migrated, progress.failed
+    );
+
+    if let Some(error) = progress.error {
+        anyhow::bail!(error);
+    }
+    if progress.failed > 0 {
+        anyhow::bail!("{} artifact(s) could not be migrated; run the migration again to retry them", progress.failed);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+async fn run_key_rotation(storage: &Storage) -> anyhow::Result<()> {
+    let report = storage.rotate_encryption_keys().await?;
+
+    println!(
+        "Key rotation to {} finished: {} of {} data key(s) re-wrapped, {} already current, {} failed",
+        report.master_key_id, report.rewrapped, report.total, report.already_current, report.failed
+    );
+
+    if report.failed > 0 {
+        anyhow::bail!("{} data key(s) could not be re-wrapped; keep the previous master keys configured and run the rotation again", report.failed);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+async fn run_backup(config: &AppConfig, pool: &DbPool, storage: &Storage) -> anyhow::Result<()> {
+    let report = backup::create_backup(pool, storage, config).await?;
+
+    println!(
+        "Backup written to {} ({} bytes): {} of {} artifact(s) included, {} failed, {} old backup(s) pruned",
+        report.archive.display(), report.archive_size, report.artifacts_included, report.artifacts, report.artifacts_failed, report.pruned
+    );
+
+    if report.artifacts_failed > 0 {
+        anyhow::bail!("{} artifact(s) could not be backed up; run an integrity scrub to find out why", report.artifacts_failed);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+async fn run_restore(config: &AppConfig, archive: &std::path::Path, dry_run: bool) -> anyhow::Result<()> {
+    // Artifacts are written through the same layers the server uses
+    let storage = Storage::new(config.storage.clone())
+        .await?
+        .with_replication(&config.storage_replication)
+        .await?
+        .with_encryption(&config.storage_encryption)
+        .await?;
+
+    let report = backup::restore_backup(archive, &storage, config, dry_run).await?;
+
+    println!(
+        "{} backup taken at {} (schema version {}): {} artifact(s) {}, {} already in storage, {} missing",
+        if dry_run { "Validated" } else { "Restored" },
+        report.created_at,
+        report.schema_version,
+        report.artifacts_restored,
+        if dry_run { "to restore" } else { "restored" },
+        report.artifacts_present,
+        report.artifacts_missing
+    );
+
+    Ok(())
+}
+
 async fn home_handler() -> Html<&'static str> {
     Html(r#"
 <!DOCTYPE html>
@@ -196,7 +445,7 @@ async fn home_handler() -> Html<&'static str> {
         <p>Your production-ready GhostCrate registry is now running! Configure your Cargo to use this registry:</p>
         <pre>
 [registries]
-ghostcrate = { index = "http://localhost:8080" }
+ghostcrate = { index = "sparse+http://localhost:8080/index/" }
         </pre>
         
         <div class="grid">
@@ -258,6 +507,9 @@ ghostcrate = { index = "http://localhost:8080" }
                     <li><strong>POST</strong> /api/auth/register</li>
                     <li><strong>POST</strong> /api/auth/login</li>
                     <li><strong>GET</strong> /api/auth/me</li>
+                    <li><strong>GET</strong> /api/tokens</li>
+                    <li><strong>POST</strong> /api/tokens</li>
+                    <li><strong>DELETE</strong> /api/tokens/:id</li>
                     <li class="feature-new"><strong>GET</strong> /api/github/link</li>
                 </ul>
             </div>
@@ -273,9 +525,20 @@ ghostcrate = { index = "http://localhost:8080" }
             <div>
                 <h3>Cargo Registry</h3>
                 <ul>
-                    <li><strong>POST</strong> /api/v1/crates/new</li>
+                    <li><strong>PUT</strong> /api/v1/crates/new</li>
                     <li><strong>GET</strong> /api/v1/crates</li>
+                    <li><strong>GET</strong> /api/v1/crates/:name</li>
+                    <li><strong>GET</strong> /api/v1/crates/:name/versions</li>
+                    <li><strong>GET</strong> /api/v1/crates/:name/:version</li>
+                    <li><strong>GET</strong> /api/v1/crates/:name/:version/dependencies</li>
+                    <li><strong>GET</strong> /api/v1/crates/:name/reverse_dependencies</li>
                     <li><strong>GET</strong> /api/v1/crates/:name/:version/download</li>
+                    <li><strong>DELETE</strong> /api/v1/crates/:name/:version/yank</li>
+                    <li><strong>PUT</strong> /api/v1/crates/:name/:version/unyank</li>
+                    <li><strong>GET</strong> /api/v1/crates/:name/owners</li>
+                    <li><strong>PUT</strong> /api/v1/crates/:name/owners</li>
+                    <li><strong>DELETE</strong> /api/v1/crates/:name/owners</li>
+                    <li><strong>GET</strong> /index/:prefix/:name</li>
                 </ul>
             </div>
             <div>
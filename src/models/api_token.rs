@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// Prefix that tells API tokens apart from web session tokens.
+pub const API_TOKEN_PREFIX: &str = "gct_";
+
+/// What an API token may be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenScope {
+    PublishNew,
+    PublishUpdate,
+    Yank,
+    ChangeOwners,
+    Read,
+}
+
+impl TokenScope {
+    pub fn all() -> Vec<Self> {
+        vec![Self::PublishNew, Self::PublishUpdate, Self::Yank, Self::ChangeOwners, Self::Read]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<TokenScope>,
+    /// Crate name patterns the token is limited to; `None` means any crate
+    pub crate_patterns: Option<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How the current request was authenticated, inserted into request
+/// extensions next to the `User` by `auth_middleware`.
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// The API token used, or `None` for a web session
+    pub token_id: Option<Uuid>,
+    pub scopes: Vec<TokenScope>,
+    pub crate_patterns: Option<Vec<String>>,
+}
+
+impl AuthContext {
+    /// Web sessions act with the user's full rights.
+    pub fn session() -> Self {
+        Self {
+            token_id: None,
+            scopes: TokenScope::all(),
+            crate_patterns: None,
+        }
+    }
+
+    pub fn for_token(token: &ApiToken) -> Self {
+        Self {
+            token_id: Some(token.id),
+            scopes: token.scopes.clone(),
+            crate_patterns: token.crate_patterns.clone(),
+        }
+    }
+
+    pub fn is_session(&self) -> bool {
+        self.token_id.is_none()
+    }
+
+    pub fn has_scope(&self, scope: TokenScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Whether the credential carries `scope` and may act on `crate_name`.
+    pub fn permits(&self, scope: TokenScope, crate_name: &str) -> bool {
+        if !self.has_scope(scope) {
+            return false;
+        }
+
+        match &self.crate_patterns {
+            Some(patterns) => patterns.iter().any(|pattern| crate_pattern_matches(pattern, crate_name)),
+            None => true,
+        }
+    }
+}
+
+/// Matches a crate name against a pattern that is either an exact name or a
+/// prefix ending in `*`, the same rules crates.io uses for token crate scopes.
+/// Like crate names themselves, `-` and `_` are interchangeable.
+pub fn crate_pattern_matches(pattern: &str, crate_name: &str) -> bool {
+    let normalize = |s: &str| s.to_lowercase().replace('-', "_");
+    let crate_name = normalize(crate_name);
+
+    match pattern.strip_suffix('*') {
+        Some(prefix) => crate_name.starts_with(&normalize(prefix)),
+        None => crate_name == normalize(pattern),
+    }
+}
+
+pub fn is_valid_crate_pattern(pattern: &str) -> bool {
+    let name = pattern.strip_suffix('*').unwrap_or(pattern);
+
+    // A bare `*` is allowed, otherwise the pattern must look like a crate name
+    pattern == "*"
+        || (!name.is_empty()
+            && name.len() <= 64
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
+
+// Request/Response DTOs
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    /// Defaults to every scope
+    pub scopes: Option<Vec<TokenScope>>,
+    pub crates: Option<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub crates: Option<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Returned once on creation; the plaintext token is never shown again.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiTokenResponse {
+    pub token: String,
+    pub api_token: ApiTokenResponse,
+}
+
+impl From<ApiToken> for ApiTokenResponse {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            scopes: token.scopes,
+            crates: token.crate_patterns,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            created_at: token.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crate_patterns_match_names_and_prefixes() {
+        let cases = [
+            ("serde", "serde", true),
+            ("serde", "serde_json", false),
+            ("serde*", "serde_json", true),
+            ("serde*", "serde", true),
+            ("serde*", "ser", false),
+            ("*", "anything", true),
+            ("serde-json", "serde_json", true),
+            ("serde_json", "serde-json", true),
+            ("my-crate-*", "my_crate_derive", true),
+            ("Serde", "serde", true),
+            ("tokio", "serde", false),
+        ];
+
+        for (pattern, name, matches) in cases {
+            assert_eq!(crate_pattern_matches(pattern, name), matches, "{} against {}", pattern, name);
+        }
+    }
+
+    #[test]
+    fn token_scopes_limit_crates() {
+        let mut context = AuthContext::session();
+        assert!(context.permits(TokenScope::Yank, "anything"));
+
+        context.scopes = vec![TokenScope::PublishUpdate];
+        context.crate_patterns = Some(vec!["ghost-*".to_string(), "serde".to_string()]);
+        assert!(context.permits(TokenScope::PublishUpdate, "ghost_crate"));
+        assert!(context.permits(TokenScope::PublishUpdate, "serde"));
+        assert!(!context.permits(TokenScope::PublishUpdate, "tokio"));
+        assert!(!context.permits(TokenScope::PublishNew, "ghost_crate"));
+    }
+
+    #[test]
+    fn crate_patterns_are_validated() {
+        for pattern in ["*", "serde", "serde-*", "serde_json", "a*"] {
+            assert!(is_valid_crate_pattern(pattern), "{}", pattern);
+        }
+        for pattern in ["", "**", "ser*de", "serde json", "../serde", "*serde"] {
+            assert!(!is_valid_crate_pattern(pattern), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A published version's artifact, as recorded in `crate_versions`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredArtifact {
+    pub version_id: Uuid,
+    pub crate_name: String,
+    pub version: String,
+    /// Hex-encoded sha256 of the `.crate` file
+    pub checksum: String,
+    pub file_size: i64,
+}
+
+/// A crate cached from the upstream registry by the crates.io mirror.
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorArtifact {
+    pub crate_name: String,
+    pub version: String,
+    /// Hex-encoded sha256 of the `.crate` file as it was downloaded
+    pub checksum: String,
+    pub file_size: i64,
+    pub cached_at: DateTime<Utc>,
+}
+
+/// Whether an artifact was published here or cached from upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactOrigin {
+    Published,
+    Mirror,
+}
+
+impl ArtifactOrigin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Published => "published",
+            Self::Mirror => "mirror",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrityProblem {
+    Missing,
+    Corrupt,
+}
+
+impl IntegrityProblem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Corrupt => "corrupt",
+        }
+    }
+}
+
+/// An artifact that failed its most recent integrity check.
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityIssue {
+    pub crate_name: String,
+    pub version: String,
+    pub origin: ArtifactOrigin,
+    pub problem: IntegrityProblem,
+    pub expected_checksum: String,
+    /// Checksum of the bytes actually stored, for corrupt artifacts
+    pub actual_checksum: Option<String>,
+    /// When the problem was first seen
+    pub detected_at: DateTime<Utc>,
+    pub checked_at: DateTime<Utc>,
+}
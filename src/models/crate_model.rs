@@ -33,9 +33,24 @@ pub struct CrateVersion {
     pub yanked: bool,
     pub license: Option<String>,
     pub readme: Option<String>,
+    pub links: Option<String>,
+    pub rust_version: Option<String>,
+    pub authors: Option<String>, // JSON encoded Vec<String>
+    pub published_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
 }
 
+/// A version of another crate that may depend on a given crate, along with
+/// what the reverse dependencies listing shows next to it.
+#[derive(Debug, Clone)]
+pub struct DependentVersion {
+    pub crate_name: String,
+    pub version: CrateVersion,
+    pub downloads: i64,
+    pub publisher_login: Option<String>,
+    pub publisher_avatar: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dependency {
     pub name: String,
@@ -75,6 +90,8 @@ pub struct PublishRequest {
     pub repository: Option<String>,
     pub badges: HashMap<String, serde_json::Value>,
     pub links: Option<String>,
+    #[serde(default)]
+    pub rust_version: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -114,7 +131,9 @@ pub struct CrateResponse {
 
 #[derive(Debug, Serialize)]
 pub struct VersionResponse {
-    pub id: i64,
+    pub id: String,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
     pub num: String,
     pub dl_path: String,
     pub readme_path: String,
@@ -126,6 +145,8 @@ pub struct VersionResponse {
     pub license: Option<String>,
     pub links: VersionLinksResponse,
     pub crate_size: Option<i64>,
+    pub checksum: String,
+    pub rust_version: Option<String>,
     pub published_by: Option<UserLinkResponse>,
     pub audit_actions: Vec<serde_json::Value>,
 }
@@ -147,7 +168,7 @@ pub struct VersionLinksResponse {
     pub authors: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct UserLinkResponse {
     pub id: i64,
     pub login: String,
@@ -156,6 +177,97 @@ pub struct UserLinkResponse {
     pub url: String,
 }
 
+/// Cargo and crates.io clients expect numeric user ids; derive a stable one
+/// from the UUID.
+pub fn numeric_id(id: &Uuid) -> u32 {
+    let bytes = id.as_bytes();
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrateDetailResponse {
+    #[serde(rename = "crate")]
+    pub crate_data: CrateResponse,
+    pub versions: Vec<VersionResponse>,
+    pub keywords: Vec<KeywordResponse>,
+    pub categories: Vec<CategoryResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeywordResponse {
+    pub id: String,
+    pub keyword: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryResponse {
+    pub id: String,
+    pub category: String,
+    pub slug: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionsResponse {
+    pub versions: Vec<VersionResponse>,
+    pub meta: SearchMeta,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SingleVersionResponse {
+    pub version: VersionResponse,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependenciesResponse {
+    pub dependencies: Vec<DependencyResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependencyResponse {
+    pub id: String,
+    pub version_id: String,
+    /// Name of the crate depended on
+    pub crate_id: String,
+    pub req: String,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    pub target: Option<String>,
+    pub kind: DependencyKind,
+    pub downloads: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReverseDependenciesResponse {
+    pub dependencies: Vec<DependencyResponse>,
+    pub versions: Vec<VersionResponse>,
+    pub meta: SearchMeta,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionDownloadsResponse {
+    pub version_downloads: Vec<VersionDownloadResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionDownloadResponse {
+    /// Id of the version the downloads belong to
+    pub version: String,
+    pub downloads: i64,
+    pub date: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorsResponse {
+    pub users: Vec<UserLinkResponse>,
+    pub meta: AuthorsMeta,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorsMeta {
+    pub names: Vec<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SearchResponse {
     pub crates: Vec<CrateResponse>,
@@ -113,6 +113,8 @@ pub struct HealthStatus {
     pub version: String,
     pub database: HealthComponent,
     pub storage: HealthComponent,
+    /// Whether stored artifacts still match their checksums, as of the last scrub
+    pub integrity: HealthComponent,
     pub uptime_seconds: u64,
     pub memory_usage_mb: u64,
 }
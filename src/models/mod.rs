@@ -5,6 +5,11 @@ pub mod organization;
 pub mod metrics;
 pub mod github;
 pub mod oidc;
+pub mod index;
+pub mod owner;
+pub mod api_token;
+pub mod artifact;
+pub mod search;
 
 pub use user::*;
 pub use session::*;
@@ -12,4 +17,9 @@ pub use crate_model::*;
 pub use organization::*;
 pub use metrics::*;
 pub use github::*;
-pub use oidc::*;
\ No newline at end of file
+pub use oidc::*;
+pub use index::*;
+pub use owner::*;
+pub use api_token::*;
+pub use artifact::*;
+pub use search::*;
\ No newline at end of file
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+use super::numeric_id;
+
+/// Prefix that marks an organization in owner logins, e.g. `org:platform`.
+pub const TEAM_LOGIN_PREFIX: &str = "org:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnerKind {
+    User,
+    Team,
+}
+
+/// A user or organization allowed to publish and yank a crate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateOwner {
+    pub kind: OwnerKind,
+    /// The user's or organization's id, depending on `kind`
+    pub owner_id: Uuid,
+    pub login: String,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Cargo owner API types
+#[derive(Debug, Deserialize)]
+pub struct OwnersRequest {
+    pub users: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OwnersResponse {
+    pub users: Vec<OwnerResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeamsResponse {
+    pub teams: Vec<OwnerResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OwnerResponse {
+    pub id: u32,
+    pub login: String,
+    pub kind: OwnerKind,
+    pub url: String,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OwnersChangeResponse {
+    pub ok: bool,
+    pub msg: String,
+}
+
+impl From<CrateOwner> for OwnerResponse {
+    fn from(owner: CrateOwner) -> Self {
+        let url = match owner.kind {
+            OwnerKind::User => format!("/users/{}", owner.login),
+            OwnerKind::Team => format!(
+                "/organizations/{}",
+                owner.login.trim_start_matches(TEAM_LOGIN_PREFIX)
+            ),
+        };
+
+        Self {
+            id: numeric_id(&owner.owner_id),
+            login: owner.login,
+            kind: owner.kind,
+            url,
+            name: owner.name,
+            avatar: owner.avatar,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use super::TEAM_LOGIN_PREFIX;
+
+/// Result orders accepted by `sort=`, named as on crates.io.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SearchSort {
+    /// Best text match first; by downloads when there is no search text
+    #[default]
+    Relevance,
+    Downloads,
+    /// Downloads over the last 90 days
+    RecentDownloads,
+    /// Most recently published version first
+    RecentUpdates,
+    /// Newest crates first
+    New,
+    Alpha,
+}
+
+/// A parsed crate search: free text plus the filters crates.io accepts,
+/// either inline in the query (`keyword:cli owner:alice`) or as separate
+/// parameters.
+#[derive(Debug, Clone, Default)]
+pub struct CrateSearch {
+    /// Lowercased words to match against name, keywords, description and readme
+    pub terms: Vec<String>,
+    /// The text part of the query as typed, for spotting an exact name match
+    pub text: String,
+    pub keywords: Vec<String>,
+    pub categories: Vec<String>,
+    /// Usernames that must be among the crate's owners
+    pub owners: Vec<String>,
+    /// Organization names that must be among the crate's owners
+    pub organizations: Vec<String>,
+    pub sort: SearchSort,
+}
+
+impl CrateSearch {
+    pub fn parse(query: &str, sort: SearchSort) -> Self {
+        let mut search = Self { sort, ..Self::default() };
+        let mut text = Vec::new();
+
+        for word in query.split_whitespace() {
+            let Some((filter, value)) = word.split_once(':').filter(|(_, value)| !value.is_empty()) else {
+                text.push(word);
+                continue;
+            };
+
+            let value = value.to_lowercase();
+            match filter.to_lowercase().as_str() {
+                "keyword" => search.keywords.push(value),
+                "category" => search.categories.push(value),
+                "owner" => match value.strip_prefix(TEAM_LOGIN_PREFIX) {
+                    Some(org) => search.organizations.push(org.to_string()),
+                    None => search.owners.push(value),
+                },
+                "org" => search.organizations.push(value),
+                _ => text.push(word),
+            }
+        }
+
+        search.text = text.join(" ");
+        search.terms = search
+            .text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        search
+    }
+
+    pub fn with_keyword(mut self, keyword: Option<&str>) -> Self {
+        self.keywords.extend(keyword.filter(|k| !k.is_empty()).map(str::to_lowercase));
+        self
+    }
+
+    pub fn with_category(mut self, category: Option<&str>) -> Self {
+        self.categories.extend(category.filter(|c| !c.is_empty()).map(str::to_lowercase));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_are_pulled_out_of_the_query() {
+        let search = CrateSearch::parse(
+            "http keyword:CLI category:web-programming owner:Alice owner:org:Ghost org:tools client",
+            SearchSort::Downloads,
+        );
+
+        assert_eq!(search.text, "http client");
+        assert_eq!(search.terms, ["http", "client"]);
+        assert_eq!(search.keywords, ["cli"]);
+        assert_eq!(search.categories, ["web-programming"]);
+        assert_eq!(search.owners, ["alice"]);
+        assert_eq!(search.organizations, ["ghost", "tools"]);
+        assert_eq!(search.sort, SearchSort::Downloads);
+    }
+
+    #[test]
+    fn unknown_and_empty_filters_stay_in_the_text() {
+        let search = CrateSearch::parse("std::fs keyword: version:1", SearchSort::Relevance);
+
+        assert_eq!(search.text, "std::fs keyword: version:1");
+        assert_eq!(search.terms, ["std", "fs", "keyword", "version", "1"]);
+        assert!(search.keywords.is_empty());
+    }
+
+    #[test]
+    fn terms_are_stripped_to_alphanumerics() {
+        let search = CrateSearch::parse("Serde-JSON  (async)_runtime! v2.0 über", SearchSort::Relevance);
+        assert_eq!(search.terms, ["serde", "json", "async", "runtime", "v2", "0", "über"]);
+
+        let search = CrateSearch::parse("--- %% __", SearchSort::Relevance);
+        assert!(search.terms.is_empty());
+    }
+
+    #[test]
+    fn like_wildcards_in_filters_are_kept_as_typed() {
+        // Escaping them is up to the query that uses them
+        let search = CrateSearch::parse("keyword:% keyword:serde_json category:50%_off", SearchSort::Relevance)
+            .with_keyword(Some("A_B"))
+            .with_category(Some(""));
+
+        assert!(search.text.is_empty() && search.terms.is_empty());
+        assert_eq!(search.keywords, ["%", "serde_json", "a_b"]);
+        assert_eq!(search.categories, ["50%_off"]);
+    }
+}
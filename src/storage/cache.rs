@@ -0,0 +1,487 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_util::io::StreamReader;
+
+use super::{blob_digest, ArtifactMeta, ArtifactStore, ArtifactStream};
+use crate::config::CacheConfig;
+
+/// How many chunks a cache fill may read ahead of the client it serves.
+const FILL_BUFFER_CHUNKS: usize = 16;
+
+/// A read-through cache that keeps recently downloaded blobs on local disk,
+/// in front of a (usually remote) store. Only blobs are cached: their
+/// contents never change, and a blob is only cached once the copy has been
+/// checked against the digest in its key. Cached copies are served without
+/// hashing them again. On a miss the blob is streamed to the client while
+/// it is copied into the cache, rather than held in memory.
+///
+/// With storage encryption enabled the cache sits in front of the decrypting
+/// store, so it holds plaintext crates on local disk.
+pub struct CachedStore {
+    inner: Arc<dyn ArtifactStore>,
+    cache: Arc<CacheDir>,
+}
+
+/// The cache directory and what it holds, shared with the tasks filling it.
+struct CacheDir {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+    stats: CacheCounters,
+}
+
+/// Cached digests by recency. `order` maps each entry's last use to its
+/// digest, so the least recently used entry is always the first.
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    order: BTreeMap<u64, String>,
+    bytes: u64,
+    clock: u64,
+}
+
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+}
+
+impl CacheIndex {
+    /// Marks an entry as just used. Returns `false` if it isn't cached.
+    fn touch(&mut self, digest: &str) -> bool {
+        self.clock += 1;
+        let Some(entry) = self.entries.get_mut(digest) else { return false };
+
+        self.order.remove(&entry.last_used);
+        entry.last_used = self.clock;
+        self.order.insert(self.clock, digest.to_string());
+        true
+    }
+
+    fn insert(&mut self, digest: &str, size: u64) {
+        self.remove(digest);
+        self.clock += 1;
+        self.entries.insert(digest.to_string(), CacheEntry { size, last_used: self.clock });
+        self.order.insert(self.clock, digest.to_string());
+        self.bytes += size;
+    }
+
+    fn remove(&mut self, digest: &str) -> bool {
+        let Some(entry) = self.entries.remove(digest) else { return false };
+        self.order.remove(&entry.last_used);
+        self.bytes -= entry.size;
+        true
+    }
+
+    /// Drops least recently used entries until `bytes` fits in `max_bytes`,
+    /// returning the digests whose files should be deleted.
+    fn evict_to(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.bytes > max_bytes {
+            let Some((_, digest)) = self.order.pop_first() else { break };
+            if let Some(entry) = self.entries.remove(&digest) {
+                self.bytes -= entry.size;
+            }
+            evicted.push(digest);
+        }
+        evicted
+    }
+}
+
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    invalid: AtomicU64,
+}
+
+/// Cache counters, as exported in metrics.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Copies fetched for the cache that failed checksum validation
+    pub invalid: u64,
+    pub entries: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+impl CachedStore {
+    /// Wraps `inner`, picking up any blobs already cached in `config.path`
+    /// from a previous run. The least recently modified ones are evicted
+    /// first if they no longer fit the budget.
+    pub async fn new(inner: Arc<dyn ArtifactStore>, config: &CacheConfig) -> Result<Self> {
+        let dir = PathBuf::from(&config.path);
+        fs::create_dir_all(&dir).await?;
+
+        let mut existing = Vec::new();
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !metadata.is_file() {
+                continue;
+            }
+            if !is_digest(&name) {
+                // Leftover temporary file from an interrupted write
+                let _ = fs::remove_file(entry.path()).await;
+                continue;
+            }
+            existing.push((metadata.modified().ok(), name, metadata.len()));
+        }
+        existing.sort();
+
+        let cache = CacheDir {
+            dir,
+            max_bytes: config.max_bytes,
+            index: Mutex::new(CacheIndex::default()),
+            stats: CacheCounters::default(),
+        };
+
+        let evicted = {
+            let mut index = cache.index.lock().unwrap();
+            for (_, digest, size) in &existing {
+                index.insert(digest, *size);
+            }
+            index.evict_to(cache.max_bytes)
+        };
+        cache.remove_files(&evicted).await;
+
+        tracing::info!(
+            "Storage cache initialized at {} with {} cached blob(s), budget {} bytes",
+            cache.dir.display(),
+            existing.len() - evicted.len(),
+            cache.max_bytes
+        );
+        Ok(Self { inner, cache: Arc::new(cache) })
+    }
+
+    /// The store being cached.
+    pub fn inner(&self) -> &Arc<dyn ArtifactStore> {
+        &self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let cache = &self.cache;
+        let index = cache.index.lock().unwrap();
+        CacheStats {
+            hits: cache.stats.hits.load(Ordering::Relaxed),
+            misses: cache.stats.misses.load(Ordering::Relaxed),
+            evictions: cache.stats.evictions.load(Ordering::Relaxed),
+            invalid: cache.stats.invalid.load(Ordering::Relaxed),
+            entries: index.entries.len(),
+            bytes: index.bytes,
+            max_bytes: cache.max_bytes,
+        }
+    }
+
+    /// Serves a blob from the inner store while a background task copies it
+    /// into the cache. The copy carries on if the client goes away, so the
+    /// next request for the blob is still a hit.
+    fn fetch(&self, digest: &str, artifact: ArtifactStream) -> ArtifactStream {
+        let (sender, receiver) = mpsc::channel(FILL_BUFFER_CHUNKS);
+        tokio::spawn(self.cache.clone().fill(digest.to_string(), artifact.reader, sender));
+
+        let chunks = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        });
+        ArtifactStream { reader: Box::pin(StreamReader::new(chunks)), size: artifact.size }
+    }
+}
+
+impl CacheDir {
+    fn path(&self, digest: &str) -> PathBuf {
+        self.dir.join(digest)
+    }
+
+    /// Opens a cached blob, or returns `None` if it isn't cached. Copies are
+    /// verified when they are cached, so they are served as they are.
+    async fn open_cached(&self, digest: &str) -> Result<Option<ArtifactStream>> {
+        if !self.index.lock().unwrap().touch(digest) {
+            return Ok(None);
+        }
+
+        let file = match fs::File::open(self.path(digest)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.index.lock().unwrap().remove(digest);
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let size = file.metadata().await?.len();
+        Ok(Some(ArtifactStream { reader: Box::pin(file), size: Some(size) }))
+    }
+
+    /// Passes a blob from the inner store on to `sender` chunk by chunk,
+    /// copying it into a temporary file in the cache directory as it goes.
+    /// The file is moved into the cache if the whole blob matches its digest
+    /// and fits the budget. Read errors go to the client; failing to cache
+    /// only loses the copy.
+    async fn fill(
+        self: Arc<Self>,
+        digest: String,
+        mut reader: Pin<Box<dyn AsyncRead + Send>>,
+        sender: mpsc::Sender<std::io::Result<Bytes>>,
+    ) {
+        let temp_path = self.path(&digest).with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        let mut file = match fs::File::create(&temp_path).await {
+            Ok(file) => Some(file),
+            Err(e) => {
+                tracing::warn!("Failed to cache blob {}: {}", digest, e);
+                None
+            }
+        };
+
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        let mut buf = vec![0; 64 * 1024];
+        let mut client_gone = false;
+        loop {
+            let read = match reader.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    file = None;
+                    break;
+                }
+            };
+            let chunk = Bytes::copy_from_slice(&buf[..read]);
+            size += read as u64;
+
+            // Blobs larger than the whole cache are never kept
+            if size > self.max_bytes {
+                file = None;
+            }
+            if let Some(writer) = file.as_mut() {
+                hasher.update(&chunk);
+                if let Err(e) = writer.write_all(&chunk).await {
+                    tracing::warn!("Failed to cache blob {}: {}", digest, e);
+                    file = None;
+                }
+            }
+
+            if !client_gone && sender.send(Ok(chunk)).await.is_err() {
+                client_gone = true;
+            }
+            if client_gone && file.is_none() {
+                break;
+            }
+        }
+        drop(sender);
+
+        if let Some(file) = file {
+            let actual = format!("{:x}", hasher.finalize());
+            if actual != digest {
+                // A corrupt remote copy is served as-is for the integrity
+                // scrubber to report, but never cached
+                tracing::warn!("Not caching blob {}: contents do not match its digest", digest);
+                self.stats.invalid.fetch_add(1, Ordering::Relaxed);
+            } else if let Err(e) = self.add_cached(&digest, file, &temp_path, size).await {
+                tracing::warn!("Failed to cache blob {}: {:#}", digest, e);
+            }
+        }
+        let _ = fs::remove_file(&temp_path).await;
+    }
+
+    /// Moves a verified blob into the cache and evicts whatever no longer fits.
+    async fn add_cached(&self, digest: &str, mut file: fs::File, temp_path: &Path, size: u64) -> Result<()> {
+        file.flush().await?;
+        file.sync_all().await?;
+        fs::rename(temp_path, self.path(digest)).await?;
+
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            index.insert(digest, size);
+            index.evict_to(self.max_bytes)
+        };
+        self.stats.evictions.fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        self.remove_files(&evicted).await;
+
+        Ok(())
+    }
+
+    /// Drops a blob from the cache, e.g. because the underlying object was
+    /// deleted or replaced.
+    async fn evict(&self, digest: &str) {
+        if self.index.lock().unwrap().remove(digest) {
+            self.remove_files(&[digest.to_string()]).await;
+        }
+    }
+
+    async fn remove_files(&self, digests: &[String]) {
+        for digest in digests {
+            if let Err(e) = fs::remove_file(self.path(digest)).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("Failed to remove cached blob {}: {}", digest, e);
+                }
+            }
+        }
+    }
+}
+
+/// Empties a cache directory, returning how many blobs it held. Only for
+/// when no server is using it, e.g. after restoring a backup, whose storage
+/// may no longer hold what was cached.
+pub async fn clear_cache_dir(dir: &Path) -> Result<usize> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut cleared = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.metadata().await?.is_file() {
+            continue;
+        }
+        fs::remove_file(entry.path()).await?;
+        if is_digest(&entry.file_name().to_string_lossy()) {
+            cleared += 1;
+        }
+    }
+
+    Ok(cleared)
+}
+
+fn is_digest(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[async_trait]
+impl ArtifactStore for CachedStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.inner.put(key, data).await?;
+        if let Some(digest) = blob_digest(key) {
+            self.cache.evict(digest).await;
+        }
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        self.inner.put_file(key, path).await?;
+        if let Some(digest) = blob_digest(key) {
+            self.cache.evict(digest).await;
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<ArtifactStream>> {
+        let Some(digest) = blob_digest(key).filter(|digest| is_digest(digest)) else {
+            return self.inner.get(key).await;
+        };
+
+        match self.cache.open_cached(digest).await {
+            Ok(Some(cached)) => {
+                self.cache.stats.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Some(cached));
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to read cached blob {}: {:#}", digest, e),
+        }
+        self.cache.stats.misses.fetch_add(1, Ordering::Relaxed);
+
+        let Some(artifact) = self.inner.get(key).await? else { return Ok(None) };
+        Ok(Some(self.fetch(digest, artifact)))
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ArtifactMeta>> {
+        self.inner.head(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(key).await?;
+        if let Some(digest) = blob_digest(key) {
+            self.cache.evict(digest).await;
+        }
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ArtifactMeta>> {
+        self.inner.list(prefix).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.inner.rename(from, to).await?;
+        for digest in [blob_digest(from), blob_digest(to)].into_iter().flatten() {
+            self.cache.evict(digest).await;
+        }
+        Ok(())
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>> {
+        self.inner.presigned_url(key, expires_in).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{blob_key, sha256_hex, LocalStore};
+
+    async fn read_all(store: &CachedStore, key: &str) -> Vec<u8> {
+        let mut artifact = store.get(key).await.unwrap().unwrap();
+        let mut data = Vec::new();
+        artifact.reader.read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    /// Waits for the background fill of a miss to finish.
+    async fn wait_for(store: &CachedStore, done: impl Fn(&CacheStats) -> bool) {
+        for _ in 0..100 {
+            if done(&store.stats()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the cache fill never finished: {:?}", store.stats());
+    }
+
+    #[tokio::test]
+    async fn misses_are_streamed_and_only_verified_copies_cached() {
+        let root = std::env::temp_dir().join(format!("ghostcrate-cache-{}", uuid::Uuid::new_v4().simple()));
+        let inner: Arc<dyn ArtifactStore> = Arc::new(LocalStore::new(root.join("store")).await.unwrap());
+        let config = CacheConfig {
+            enabled: true,
+            path: root.join("cache").display().to_string(),
+            max_bytes: 1024 * 1024,
+        };
+        let store = CachedStore::new(inner.clone(), &config).await.unwrap();
+
+        // Several chunks, so the client and the fill overlap
+        let data: Vec<u8> = (0..300 * 1024).map(|i| (i % 251) as u8).collect();
+        let key = blob_key(&sha256_hex(&data));
+        inner.put(&key, &data).await.unwrap();
+
+        assert_eq!(read_all(&store, &key).await, data);
+        wait_for(&store, |stats| stats.entries == 1).await;
+        assert_eq!(read_all(&store, &key).await, data);
+        let stats = store.stats();
+        assert_eq!((stats.hits, stats.misses, stats.bytes), (1, 1, data.len() as u64));
+
+        // A copy that does not match its digest is served but not cached
+        let corrupt_key = blob_key(&sha256_hex(b"expected"));
+        inner.put(&corrupt_key, b"tampered").await.unwrap();
+        assert_eq!(read_all(&store, &corrupt_key).await, b"tampered");
+        wait_for(&store, |stats| stats.invalid == 1).await;
+        assert_eq!((store.stats().invalid, store.stats().entries), (1, 1));
+
+        let _ = fs::remove_dir_all(&root).await;
+    }
+}
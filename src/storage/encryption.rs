@@ -0,0 +1,489 @@
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::io::StreamReader;
+
+use super::{ArtifactMeta, ArtifactStore, ArtifactStream, KeyLocks};
+use crate::config::{EncryptionConfig, MasterKeyConfig};
+
+/// Artifacts are encrypted in segments of this much plaintext, so they can be
+/// decrypted while they are streamed instead of all at once.
+const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Size of the AES-GCM tag appended to every segment.
+const TAG_SIZE: usize = 16;
+
+/// Where the wrapped data key of each encrypted object is kept, under the
+/// object's own key. Keeping them apart from the objects is what lets a key
+/// rotation re-wrap them without rewriting any artifact.
+const KEY_PREFIX: &str = "keys/";
+
+/// Where the wrapped data key of the object at `key` is stored.
+pub fn data_key_path(key: &str) -> String {
+    format!("{}{}.key", KEY_PREFIX, key)
+}
+
+/// The object a [`data_key_path`] belongs to.
+fn object_key(path: &str) -> Option<&str> {
+    path.strip_prefix(KEY_PREFIX)?.strip_suffix(".key")
+}
+
+/// A data key as stored, encrypted with the master key it names.
+#[derive(Debug, Serialize, Deserialize)]
+struct WrappedKey {
+    master_key_id: String,
+    nonce: String,
+    key: String,
+}
+
+#[derive(Clone)]
+struct MasterKey {
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+impl MasterKey {
+    /// Reads a base64-encoded 256-bit key from the config or its key file.
+    async fn load(config: &MasterKeyConfig) -> Result<Self> {
+        let encoded = match (&config.key, &config.key_file) {
+            (Some(key), _) => key.clone(),
+            (None, Some(path)) => fs::read_to_string(path)
+                .await
+                .with_context(|| format!("failed to read master key file {}", path))?,
+            (None, None) => anyhow::bail!("master key {} has neither a key nor a key file", config.id),
+        };
+
+        let bytes = BASE64
+            .decode(encoded.trim())
+            .with_context(|| format!("master key {} is not valid base64", config.id))?;
+        if bytes.len() != 32 {
+            anyhow::bail!("master key {} must be 32 bytes, not {}", config.id, bytes.len());
+        }
+
+        Ok(Self {
+            id: config.id.clone(),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)),
+        })
+    }
+
+    /// The master key id is authenticated along with the data key, so a
+    /// wrapped key can't be passed off as belonging to another master key.
+    fn wrap(&self, data_key: &Key<Aes256Gcm>) -> Result<WrappedKey> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let wrapped = self.cipher
+            .encrypt(&nonce, Payload { msg: data_key.as_slice(), aad: self.id.as_bytes() })
+            .map_err(|_| anyhow::anyhow!("failed to wrap data key"))?;
+
+        Ok(WrappedKey {
+            master_key_id: self.id.clone(),
+            nonce: BASE64.encode(nonce),
+            key: BASE64.encode(wrapped),
+        })
+    }
+
+    fn unwrap(&self, wrapped: &WrappedKey) -> Result<Key<Aes256Gcm>> {
+        let nonce = BASE64.decode(&wrapped.nonce)?;
+        if nonce.len() != 12 {
+            anyhow::bail!("wrapped data key has an invalid nonce");
+        }
+
+        let key = self.cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload { msg: &BASE64.decode(&wrapped.key)?, aad: self.id.as_bytes() },
+            )
+            .map_err(|_| anyhow::anyhow!("failed to unwrap data key with master key {}", self.id))?;
+        if key.len() != 32 {
+            anyhow::bail!("unwrapped data key has the wrong length");
+        }
+
+        Ok(Key::<Aes256Gcm>::clone_from_slice(&key))
+    }
+}
+
+/// Outcome of re-wrapping data keys with the current master key.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KeyRotationReport {
+    pub master_key_id: String,
+    /// Data keys found in storage
+    pub total: usize,
+    /// Keys already wrapped by the current master key
+    pub already_current: usize,
+    pub rewrapped: usize,
+    /// Keys that couldn't be unwrapped or written back
+    pub failed: usize,
+}
+
+/// Envelope encryption in front of another store. Every object is encrypted
+/// with its own random AES-256-GCM data key, which is stored wrapped by the
+/// current master key under [`KEY_PREFIX`]. Objects without a data key were
+/// written before encryption was enabled and are read as they are.
+///
+/// Encrypted objects are a sequence of segments of [`SEGMENT_SIZE`] bytes of
+/// plaintext, each sealed with a nonce made of its index and whether it is
+/// the last one. The last segment is always shorter than the others, possibly
+/// empty, so truncated and reordered objects fail to decrypt.
+///
+/// An object and its data key are written separately, so every operation
+/// holds a lock on the object's key; otherwise two writers of the same key
+/// could leave one's data key next to the other's ciphertext.
+pub struct EncryptedStore {
+    inner: Arc<dyn ArtifactStore>,
+    /// The current master key first, then the ones it replaced
+    master_keys: Vec<MasterKey>,
+    locks: KeyLocks,
+}
+
+impl EncryptedStore {
+    pub async fn new(inner: Arc<dyn ArtifactStore>, config: &EncryptionConfig) -> Result<Self> {
+        let current = config.master_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("storage encryption is enabled but no master key is configured")
+        })?;
+
+        let mut master_keys = Vec::new();
+        for key_config in std::iter::once(current).chain(&config.previous_master_keys) {
+            if master_keys.iter().any(|key: &MasterKey| key.id == key_config.id) {
+                anyhow::bail!("master key id {} is configured more than once", key_config.id);
+            }
+            master_keys.push(MasterKey::load(key_config).await?);
+        }
+
+        tracing::info!(
+            "Storage encryption enabled with master key {} ({} previous key(s))",
+            current.id,
+            master_keys.len() - 1
+        );
+        Ok(Self { inner, master_keys, locks: KeyLocks::new() })
+    }
+
+    /// The store holding the encrypted objects and their wrapped data keys.
+    pub fn inner(&self) -> &Arc<dyn ArtifactStore> {
+        &self.inner
+    }
+
+    fn current_key(&self) -> &MasterKey {
+        &self.master_keys[0]
+    }
+
+    fn master_key(&self, id: &str) -> Result<&MasterKey> {
+        self.master_keys
+            .iter()
+            .find(|key| key.id == id)
+            .ok_or_else(|| anyhow::anyhow!("data key is wrapped by unknown master key {}", id))
+    }
+
+    /// Generates a data key for a new object and stores it, wrapped.
+    async fn new_data_key(&self, key: &str) -> Result<Aes256Gcm> {
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let wrapped = self.current_key().wrap(&data_key)?;
+        self.inner.put(&data_key_path(key), &serde_json::to_vec(&wrapped)?).await?;
+
+        Ok(Aes256Gcm::new(&data_key))
+    }
+
+    async fn read_wrapped_key(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let Some(mut object) = self.inner.get(path).await? else { return Ok(None) };
+        let mut data = Vec::new();
+        object.reader.read_to_end(&mut data).await?;
+        Ok(Some(data))
+    }
+
+    /// The cipher for an object, or `None` if it isn't encrypted.
+    async fn data_key(&self, key: &str) -> Result<Option<Aes256Gcm>> {
+        let Some(data) = self.read_wrapped_key(&data_key_path(key)).await? else { return Ok(None) };
+        let wrapped: WrappedKey = serde_json::from_slice(&data)
+            .with_context(|| format!("invalid data key for {}", key))?;
+        let data_key = self.master_key(&wrapped.master_key_id)?.unwrap(&wrapped)?;
+
+        Ok(Some(Aes256Gcm::new(&data_key)))
+    }
+
+    /// Re-wraps every data key that isn't wrapped by the current master key.
+    /// Only the keys are rewritten; the artifacts they encrypt are untouched.
+    /// Once this reports no failures, the previous master keys can be removed
+    /// from the configuration.
+    pub async fn rotate_keys(&self) -> Result<KeyRotationReport> {
+        let current = self.current_key();
+        let mut report = KeyRotationReport {
+            master_key_id: current.id.clone(),
+            ..Default::default()
+        };
+
+        for object in self.inner.list(KEY_PREFIX).await? {
+            report.total += 1;
+            match self.rewrap(&object.key).await {
+                Ok(true) => report.rewrapped += 1,
+                Ok(false) => report.already_current += 1,
+                Err(e) => {
+                    tracing::warn!("Failed to re-wrap data key {}: {:#}", object.key, e);
+                    report.failed += 1;
+                }
+            }
+        }
+
+        tracing::info!(
+            "Data key rotation to {} finished: {} re-wrapped, {} already current, {} failed",
+            report.master_key_id, report.rewrapped, report.already_current, report.failed
+        );
+        Ok(report)
+    }
+
+    /// Returns `false` if the key was already wrapped by the current master key.
+    async fn rewrap(&self, path: &str) -> Result<bool> {
+        // A put of the object in the meantime would be left with the old data key
+        let _lock = self.locks.lock(object_key(path).unwrap_or(path)).await;
+
+        let Some(data) = self.read_wrapped_key(path).await? else { return Ok(false) };
+        let wrapped: WrappedKey = serde_json::from_slice(&data)?;
+
+        let current = self.current_key();
+        if wrapped.master_key_id == current.id {
+            return Ok(false);
+        }
+
+        let data_key = self.master_key(&wrapped.master_key_id)?.unwrap(&wrapped)?;
+        self.inner.put(path, &serde_json::to_vec(&current.wrap(&data_key)?)?).await?;
+        Ok(true)
+    }
+}
+
+fn segment_nonce(index: u64, last: bool) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[..8].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Fills `buf` unless the reader ends first, returning how much was read.
+async fn read_segment(reader: &mut (dyn AsyncRead + Send + Unpin), buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let read = reader.read(&mut buf[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
+
+/// Encrypts everything `reader` yields into `writer`.
+async fn seal(
+    cipher: &Aes256Gcm,
+    reader: &mut (dyn AsyncRead + Send + Unpin),
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+) -> Result<()> {
+    let mut buf = vec![0; SEGMENT_SIZE];
+    let mut index = 0;
+    loop {
+        let filled = read_segment(reader, &mut buf).await?;
+        let last = filled < SEGMENT_SIZE;
+
+        let segment = cipher
+            .encrypt(Nonce::from_slice(&segment_nonce(index, last)), &buf[..filled])
+            .map_err(|_| anyhow::anyhow!("failed to encrypt artifact"))?;
+        writer.write_all(&segment).await?;
+
+        if last {
+            break;
+        }
+        index += 1;
+    }
+
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Raised, inside an [`std::io::ErrorKind::InvalidData`] error, when an
+/// object fails authentication: it was modified, truncated or reordered
+/// after it was written.
+#[derive(Debug, thiserror::Error)]
+#[error("artifact failed to decrypt")]
+pub struct DecryptionFailed;
+
+impl DecryptionFailed {
+    /// Whether `error` was caused by an object failing authentication.
+    pub fn is_cause_of(error: &anyhow::Error) -> bool {
+        error.chain().any(|cause| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .and_then(|error| error.get_ref())
+                .is_some_and(|inner| inner.is::<DecryptionFailed>())
+        })
+    }
+}
+
+/// Decrypts an object one segment at a time as it is read. A segment that
+/// fails authentication ends the stream with a [`DecryptionFailed`] error.
+fn open(cipher: Aes256Gcm, reader: Pin<Box<dyn AsyncRead + Send>>) -> Pin<Box<dyn AsyncRead + Send>> {
+    let segments = futures::stream::try_unfold(Some((reader, 0u64)), move |state| {
+        let cipher = cipher.clone();
+        async move {
+            let Some((mut reader, index)) = state else {
+                return Ok::<_, std::io::Error>(None);
+            };
+
+            let mut buf = vec![0; SEGMENT_SIZE + TAG_SIZE];
+            let filled = read_segment(&mut reader, &mut buf).await?;
+            let last = filled < buf.len();
+
+            let plaintext = cipher
+                .decrypt(Nonce::from_slice(&segment_nonce(index, last)), &buf[..filled])
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, DecryptionFailed))?;
+
+            Ok(Some((Bytes::from(plaintext), (!last).then_some((reader, index + 1)))))
+        }
+    });
+
+    Box::pin(StreamReader::new(segments))
+}
+
+/// Size of the plaintext an encrypted object of `sealed` bytes holds.
+fn plaintext_size(sealed: u64) -> Option<u64> {
+    let segment = (SEGMENT_SIZE + TAG_SIZE) as u64;
+    let last = (sealed % segment).checked_sub(TAG_SIZE as u64)?;
+    Some(sealed / segment * SEGMENT_SIZE as u64 + last)
+}
+
+#[async_trait]
+impl ArtifactStore for EncryptedStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let _lock = self.locks.lock(key).await;
+
+        // The data key goes first: an object without one would be served as plaintext
+        let cipher = self.new_data_key(key).await?;
+
+        let mut sealed = Vec::with_capacity(data.len() + data.len() / SEGMENT_SIZE * TAG_SIZE + TAG_SIZE);
+        seal(&cipher, &mut &data[..], &mut sealed).await?;
+        self.inner.put(key, &sealed).await
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        let _lock = self.locks.lock(key).await;
+        let cipher = self.new_data_key(key).await?;
+
+        let temp_path = std::env::temp_dir().join(format!("ghostcrate-sealed-{}", uuid::Uuid::new_v4().simple()));
+        let result: Result<()> = async {
+            let mut source = fs::File::open(path).await?;
+            let mut sealed = fs::File::create(&temp_path).await?;
+            seal(&cipher, &mut source, &mut sealed).await?;
+            self.inner.put_file(key, &temp_path).await
+        }
+        .await;
+
+        let _ = fs::remove_file(&temp_path).await;
+        result
+    }
+
+    /// The lock is only held until the object is opened: the stream reads
+    /// the object as it was then, which the data key read with it matches.
+    async fn get(&self, key: &str) -> Result<Option<ArtifactStream>> {
+        let _lock = self.locks.lock(key).await;
+        let Some(cipher) = self.data_key(key).await? else {
+            return self.inner.get(key).await;
+        };
+
+        Ok(self.inner.get(key).await?.map(|artifact| ArtifactStream {
+            size: artifact.size.and_then(plaintext_size),
+            reader: open(cipher, artifact.reader),
+        }))
+    }
+
+    /// Sizes are those of the stored, encrypted objects.
+    async fn head(&self, key: &str) -> Result<Option<ArtifactMeta>> {
+        self.inner.head(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let _lock = self.locks.lock(key).await;
+        self.inner.delete(key).await?;
+        self.inner.delete(&data_key_path(key)).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ArtifactMeta>> {
+        let mut objects = self.inner.list(prefix).await?;
+        objects.retain(|object| !object.key.starts_with(KEY_PREFIX));
+        Ok(objects)
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let _locks = self.locks.lock_all(&[from, to]).await;
+        let (from_key, to_key) = (data_key_path(from), data_key_path(to));
+
+        // The data key moves before the object, so the object is never without it
+        let wrapped = self.read_wrapped_key(&from_key).await?;
+        match &wrapped {
+            Some(data) => self.inner.put(&to_key, data).await?,
+            None => self.inner.delete(&to_key).await?,
+        }
+
+        self.inner.rename(from, to).await?;
+        if wrapped.is_some() {
+            self.inner.delete(&from_key).await?;
+        }
+        Ok(())
+    }
+
+    /// A presigned URL would hand out ciphertext, so downloads are always
+    /// decrypted and streamed through the registry.
+    async fn presigned_url(&self, _key: &str, _expires_in: Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStore;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_puts_of_the_same_key_stay_decryptable() {
+        let root = std::env::temp_dir().join(format!("ghostcrate-encryption-{}", uuid::Uuid::new_v4().simple()));
+        let inner: Arc<dyn ArtifactStore> = Arc::new(LocalStore::new(&root).await.unwrap());
+        let config = EncryptionConfig {
+            enabled: true,
+            master_key: Some(MasterKeyConfig {
+                id: "test".to_string(),
+                key: Some(BASE64.encode([7u8; 32])),
+                key_file: None,
+            }),
+            previous_master_keys: Vec::new(),
+        };
+        let store = Arc::new(EncryptedStore::new(inner, &config).await.unwrap());
+
+        // Several segments each, so the writes overlap
+        let payloads: Vec<Vec<u8>> = (0..16u8).map(|i| vec![i; 3 * SEGMENT_SIZE + 100]).collect();
+
+        for round in 0..5 {
+            let writers: Vec<_> = payloads
+                .iter()
+                .cloned()
+                .map(|payload| {
+                    let store = store.clone();
+                    tokio::spawn(async move { store.put("blobs/same", &payload).await })
+                })
+                .collect();
+            for writer in writers {
+                writer.await.unwrap().unwrap();
+            }
+
+            let mut artifact = store.get("blobs/same").await.unwrap().unwrap();
+            let mut data = Vec::new();
+            artifact.reader.read_to_end(&mut data).await.unwrap_or_else(|e| panic!("round {}: {}", round, e));
+            assert!(payloads.contains(&data), "round {}: read back a mix of writes", round);
+        }
+
+        let _ = fs::remove_dir_all(&root).await;
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::{blob_digest, blob_key, ArtifactMeta, Storage};
+use crate::config::GcConfig;
+use crate::db::{self, DbPool};
+
+/// Prefixes the collector looks for orphans under. Nothing is stored under
+/// `crates/` any more, so whatever the layout migration left there is swept up.
+const COLLECTED_PREFIXES: &[&str] = &["blobs/", "crates/", "staging/"];
+
+/// Where orphans are moved to in dry-run mode, under their original key.
+const QUARANTINE_PREFIX: &str = "quarantine/";
+
+/// Outcome of the current (or last) collection.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GcReport {
+    pub running: bool,
+    /// Orphans were quarantined rather than deleted
+    pub dry_run: bool,
+    pub grace_period_hours: u64,
+    /// Objects listed from storage
+    pub scanned: usize,
+    /// Orphans deleted, or quarantined in dry-run mode
+    pub orphans: Vec<OrphanArtifact>,
+    /// Orphans still inside the grace period, left for a later run
+    pub skipped_recent: usize,
+    /// Bytes freed by deleting orphans; in dry-run mode, bytes that would be
+    pub reclaimed_bytes: u64,
+    /// Orphans that could not be removed
+    pub errors: usize,
+    /// Why the collection stopped early, if it did
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanArtifact {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Shares collection progress between the task running it and the admin API.
+#[derive(Clone, Default)]
+pub struct GcTracker(Arc<Mutex<GcReport>>);
+
+impl GcTracker {
+    pub fn snapshot(&self) -> GcReport {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Resets the report for a new collection, or returns `false` if one is
+    /// already running.
+    pub fn try_start(&self, options: &GcOptions) -> bool {
+        let mut report = self.0.lock().unwrap();
+        if report.running {
+            return false;
+        }
+
+        *report = GcReport {
+            running: true,
+            dry_run: options.dry_run,
+            grace_period_hours: options.grace_period_hours,
+            started_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        true
+    }
+
+    fn update(&self, f: impl FnOnce(&mut GcReport)) {
+        f(&mut self.0.lock().unwrap())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    pub grace_period_hours: u64,
+    pub dry_run: bool,
+}
+
+impl From<&GcConfig> for GcOptions {
+    fn from(config: &GcConfig) -> Self {
+        Self {
+            grace_period_hours: config.grace_period_hours,
+            dry_run: config.dry_run,
+        }
+    }
+}
+
+/// Removes blobs whose digest neither `crate_versions` nor `mirror_artifacts`
+/// refers to, along with abandoned staged uploads and other leftovers of
+/// failed publishes.
+/// Only orphans last modified before the grace period are touched.
+///
+/// The tracker must have been started with [`GcTracker::try_start`].
+pub async fn collect_garbage(
+    pool: &DbPool,
+    storage: &Storage,
+    options: &GcOptions,
+    tracker: &GcTracker,
+) -> GcReport {
+    if let Err(e) = run_gc(pool, storage, options, tracker).await {
+        tracing::error!("Storage garbage collection stopped: {:#}", e);
+        tracker.update(|report| report.error = Some(format!("{:#}", e)));
+    }
+
+    tracker.update(|report| {
+        report.running = false;
+        report.finished_at = Some(Utc::now());
+    });
+
+    let report = tracker.snapshot();
+    tracing::info!(
+        "Storage garbage collection finished: {} orphan(s) {}, {} bytes reclaimed, {} within the grace period",
+        report.orphans.len(),
+        if report.dry_run { "quarantined" } else { "deleted" },
+        report.reclaimed_bytes,
+        report.skipped_recent
+    );
+    report
+}
+
+/// Collects every `interval_hours`, skipping a round if a collection started
+/// from the admin API is still running.
+pub fn spawn_collector(pool: DbPool, storage: Storage, config: &GcConfig, tracker: GcTracker) {
+    let period = Duration::from_secs(config.interval_hours.max(1) * 60 * 60);
+    let options = GcOptions::from(config);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if tracker.try_start(&options) {
+                collect_garbage(&pool, &storage, &options, &tracker).await;
+            }
+        }
+    });
+}
+
+async fn run_gc(pool: &DbPool, storage: &Storage, options: &GcOptions, tracker: &GcTracker) -> Result<()> {
+    // Read the database first: anything published after this is too new to collect
+    let mut referenced: HashSet<String> = db::get_stored_artifacts(pool)
+        .await?
+        .iter()
+        .map(|artifact| blob_key(&artifact.checksum))
+        .collect();
+    referenced.extend(
+        db::get_mirror_artifacts(pool)
+            .await?
+            .iter()
+            .map(|artifact| blob_key(&artifact.checksum)),
+    );
+
+    let cutoff = Utc::now() - chrono::Duration::hours(options.grace_period_hours as i64);
+
+    let mut objects = Vec::new();
+    for prefix in COLLECTED_PREFIXES {
+        objects.extend(storage.store().list(prefix).await?);
+    }
+    tracker.update(|report| report.scanned = objects.len());
+
+    for object in objects.iter().filter(|object| !referenced.contains(&object.key)) {
+        if object.last_modified.is_none_or(|modified| modified >= cutoff) {
+            tracker.update(|report| report.skipped_recent += 1);
+            continue;
+        }
+
+        match remove_orphan(pool, storage, object, options.dry_run).await {
+            Ok(true) => {
+                tracing::info!(
+                    "{} orphaned artifact {} ({} bytes)",
+                    if options.dry_run { "Quarantined" } else { "Deleted" },
+                    object.key,
+                    object.size
+                );
+                tracker.update(|report| {
+                    report.reclaimed_bytes += object.size;
+                    report.orphans.push(OrphanArtifact {
+                        key: object.key.clone(),
+                        size: object.size,
+                        last_modified: object.last_modified,
+                    });
+                });
+            }
+            Ok(false) => tracker.update(|report| report.skipped_recent += 1),
+            Err(e) => {
+                tracing::warn!("Failed to remove orphaned artifact {}: {:#}", object.key, e);
+                tracker.update(|report| report.errors += 1);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes or quarantines an orphan, unless it changed since it was listed
+/// or something started referring to it (e.g. the same version was just
+/// published), in which case it is kept and `false` is returned.
+///
+/// The references were read before listing, so a blob promoted in between
+/// looks orphaned. Blobs are re-checked under the lock publishes and mirror
+/// fills hold until their row is committed.
+async fn remove_orphan(pool: &DbPool, storage: &Storage, object: &ArtifactMeta, dry_run: bool) -> Result<bool> {
+    let store = storage.store();
+
+    let _blob_lock = match blob_digest(&object.key) {
+        Some(digest) => {
+            let guard = storage.lock_blob(digest).await;
+            if db::is_blob_referenced(pool, digest).await? {
+                return Ok(false);
+            }
+            Some(guard)
+        }
+        None => None,
+    };
+
+    match store.head(&object.key).await? {
+        Some(current) if current.last_modified == object.last_modified => {}
+        _ => return Ok(false),
+    }
+
+    if dry_run {
+        store.rename(&object.key, &format!("{}{}", QUARANTINE_PREFIX, object.key)).await?;
+    } else {
+        store.delete(&object.key).await?;
+    }
+
+    Ok(true)
+}
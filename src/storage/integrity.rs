@@ -0,0 +1,320 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_util::io::StreamReader;
+
+use super::{sha256_stream, DecryptionFailed, SpooledUpload, Storage};
+use crate::config::AppConfig;
+use crate::db::{self, DbPool};
+use crate::models::{ArtifactOrigin, IntegrityProblem};
+
+/// Outcome of the current (or last) scrub.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScrubProgress {
+    pub running: bool,
+    /// Artifacts known to the database, published and mirrored
+    pub total: usize,
+    pub checked: usize,
+    pub missing: usize,
+    pub corrupt: usize,
+    /// Mirrored artifacts that failed and were fetched again from upstream
+    pub repaired: usize,
+    /// Artifacts that couldn't be checked, e.g. because storage was unreachable
+    pub errors: usize,
+    pub bytes_checked: u64,
+    /// Why the scrub stopped early, if it did
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Shares scrub progress between the task running it and the admin API.
+#[derive(Clone, Default)]
+pub struct ScrubTracker(Arc<Mutex<ScrubProgress>>);
+
+impl ScrubTracker {
+    pub fn snapshot(&self) -> ScrubProgress {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Resets the progress for a new scrub, or returns `false` if one is
+    /// already running.
+    pub fn try_start(&self) -> bool {
+        let mut progress = self.0.lock().unwrap();
+        if progress.running {
+            return false;
+        }
+
+        *progress = ScrubProgress {
+            running: true,
+            started_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        true
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ScrubProgress)) {
+        f(&mut self.0.lock().unwrap())
+    }
+}
+
+/// Limit on a whole request to the upstream registry, download included.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The HTTP client for the upstream registry. One is built at startup and
+/// shared by the mirror and the integrity scrubber, so connections are reused.
+pub fn upstream_client(config: &AppConfig) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(&config.github.user_agent)
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(UPSTREAM_TIMEOUT)
+        .build()
+        .context("failed to build the upstream HTTP client")
+}
+
+/// Where corrupt mirrored crates are fetched again from.
+#[derive(Debug, Clone)]
+pub struct MirrorUpstream {
+    pub url: String,
+    pub client: reqwest::Client,
+    /// Downloads larger than this are refused
+    pub max_size: u64,
+}
+
+impl MirrorUpstream {
+    /// Set when the mirror is enabled and `repair_mirrored` is turned on.
+    pub fn from_config(config: &AppConfig, client: &reqwest::Client) -> Option<Self> {
+        let mirror = &config.registry.crates_io_mirror;
+        (mirror.enabled && config.integrity.repair_mirrored).then(|| Self {
+            url: mirror.upstream_url.clone(),
+            client: client.clone(),
+            max_size: config.registry.max_crate_size,
+        })
+    }
+}
+
+enum Verdict {
+    Intact(u64),
+    Missing,
+    /// With the checksum of what is stored, unless it failed to decrypt
+    Corrupt(Option<String>),
+}
+
+struct ArtifactToCheck {
+    origin: ArtifactOrigin,
+    crate_name: String,
+    version: String,
+    checksum: String,
+}
+
+/// Re-hashes every published and mirrored artifact and compares it with the
+/// checksum recorded when it was stored. Failures are recorded in
+/// `integrity_issues` and cleared again once an artifact verifies.
+///
+/// The tracker must have been started with [`ScrubTracker::try_start`].
+pub async fn scrub_storage(
+    pool: &DbPool,
+    storage: &Storage,
+    upstream: Option<&MirrorUpstream>,
+    tracker: &ScrubTracker,
+) -> ScrubProgress {
+    if let Err(e) = run_scrub(pool, storage, upstream, tracker).await {
+        tracing::error!("Integrity scrub stopped: {:#}", e);
+        tracker.update(|progress| progress.error = Some(format!("{:#}", e)));
+    }
+
+    tracker.update(|progress| {
+        progress.running = false;
+        progress.finished_at = Some(Utc::now());
+    });
+
+    let progress = tracker.snapshot();
+    tracing::info!(
+        "Integrity scrub finished: {} checked, {} missing, {} corrupt, {} repaired, {} errors",
+        progress.checked, progress.missing, progress.corrupt, progress.repaired, progress.errors
+    );
+    progress
+}
+
+/// Scrubs once right away and then every `scrub_interval_hours`, skipping a
+/// round if the previous scrub (or one started from the admin API) is still
+/// running.
+pub fn spawn_scrubber(
+    pool: DbPool,
+    storage: Storage,
+    config: &AppConfig,
+    client: &reqwest::Client,
+    tracker: ScrubTracker,
+) {
+    let period = Duration::from_secs(config.integrity.scrub_interval_hours.max(1) * 60 * 60);
+    let upstream = MirrorUpstream::from_config(config, client);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if tracker.try_start() {
+                scrub_storage(&pool, &storage, upstream.as_ref(), &tracker).await;
+            }
+        }
+    });
+}
+
+async fn run_scrub(
+    pool: &DbPool,
+    storage: &Storage,
+    upstream: Option<&MirrorUpstream>,
+    tracker: &ScrubTracker,
+) -> Result<()> {
+    let published = db::get_stored_artifacts(pool).await?.into_iter().map(|a| ArtifactToCheck {
+        origin: ArtifactOrigin::Published,
+        crate_name: a.crate_name,
+        version: a.version,
+        checksum: a.checksum,
+    });
+    let mirrored = db::get_mirror_artifacts(pool).await?.into_iter().map(|a| ArtifactToCheck {
+        origin: ArtifactOrigin::Mirror,
+        crate_name: a.crate_name,
+        version: a.version,
+        checksum: a.checksum,
+    });
+    let artifacts: Vec<_> = published.chain(mirrored).collect();
+
+    tracker.update(|progress| progress.total = artifacts.len());
+    tracing::info!("Starting integrity scrub of {} artifacts", artifacts.len());
+
+    for artifact in &artifacts {
+        let (name, version, origin) = (&artifact.crate_name, &artifact.version, artifact.origin);
+
+        let verdict = match verify_artifact(storage, artifact).await {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                tracing::warn!("Could not check {} {}: {:#}", name, version, e);
+                tracker.update(|progress| progress.errors += 1);
+                continue;
+            }
+        };
+
+        let (problem, actual_checksum) = match verdict {
+            Verdict::Intact(size) => {
+                db::clear_integrity_issue(pool, name, version, origin).await?;
+                tracker.update(|progress| {
+                    progress.checked += 1;
+                    progress.bytes_checked += size;
+                });
+                continue;
+            }
+            Verdict::Missing => (IntegrityProblem::Missing, None),
+            Verdict::Corrupt(actual) => (IntegrityProblem::Corrupt, actual),
+        };
+
+        tracing::warn!("{} {} is {}", name, version, problem.as_str());
+        tracker.update(|progress| {
+            progress.checked += 1;
+            match problem {
+                IntegrityProblem::Missing => progress.missing += 1,
+                IntegrityProblem::Corrupt => progress.corrupt += 1,
+            }
+        });
+
+        if let (ArtifactOrigin::Mirror, Some(upstream)) = (origin, upstream) {
+            match refetch_from_upstream(storage, upstream, artifact).await {
+                Ok(()) => {
+                    tracing::info!("Repaired mirrored crate {} {} from upstream", name, version);
+                    db::clear_integrity_issue(pool, name, version, origin).await?;
+                    tracker.update(|progress| progress.repaired += 1);
+                    continue;
+                }
+                Err(e) => tracing::warn!("Failed to repair {} {} from upstream: {:#}", name, version, e),
+            }
+        }
+
+        db::record_integrity_issue(
+            pool,
+            name,
+            version,
+            origin,
+            problem,
+            &artifact.checksum,
+            actual_checksum.as_deref(),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Streams the blob through SHA-256 without buffering it whole. Since blobs
+/// are keyed by digest, an intact blob is one whose contents hash to its key.
+/// With encryption enabled, a blob that fails authentication has been
+/// tampered with and is corrupt as well.
+/// Reads bypass the disk cache, which only ever holds verified copies.
+async fn verify_artifact(storage: &Storage, artifact: &ArtifactToCheck) -> Result<Verdict> {
+    let Some(mut stream) = storage.uncached().open_blob(&artifact.checksum).await? else {
+        return Ok(Verdict::Missing);
+    };
+
+    let (actual, size) = match sha256_stream(&mut stream.reader).await {
+        Ok(hashed) => hashed,
+        Err(e) if DecryptionFailed::is_cause_of(&e) => return Ok(Verdict::Corrupt(None)),
+        Err(e) => return Err(e),
+    };
+    if actual == artifact.checksum {
+        Ok(Verdict::Intact(size))
+    } else {
+        Ok(Verdict::Corrupt(Some(actual)))
+    }
+}
+
+async fn refetch_from_upstream(storage: &Storage, upstream: &MirrorUpstream, artifact: &ArtifactToCheck) -> Result<()> {
+    let url = format!(
+        "{}/api/v1/crates/{}/{}/download",
+        upstream.url, artifact.crate_name, artifact.version
+    );
+
+    let response = upstream
+        .client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed to download {}", url))?;
+
+    let size = response
+        .content_length()
+        .context("the upstream response has no Content-Length")?;
+    if size > upstream.max_size {
+        anyhow::bail!("upstream copy is {} bytes, more than the {} byte limit", size, upstream.max_size);
+    }
+
+    // Spooled to disk rather than held in memory
+    let chunks = futures::stream::try_unfold(response, |mut response| async move {
+        let chunk = response.chunk().await.map_err(std::io::Error::other)?;
+        Ok::<_, std::io::Error>(chunk.map(|chunk| (chunk, response)))
+    });
+    let mut body = StreamReader::new(Box::pin(chunks));
+    let upload = SpooledUpload::receive(&mut body, size)
+        .await
+        .context("failed to read the upstream response")?;
+
+    if upload.checksum != artifact.checksum {
+        anyhow::bail!("upstream copy does not match the recorded checksum");
+    }
+
+    // The corrupt blob sits under the same digest, so it has to go first
+    let staged = storage.stage_crate(&upload).await?;
+    let _blob_lock = storage.lock_blob(&artifact.checksum).await;
+    let replaced = match storage.delete_blob(&artifact.checksum).await {
+        Ok(()) => storage.promote_staged(&staged, &artifact.checksum).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+
+    if replaced.is_err() {
+        if let Err(e) = storage.discard_staged(&staged).await {
+            tracing::warn!("Failed to discard staged copy of {} {}: {}", artifact.crate_name, artifact.version, e);
+        }
+    }
+    replaced
+}
@@ -0,0 +1,157 @@
+use std::path::{Component, Path, PathBuf};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::DateTime;
+use tokio::fs;
+
+use super::{ArtifactMeta, ArtifactStore, ArtifactStream};
+
+/// Stores artifacts as files under a root directory, one file per key.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub async fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root).await?;
+        tracing::info!("Local storage initialized at: {}", root.display());
+        Ok(Self { root })
+    }
+
+    /// Maps a key to its file, refusing anything that could escape the root.
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        let is_plain = !key.is_empty() && relative.components().all(|c| matches!(c, Component::Normal(_)));
+
+        if !is_plain {
+            return Err(anyhow::anyhow!("Invalid storage key: {}", key));
+        }
+
+        Ok(self.root.join(relative))
+    }
+
+    fn key(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<&str> = relative.components()
+            .map(|c| c.as_os_str().to_str())
+            .collect::<Option<_>>()?;
+        Some(parts.join("/"))
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for LocalStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // Write next to the target and rename, so readers never see a partial file
+        let temp_path = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        fs::write(&temp_path, data).await?;
+        if let Err(e) = fs::rename(&temp_path, &path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, source: &Path) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let temp_path = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        fs::copy(source, &temp_path).await?;
+        if let Err(e) = fs::rename(&temp_path, &path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<ArtifactStream>> {
+        let file = match fs::File::open(self.path(key)?).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let size = file.metadata().await?.len();
+
+        Ok(Some(ArtifactStream { reader: Box::pin(file), size: Some(size) }))
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ArtifactMeta>> {
+        match fs::metadata(self.path(key)?).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(ArtifactMeta {
+                key: key.to_string(),
+                size: metadata.len(),
+                last_modified: metadata.modified().ok().map(DateTime::from),
+            })),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ArtifactMeta>> {
+        // Only the directory the prefix points into needs walking
+        let start = match prefix.rfind('/') {
+            Some(end) => self.path(&prefix[..end])?,
+            None => self.root.clone(),
+        };
+
+        let mut objects = Vec::new();
+        let mut pending = vec![start];
+        while let Some(dir) = pending.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    pending.push(entry.path());
+                    continue;
+                }
+
+                let Some(key) = self.key(&entry.path()) else { continue };
+                if file_type.is_file() && key.starts_with(prefix) {
+                    let metadata = entry.metadata().await?;
+                    objects.push(ArtifactMeta {
+                        key,
+                        size: metadata.len(),
+                        last_modified: metadata.modified().ok().map(DateTime::from),
+                    });
+                }
+            }
+        }
+
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let to_path = self.path(to)?;
+        if let Some(parent) = to_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::rename(self.path(from)?, to_path).await?;
+        Ok(())
+    }
+}
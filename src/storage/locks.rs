@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Async locks keyed by string, for serializing work on one object without
+/// holding up work on others. A key's lock is dropped once nobody holds or
+/// waits for it, so the map only ever holds keys in use.
+///
+/// These only serialize callers within this process.
+#[derive(Clone, Default)]
+pub struct KeyLocks(Arc<Mutex<HashMap<String, Arc<AsyncMutex<()>>>>>);
+
+impl KeyLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for the lock on `key` and holds it until the guard is dropped.
+    pub async fn lock(&self, key: &str) -> KeyGuard {
+        let mutex = self.0.lock().unwrap().entry(key.to_string()).or_default().clone();
+        let guard = mutex.lock_owned().await;
+
+        KeyGuard {
+            locks: self.clone(),
+            key: key.to_string(),
+            guard: Some(guard),
+        }
+    }
+
+    /// Locks several keys at once. They are taken in sorted order, so two
+    /// callers locking overlapping sets cannot deadlock.
+    pub async fn lock_all(&self, keys: &[&str]) -> Vec<KeyGuard> {
+        let mut keys = keys.to_vec();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in keys {
+            guards.push(self.lock(key).await);
+        }
+        guards
+    }
+}
+
+pub struct KeyGuard {
+    locks: KeyLocks,
+    key: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for KeyGuard {
+    fn drop(&mut self) {
+        let mut locks = self.locks.0.lock().unwrap();
+        let mutex = self.guard.take().map(|guard| OwnedMutexGuard::mutex(&guard).clone());
+
+        // Waiters clone the lock under the map's mutex, so if the map holds
+        // the only other reference nobody else can be waiting for it
+        if let Some(mutex) = mutex {
+            if Arc::strong_count(&mutex) == 2 {
+                locks.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn serializes_holders_of_the_same_key() {
+        let locks = KeyLocks::new();
+        let guard = locks.lock("a").await;
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move { locks.lock("a").await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        // Other keys are not held up
+        drop(locks.lock("b").await);
+
+        drop(guard);
+        drop(waiter.await.unwrap());
+        assert!(locks.0.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::{Arc, Mutex};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::{blob_key, sha256_stream, SpooledUpload, Storage};
+use crate::db::{self, DbPool};
+use crate::models::ArtifactOrigin;
+
+/// Progress of a storage migration, as reported by the CLI and the admin API.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MigrationProgress {
+    pub source: String,
+    pub target: String,
+    pub running: bool,
+    /// Artifacts recorded in `crate_versions` and `mirror_artifacts`
+    pub total: usize,
+    /// Artifacts copied by an earlier run, which are not copied again
+    pub already_migrated: usize,
+    pub copied: usize,
+    pub failed: usize,
+    pub bytes_copied: u64,
+    pub failures: Vec<MigrationFailure>,
+    /// Why the migration stopped early, if it did
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationFailure {
+    pub origin: ArtifactOrigin,
+    pub crate_name: String,
+    pub version: String,
+    pub error: String,
+}
+
+/// Shares the progress of the current (or last) migration between the task
+/// running it and whoever is watching.
+#[derive(Clone, Default)]
+pub struct MigrationTracker(Arc<Mutex<MigrationProgress>>);
+
+impl MigrationTracker {
+    pub fn snapshot(&self) -> MigrationProgress {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Resets the progress for a new migration, or returns `false` if one is
+    /// already running.
+    pub fn try_start(&self, source: &Storage, target: &Storage) -> bool {
+        let mut progress = self.0.lock().unwrap();
+        if progress.running {
+            return false;
+        }
+
+        *progress = MigrationProgress {
+            source: source.config().location(),
+            target: target.config().location(),
+            running: true,
+            started_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        true
+    }
+
+    fn update(&self, f: impl FnOnce(&mut MigrationProgress)) {
+        f(&mut self.0.lock().unwrap())
+    }
+}
+
+/// Copies every artifact referenced by `crate_versions` or `mirror_artifacts`
+/// from `source` to `target`, checking both the original and the copy against
+/// the recorded sha256. The outcome for each version is recorded in
+/// `storage_migrations`, so running the migration again only retries what
+/// hasn't been copied yet. Mirrored crates have no version row to record
+/// against; since a copy is only left in the target once it verified, those
+/// whose blob the target already has are skipped instead.
+///
+/// The tracker must have been started with [`MigrationTracker::try_start`].
+pub async fn migrate_storage(
+    pool: &DbPool,
+    source: &Storage,
+    target: &Storage,
+    tracker: &MigrationTracker,
+) -> MigrationProgress {
+    if let Err(e) = run_migration(pool, source, target, tracker).await {
+        tracing::error!("Storage migration stopped: {:#}", e);
+        tracker.update(|progress| progress.error = Some(format!("{:#}", e)));
+    }
+
+    tracker.update(|progress| {
+        progress.running = false;
+        progress.finished_at = Some(Utc::now());
+    });
+    tracker.snapshot()
+}
+
+async fn run_migration(
+    pool: &DbPool,
+    source: &Storage,
+    target: &Storage,
+    tracker: &MigrationTracker,
+) -> Result<()> {
+    let target_location = target.config().location();
+    if source.config().location() == target_location {
+        anyhow::bail!("source and target storage are the same ({})", target_location);
+    }
+
+    let artifacts = db::get_stored_artifacts(pool).await?;
+    let migrated = db::get_migrated_version_ids(pool, &target_location).await?;
+    let pending: Vec<_> = artifacts.iter().filter(|a| !migrated.contains(&a.version_id)).collect();
+
+    let mirrors = db::get_mirror_artifacts(pool).await?;
+    let mut pending_mirrors = Vec::new();
+    for mirror in &mirrors {
+        if target.store().head(&blob_key(&mirror.checksum)).await?.is_none() {
+            pending_mirrors.push(mirror);
+        }
+    }
+
+    let total = artifacts.len() + mirrors.len();
+    let pending_total = pending.len() + pending_mirrors.len();
+    tracker.update(|progress| {
+        progress.total = total;
+        progress.already_migrated = total - pending_total;
+    });
+    tracing::info!(
+        "Migrating {} of {} artifacts from {} to {}",
+        pending_total, total, source.config().location(), target_location
+    );
+
+    for (index, artifact) in pending.iter().enumerate() {
+        let copied = copy_blob(source, target, &artifact.checksum, artifact.file_size).await;
+        let error = copied.as_ref().err().map(|e| format!("{:#}", e));
+        db::record_storage_migration(pool, &target_location, artifact.version_id, error.as_deref()).await?;
+
+        record_copy(
+            tracker,
+            (index + 1, pending_total),
+            ArtifactOrigin::Published,
+            &artifact.crate_name,
+            &artifact.version,
+            copied,
+        );
+    }
+
+    for (index, mirror) in pending_mirrors.iter().enumerate() {
+        let copied = copy_blob(source, target, &mirror.checksum, mirror.file_size).await;
+
+        record_copy(
+            tracker,
+            (pending.len() + index + 1, pending_total),
+            ArtifactOrigin::Mirror,
+            &mirror.crate_name,
+            &mirror.version,
+            copied,
+        );
+    }
+
+    Ok(())
+}
+
+fn record_copy(
+    tracker: &MigrationTracker,
+    (position, count): (usize, usize),
+    origin: ArtifactOrigin,
+    crate_name: &str,
+    version: &str,
+    copied: Result<u64>,
+) {
+    match copied {
+        Ok(size) => {
+            tracker.update(|progress| {
+                progress.copied += 1;
+                progress.bytes_copied += size;
+            });
+            tracing::info!("[{}/{}] Copied {} {} ({})", position, count, crate_name, version, origin.as_str());
+        }
+        Err(e) => {
+            let error = format!("{:#}", e);
+            tracing::warn!(
+                "[{}/{}] Failed to copy {} {} ({}): {}",
+                position, count, crate_name, version, origin.as_str(), error
+            );
+            tracker.update(|progress| {
+                progress.failed += 1;
+                progress.failures.push(MigrationFailure {
+                    origin,
+                    crate_name: crate_name.to_string(),
+                    version: version.to_string(),
+                    error,
+                });
+            });
+        }
+    }
+}
+
+/// Copies one blob through a temporary file and reads it back from the
+/// target, returning its size. Nothing is held in memory whole, so crates of
+/// any size can be moved. Blobs the target already holds are verified rather
+/// than written again.
+async fn copy_blob(source: &Storage, target: &Storage, digest: &str, file_size: i64) -> Result<u64> {
+    let mut artifact = source
+        .open_blob(digest)
+        .await
+        .context("failed to read from source")?
+        .ok_or_else(|| anyhow::anyhow!("artifact not found in source: {}", blob_key(digest)))?;
+
+    let spooled = SpooledUpload::receive(&mut artifact.reader, file_size as u64)
+        .await
+        .context("failed to read from source")?;
+    if spooled.checksum != digest {
+        anyhow::bail!("source artifact does not match the recorded checksum");
+    }
+
+    let key = blob_key(digest);
+    if target.store().head(&key).await?.is_none() {
+        target.store().put_file(&key, spooled.path()).await.context("failed to write to target")?;
+    }
+
+    let mut copy = target
+        .open_blob(digest)
+        .await
+        .context("failed to read back from target")?
+        .ok_or_else(|| anyhow::anyhow!("copy in target disappeared before it could be verified"))?;
+    let (copy_digest, _) = sha256_stream(&mut copy.reader).await.context("failed to read back from target")?;
+    if copy_digest != digest {
+        target.delete_blob(digest).await?;
+        anyhow::bail!("copy in target does not match the recorded checksum");
+    }
+
+    Ok(spooled.size)
+}
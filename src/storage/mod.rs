@@ -1,260 +1,471 @@
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
 use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 
+use crate::config::{CacheConfig, EncryptionConfig, ReplicationConfig, StorageConfig, StorageBackend, S3Config};
+
+pub mod cache;
+pub mod encryption;
+pub mod gc;
+pub mod integrity;
+mod local;
+mod locks;
+pub mod migration;
+pub mod replication;
 #[cfg(feature = "ssr")]
-use aws_sdk_s3::{Client as S3Client, primitives::ByteStream, config::{Credentials, Region}};
+pub mod s3;
 
+pub use cache::{CacheStats, CachedStore};
+pub use encryption::{DecryptionFailed, EncryptedStore, KeyRotationReport};
+pub use local::LocalStore;
+pub use locks::{KeyGuard, KeyLocks};
+pub use replication::{ReplicatedStore, ReplicationStats};
 #[cfg(feature = "ssr")]
-use aws_config::BehaviorVersion;
+pub use s3::S3Store;
 
-use crate::config::{StorageConfig, StorageBackend, S3Config};
+/// A flat key/value store for artifacts. Keys are `/`-separated paths such
+/// as `blobs/sha256/ab/ab12…`; see [`blob_key`] for the layout.
+#[async_trait]
+pub trait ArtifactStore: Send + Sync {
+    /// Writes `data` under `key`, replacing whatever was there.
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()>;
 
-#[cfg(feature = "ssr")]
-pub mod s3;
+    /// Writes the contents of the file at `path` under `key`, replacing
+    /// whatever was there. Backends override this to avoid reading the
+    /// whole file into memory.
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        self.put(key, &fs::read(path).await?).await
+    }
+
+    /// Opens `key` for reading, or returns `None` if it doesn't exist.
+    async fn get(&self, key: &str) -> Result<Option<ArtifactStream>>;
+
+    async fn head(&self, key: &str) -> Result<Option<ArtifactMeta>>;
+
+    /// Removes `key`. Deleting a missing key is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Every key starting with `prefix`.
+    async fn list(&self, prefix: &str) -> Result<Vec<ArtifactMeta>>;
+
+    /// Moves `from` to `to`, replacing `to` if it exists.
+    async fn rename(&self, from: &str, to: &str) -> Result<()>;
+
+    /// A URL clients can fetch `key` from directly, if the backend supports it.
+    async fn presigned_url(&self, _key: &str, _expires_in: Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtifactMeta {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+pub struct ArtifactStream {
+    pub reader: Pin<Box<dyn AsyncRead + Send>>,
+    pub size: Option<u64>,
+}
+
+/// Where the blob with the given SHA-256 digest lives in every backend.
+/// Crates are stored by content; the database maps each name and version to
+/// its digest (`crate_versions.checksum`, `mirror_artifacts.checksum`), so
+/// identical uploads share one blob.
+pub fn blob_key(digest: &str) -> String {
+    format!("blobs/sha256/{}/{}", digest.get(..2).unwrap_or(""), digest)
+}
+
+/// The digest a [`blob_key`] was built from, or `None` for other keys.
+pub fn blob_digest(key: &str) -> Option<&str> {
+    key.strip_prefix("blobs/sha256/")?.split_once('/').map(|(_, digest)| digest)
+}
+
+/// Hex-encoded SHA-256, the form checksums are recorded in.
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Hashes a stream without buffering it whole, returning the hex digest and
+/// the number of bytes read.
+pub async fn sha256_stream(reader: &mut (dyn AsyncRead + Send + Unpin)) -> Result<(String, u64)> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        size += read as u64;
+    }
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// An upload received into a temporary file, hashed on the way in. The file
+/// is removed when this is dropped.
+#[derive(Debug)]
+pub struct SpooledUpload {
+    path: PathBuf,
+    pub size: u64,
+    pub checksum: String,
+}
+
+impl SpooledUpload {
+    /// Copies exactly `expected_size` bytes from `reader` to a temporary file.
+    /// Fails with [`std::io::ErrorKind::UnexpectedEof`] if the reader ends early.
+    pub async fn receive(reader: &mut (dyn AsyncRead + Send + Unpin), expected_size: u64) -> std::io::Result<Self> {
+        // Created first, so the file is cleaned up if anything below fails
+        let mut upload = Self {
+            path: std::env::temp_dir().join(format!("ghostcrate-upload-{}.crate", uuid::Uuid::new_v4().simple())),
+            size: 0,
+            checksum: String::new(),
+        };
+        let mut file = fs::File::create(&upload.path).await?;
+        let mut reader = reader.take(expected_size);
+
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; 64 * 1024];
+        let mut size = 0;
+        loop {
+            let read = reader.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            file.write_all(&buf[..read]).await?;
+            size += read as u64;
+        }
+        file.flush().await?;
+
+        if size != expected_size {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        upload.size = size;
+        upload.checksum = format!("{:x}", hasher.finalize());
+        Ok(upload)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpooledUpload {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Connects to the backend `config` selects.
+async fn connect(config: &StorageConfig) -> Result<Arc<dyn ArtifactStore>> {
+    match &config.backend {
+        StorageBackend::Local => Ok(Arc::new(LocalStore::new(&config.local_path).await?)),
+
+        #[cfg(feature = "ssr")]
+        StorageBackend::S3 => {
+            let s3_config = config.s3.as_ref().ok_or_else(|| {
+                anyhow::anyhow!("S3 backend selected but no S3 configuration provided")
+            })?;
+            Ok(Arc::new(S3Store::connect(s3_config).await?))
+        }
+
+        #[cfg(not(feature = "ssr"))]
+        StorageBackend::S3 => {
+            Err(anyhow::anyhow!("S3 storage not available in client-side builds"))
+        }
+    }
+}
+
+fn staging_key(staged: &StagedCrate) -> String {
+    format!("staging/{}.crate", staged.id)
+}
+
+/// An upload written to a temporary location, waiting to be promoted to its
+/// final path once the publish has been recorded in the database.
+#[derive(Debug)]
+pub struct StagedCrate {
+    id: String,
+}
+
+/// How a crate download is served.
+pub enum CrateDownload {
+    /// The artifact itself, streamed through the registry
+    Stream(ArtifactStream),
+    /// A short-lived URL the client should fetch the artifact from instead
+    Redirect(String),
+}
 
 #[derive(Clone)]
 pub struct Storage {
     config: StorageConfig,
-    #[cfg(feature = "ssr")]
-    s3_client: Option<S3Client>,
+    store: Arc<dyn ArtifactStore>,
+    replication: Option<Arc<ReplicatedStore>>,
+    encryption: Option<Arc<EncryptedStore>>,
+    cache: Option<Arc<CachedStore>>,
+    /// Held while a blob's existence and the rows referring to it must not
+    /// change underneath the holder; see [`Storage::lock_blob`]
+    blob_locks: KeyLocks,
 }
 
 impl Storage {
-    pub fn new(config: StorageConfig) -> Result<Self> {
-        Ok(Self {
-            config,
-            #[cfg(feature = "ssr")]
-            s3_client: None,
-        })
-    }
-    
-    #[cfg(feature = "ssr")]
-    pub async fn init(&mut self) -> Result<()> {
-        match &self.config.backend {
-            StorageBackend::Local => {
-                fs::create_dir_all(&self.config.local_path).await?;
-                fs::create_dir_all(format!("{}/crates", &self.config.local_path)).await?;
-                tracing::info!("Local storage initialized at: {}", self.config.local_path);
-            }
-            StorageBackend::S3 => {
-                if let Some(s3_config) = &self.config.s3 {
-                    // Create credentials
-                    let credentials = Credentials::new(
-                        &s3_config.access_key,
-                        &s3_config.secret_key,
-                        None,
-                        None,
-                        "ghostcrate"
-                    );
-
-                    let mut config_builder = aws_config::defaults(BehaviorVersion::latest())
-                        .credentials_provider(credentials)
-                        .region(Region::new(s3_config.region.clone()));
-
-                    // Handle custom endpoint (MinIO, etc.)
-                    if let Some(endpoint) = &s3_config.endpoint {
-                        tracing::info!("Using custom S3 endpoint: {}", endpoint);
-                        config_builder = config_builder.endpoint_url(endpoint);
-                    }
-
-                    let aws_config = config_builder.load().await;
-                    
-                    let mut s3_config_builder = aws_sdk_s3::config::Builder::from(&aws_config);
-                    
-                    // Force path style for MinIO compatibility
-                    if s3_config.path_style {
-                        s3_config_builder = s3_config_builder.force_path_style(true);
-                        tracing::debug!("Using path-style addressing for S3 requests");
-                    }
-                    
-                    let s3_client_config = s3_config_builder.build();
-                    self.s3_client = Some(S3Client::from_conf(s3_client_config));
-                    
-                    // Test connection
-                    if let Some(client) = &self.s3_client {
-                        client.head_bucket()
-                            .bucket(&s3_config.bucket)
-                            .send()
-                            .await
-                            .map_err(|e| anyhow::anyhow!("Failed to connect to S3 bucket: {}", e))?;
-                        
-                        tracing::info!("S3 storage initialized for bucket: {} (MinIO compatible: {})", 
-                                     s3_config.bucket, s3_config.path_style);
-                    }
-                } else {
-                    return Err(anyhow::anyhow!("S3 backend selected but no S3 configuration provided"));
-                }
-            }
+    /// Connects to the configured backend and moves any artifacts still
+    /// stored under an old per-name layout into the blob store.
+    pub async fn new(config: StorageConfig) -> Result<Self> {
+        let store = connect(&config).await?;
+        let storage = Self::from_store(config, store);
+        storage.migrate_legacy_keys().await?;
+        Ok(storage)
+    }
+
+    /// Wraps an already constructed store.
+    pub fn from_store(config: StorageConfig, store: Arc<dyn ArtifactStore>) -> Self {
+        Self { config, store, replication: None, encryption: None, cache: None, blob_locks: KeyLocks::new() }
+    }
+
+    /// Copies every change to the replica backend in the background when
+    /// replication is enabled. Must be set up before encryption, so the
+    /// replica only ever holds what the primary does.
+    pub async fn with_replication(self, replication_config: &ReplicationConfig) -> Result<Self> {
+        if !replication_config.enabled {
+            return Ok(self);
         }
-        Ok(())
+        if self.encryption.is_some() || self.cache.is_some() {
+            anyhow::bail!("storage replication must be set up before encryption and the cache");
+        }
+
+        let target = replication_config.target.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("storage replication is enabled but no replica is configured (set REPLICA_STORAGE_BACKEND)")
+        })?;
+        if target.location() == self.config.location() {
+            anyhow::bail!("the storage replica is the primary storage itself");
+        }
+
+        let replica = connect(target).await?;
+        let replication = ReplicatedStore::spawn(self.store.clone(), replica, target.location(), replication_config);
+        Ok(Self { store: replication.clone(), replication: Some(replication), ..self })
     }
-    
-    #[cfg(not(feature = "ssr"))]
-    pub async fn init(&mut self) -> Result<()> {
-        Ok(())
+
+    /// Encrypts everything written from now on when encryption is enabled.
+    /// Must be set up before the cache, which keeps decrypted copies.
+    pub async fn with_encryption(self, encryption_config: &EncryptionConfig) -> Result<Self> {
+        if !encryption_config.enabled {
+            return Ok(self);
+        }
+        if self.cache.is_some() {
+            anyhow::bail!("storage encryption must be set up before the cache");
+        }
+
+        let encryption = Arc::new(EncryptedStore::new(self.store.clone(), encryption_config).await?);
+        Ok(Self { store: encryption.clone(), encryption: Some(encryption), ..self })
     }
-    
-    pub async fn store_crate(&self, name: &str, version: &str, data: &[u8]) -> Result<String> {
-        let filename = format!("{}-{}.crate", name, version);
-        
-        match &self.config.backend {
-            StorageBackend::Local => {
-                let crate_path = self.get_local_crate_path(name, version).await;
-                
-                if let Some(parent) = crate_path.parent() {
-                    fs::create_dir_all(parent).await?;
-                }
-                
-                fs::write(&crate_path, data).await?;
-                tracing::info!("Stored crate locally: {}", crate_path.display());
-                Ok(filename)
-            }
-            
-            #[cfg(feature = "ssr")]
-            StorageBackend::S3 => {
-                if let (Some(s3_config), Some(client)) = (&self.config.s3, &self.s3_client) {
-                    let key = format!("crates/{}/{}/{}", name, version, filename);
-                    
-                    client
-                        .put_object()
-                        .bucket(&s3_config.bucket)
-                        .key(&key)
-                        .body(ByteStream::from(data.to_vec()))
-                        .content_type("application/x-tar")
-                        .send()
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Failed to upload to S3: {}", e))?;
-                    
-                    tracing::info!("Stored crate in S3: {}", key);
-                    Ok(key)
-                } else {
-                    Err(anyhow::anyhow!("S3 client not initialized"))
-                }
-            }
-            
-            #[cfg(not(feature = "ssr"))]
-            StorageBackend::S3 => {
-                Err(anyhow::anyhow!("S3 storage not available in client-side builds"))
-            }
+
+    /// Puts a local disk cache in front of the backend when it is enabled.
+    /// Local storage is already on disk, so it is never cached.
+    pub async fn with_cache(self, cache_config: &CacheConfig) -> Result<Self> {
+        if !cache_config.enabled {
+            return Ok(self);
+        }
+        if self.config.backend == StorageBackend::Local {
+            tracing::info!("Storage cache is enabled but not used with local storage");
+            return Ok(self);
         }
+
+        let cache = Arc::new(CachedStore::new(self.store.clone(), cache_config).await?);
+        Ok(Self { store: cache.clone(), cache: Some(cache), ..self })
     }
-    
-    pub async fn get_crate_path(&self, name: &str, version: &str) -> PathBuf {
-        match &self.config.backend {
-            StorageBackend::Local => self.get_local_crate_path(name, version).await,
-            StorageBackend::S3 => {
-                // For S3, return a placeholder path - actual retrieval will be handled differently
-                PathBuf::from(format!("s3://{}/{}", name, version))
-            }
+
+    /// The same storage without the cache, for reads that must see what the
+    /// backend actually holds.
+    pub fn uncached(&self) -> Self {
+        match &self.cache {
+            Some(cache) => Self { store: cache.inner().clone(), cache: None, ..self.clone() },
+            None => self.clone(),
         }
     }
-    
-    async fn get_local_crate_path(&self, name: &str, version: &str) -> PathBuf {
-        let mut path = PathBuf::from(&self.config.local_path);
-        path.push("crates");
-        path.push(name);
-        path.push(format!("{}-{}.crate", name, version));
-        path
-    }
-    
-    #[cfg(feature = "ssr")]
-    pub async fn get_crate_data(&self, name: &str, version: &str) -> Result<Vec<u8>> {
-        match &self.config.backend {
-            StorageBackend::Local => {
-                let path = self.get_local_crate_path(name, version).await;
-                let data = fs::read(path).await?;
-                Ok(data)
+
+    /// Hit and miss counters of the cache, if there is one.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// Queue and lag of the replica, if there is one.
+    pub fn replication_stats(&self) -> Option<ReplicationStats> {
+        self.replication.as_ref().map(|replication| replication.stats())
+    }
+
+    /// Queues everything the replica is missing. See
+    /// [`ReplicatedStore::reconcile`].
+    pub async fn reconcile_replica(&self) -> Result<usize> {
+        let replication = self.replication.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("storage replication is not enabled")
+        })?;
+        replication.reconcile().await
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// The store below encryption, which holds objects as ciphertext next to
+    /// their wrapped data keys (see [`encryption::data_key_path`]). `None`
+    /// when encryption is off.
+    pub fn sealed_store(&self) -> Option<&Arc<dyn ArtifactStore>> {
+        self.encryption.as_ref().map(|encryption| encryption.inner())
+    }
+
+    /// Re-wraps every data key with the current master key. See
+    /// [`EncryptedStore::rotate_keys`].
+    pub async fn rotate_encryption_keys(&self) -> Result<KeyRotationReport> {
+        let encryption = self.encryption.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("storage encryption is not enabled")
+        })?;
+        encryption.rotate_keys().await
+    }
+
+    /// Crates used to be stored by name under `crates/`, either as
+    /// `crates/{name}/{name}-{version}.crate` or, on S3,
+    /// `crates/{name}/{version}/{name}-{version}.crate`. Moves each one to
+    /// the [`blob_key`] of its contents.
+    async fn migrate_legacy_keys(&self) -> Result<()> {
+        let mut migrated = 0;
+
+        for object in self.store.list("crates/").await? {
+            if !object.key.ends_with(".crate") {
+                continue;
             }
-            StorageBackend::S3 => {
-                if let (Some(s3_config), Some(client)) = (&self.config.s3, &self.s3_client) {
-                    let key = format!("crates/{}/{}/{}-{}.crate", name, version, name, version);
-                    
-                    let response = client
-                        .get_object()
-                        .bucket(&s3_config.bucket)
-                        .key(&key)
-                        .send()
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Failed to get from S3: {}", e))?;
-                    
-                    let data = response.body.collect().await?.into_bytes().to_vec();
-                    Ok(data)
-                } else {
-                    Err(anyhow::anyhow!("S3 client not initialized"))
-                }
+            let Some(mut artifact) = self.store.get(&object.key).await? else { continue };
+            let (digest, _) = sha256_stream(&mut artifact.reader).await?;
+            drop(artifact);
+
+            let key = blob_key(&digest);
+            if self.store.head(&key).await?.is_some() {
+                self.store.delete(&object.key).await?;
+            } else {
+                self.store.rename(&object.key, &key).await?;
             }
+            migrated += 1;
+        }
+
+        if migrated > 0 {
+            tracing::info!("Moved {} crate(s) into the content-addressed blob store", migrated);
         }
+
+        Ok(())
     }
-    
-    #[cfg(not(feature = "ssr"))]
-    pub async fn get_crate_data(&self, _name: &str, _version: &str) -> Result<Vec<u8>> {
-        Err(anyhow::anyhow!("Storage operations not available in client-side builds"))
-    }
-    
-    pub async fn crate_exists(&self, name: &str, version: &str) -> bool {
-        match &self.config.backend {
-            StorageBackend::Local => {
-                let path = self.get_local_crate_path(name, version).await;
-                path.exists()
-            }
-            
-            #[cfg(feature = "ssr")]
-            StorageBackend::S3 => {
-                if let (Some(s3_config), Some(client)) = (&self.config.s3, &self.s3_client) {
-                    let key = format!("crates/{}/{}/{}-{}.crate", name, version, name, version);
-                    
-                    client
-                        .head_object()
-                        .bucket(&s3_config.bucket)
-                        .key(&key)
-                        .send()
-                        .await
-                        .is_ok()
-                } else {
-                    false
-                }
-            }
-            
-            #[cfg(not(feature = "ssr"))]
-            StorageBackend::S3 => false,
+
+    /// Stores `data` under its digest and returns the digest. If a blob with
+    /// the same contents already exists nothing is written.
+    pub async fn store_blob(&self, data: &[u8]) -> Result<String> {
+        let digest = sha256_hex(data);
+        let key = blob_key(&digest);
+
+        if self.store.head(&key).await?.is_none() {
+            self.store.put(&key, data).await?;
+            tracing::info!("Stored blob: {}", key);
         }
+        Ok(digest)
     }
-    
-    pub async fn get_crate_size(&self, name: &str, version: &str) -> Result<u64> {
-        match &self.config.backend {
-            StorageBackend::Local => {
-                let path = self.get_local_crate_path(name, version).await;
-                let metadata = fs::metadata(path).await?;
-                Ok(metadata.len())
-            }
-            
-            #[cfg(feature = "ssr")]
-            StorageBackend::S3 => {
-                if let (Some(s3_config), Some(client)) = (&self.config.s3, &self.s3_client) {
-                    let key = format!("crates/{}/{}/{}-{}.crate", name, version, name, version);
-                    
-                    let response = client
-                        .head_object()
-                        .bucket(&s3_config.bucket)
-                        .key(&key)
-                        .send()
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Failed to get S3 object metadata: {}", e))?;
-                    
-                    Ok(response.content_length().unwrap_or(0) as u64)
-                } else {
-                    Err(anyhow::anyhow!("S3 client not initialized"))
-                }
+
+    /// Locks the blob for `digest` against other publishes, mirror fills and
+    /// the garbage collector in this process. Hold it from before a blob is
+    /// written until the row referring to it is committed.
+    pub async fn lock_blob(&self, digest: &str) -> KeyGuard {
+        self.blob_locks.lock(digest).await
+    }
+
+    /// Writes an upload to the staging area. Nothing is visible in the blob
+    /// store until `promote_staged` is called.
+    pub async fn stage_crate(&self, upload: &SpooledUpload) -> Result<StagedCrate> {
+        let staged = StagedCrate { id: uuid::Uuid::new_v4().to_string() };
+        self.store.put_file(&staging_key(&staged), upload.path()).await?;
+
+        tracing::debug!("Staged upload {}", staged.id);
+        Ok(staged)
+    }
+
+    /// Moves a staged upload to the blob for `digest`, which must be the
+    /// SHA-256 of its contents, and returns whether a new blob was stored.
+    /// If that blob already exists the upload is a duplicate and is simply
+    /// discarded. Callers hold [`Storage::lock_blob`] for `digest`.
+    pub async fn promote_staged(&self, staged: &StagedCrate, digest: &str) -> Result<bool> {
+        let key = blob_key(digest);
+
+        if self.store.head(&key).await?.is_some() {
+            tracing::debug!("Discarding staged upload {}: {} already stored", staged.id, key);
+            self.discard_staged(staged).await?;
+            return Ok(false);
+        }
+
+        self.store.rename(&staging_key(staged), &key).await?;
+        tracing::info!("Stored blob: {}", key);
+        Ok(true)
+    }
+
+    /// Removes a staged upload that will not be published.
+    pub async fn discard_staged(&self, staged: &StagedCrate) -> Result<()> {
+        self.store.delete(&staging_key(staged)).await
+    }
+
+    /// Removes a blob. Callers must make sure no version still refers to it.
+    pub async fn delete_blob(&self, digest: &str) -> Result<()> {
+        self.store.delete(&blob_key(digest)).await
+    }
+
+    /// Opens a blob for download, or returns `None` if it doesn't exist.
+    /// With `download_redirect` set, backends that can presign URLs hand one
+    /// out rather than proxying the object.
+    pub async fn open_download(&self, digest: &str) -> Result<Option<CrateDownload>> {
+        let key = blob_key(digest);
+
+        if let Some(s3_config) = self.config.s3.as_ref().filter(|s3| s3.download_redirect) {
+            if self.store.head(&key).await?.is_none() {
+                return Ok(None);
             }
-            
-            #[cfg(not(feature = "ssr"))]
-            StorageBackend::S3 => {
-                Err(anyhow::anyhow!("Storage operations not available in client-side builds"))
+
+            let expires_in = Duration::from_secs(s3_config.presign_expiry_secs);
+            if let Some(url) = self.store.presigned_url(&key, expires_in).await? {
+                return Ok(Some(CrateDownload::Redirect(url)));
             }
         }
+
+        Ok(self.store.get(&key).await?.map(CrateDownload::Stream))
+    }
+
+    /// Opens a blob for reading, or returns `None` if it doesn't exist.
+    pub async fn open_blob(&self, digest: &str) -> Result<Option<ArtifactStream>> {
+        self.store.get(&blob_key(digest)).await
+    }
+
+    pub async fn get_blob_data(&self, digest: &str) -> Result<Vec<u8>> {
+        let mut artifact = self.open_blob(digest)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Artifact not found: {}", blob_key(digest)))?;
+
+        let mut data = Vec::with_capacity(artifact.size.unwrap_or(0) as usize);
+        artifact.reader.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    pub async fn blob_exists(&self, digest: &str) -> bool {
+        matches!(self.store.head(&blob_key(digest)).await, Ok(Some(_)))
     }
-    
+
+    pub fn store(&self) -> &Arc<dyn ArtifactStore> {
+        &self.store
+    }
+
     // Legacy compatibility method
     pub fn base_path(&self) -> &str {
         &self.config.local_path
@@ -276,4 +487,4 @@ impl Storage {
     pub fn s3_config(&self) -> Option<&S3Config> {
         self.config.s3.as_ref()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::fs;
+use tokio::sync::Notify;
+
+use super::{ArtifactMeta, ArtifactStore, ArtifactStream};
+use crate::config::ReplicationConfig;
+
+/// Prefixes compared between the primary and the replica when reconciling.
+/// Staged uploads are never replicated; they only matter once promoted.
+const RECONCILED_PREFIXES: &[&str] = &["blobs/", "keys/"];
+
+/// Retries back off exponentially up to this long.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+fn is_replicated(key: &str) -> bool {
+    !key.starts_with("staging/")
+}
+
+/// A key whose replica copy is out of date.
+struct PendingSync {
+    /// When the first change not yet replicated was made
+    queued_at: DateTime<Utc>,
+    attempts: u32,
+    next_attempt: Instant,
+    /// Bumped by every change, so a change made while the key is being
+    /// synced isn't lost when the sync finishes
+    generation: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationFailure {
+    pub key: String,
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct ReplicationQueue {
+    pending: HashMap<String, PendingSync>,
+    /// Keys that ran out of attempts; they are queued again by the next
+    /// reconciliation
+    failed: HashMap<String, ReplicationFailure>,
+}
+
+#[derive(Default)]
+struct ReplicationCounters {
+    copied: AtomicU64,
+    deleted: AtomicU64,
+    bytes_copied: AtomicU64,
+    retries: AtomicU64,
+    failovers: AtomicU64,
+}
+
+/// Replication state, as exported in metrics and the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationStats {
+    pub replica: String,
+    /// Keys waiting to be copied to (or deleted from) the replica
+    pub pending: usize,
+    /// Age of the oldest change not yet replicated
+    pub lag_seconds: u64,
+    pub copied: u64,
+    pub deleted: u64,
+    pub bytes_copied: u64,
+    pub retries: u64,
+    /// Reads served by the replica because the primary failed
+    pub failovers: u64,
+    pub failures: Vec<ReplicationFailure>,
+    pub last_reconciled_at: Option<DateTime<Utc>>,
+}
+
+/// Writes to a primary store and copies every change to a replica in the
+/// background. Changes are queued by key: syncing a key copies the primary's
+/// current object, or deletes the replica's if the primary no longer has it,
+/// so repeated changes to a key collapse into one copy.
+///
+/// The queue lives in memory. Whatever a restart loses is picked up again by
+/// reconciliation, which compares both stores at startup and every
+/// `reconcile_interval_hours`.
+pub struct ReplicatedStore {
+    primary: Arc<dyn ArtifactStore>,
+    replica: Arc<dyn ArtifactStore>,
+    replica_location: String,
+    config: ReplicationConfig,
+    queue: Mutex<ReplicationQueue>,
+    wake: Notify,
+    stats: ReplicationCounters,
+    last_reconciled_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl ReplicatedStore {
+    /// Wraps `primary` and starts the replication worker and the periodic
+    /// reconciliation.
+    pub fn spawn(
+        primary: Arc<dyn ArtifactStore>,
+        replica: Arc<dyn ArtifactStore>,
+        replica_location: String,
+        config: &ReplicationConfig,
+    ) -> Arc<Self> {
+        let store = Arc::new(Self {
+            primary,
+            replica,
+            replica_location,
+            config: config.clone(),
+            queue: Mutex::new(ReplicationQueue::default()),
+            wake: Notify::new(),
+            stats: ReplicationCounters::default(),
+            last_reconciled_at: Mutex::new(None),
+        });
+
+        tokio::spawn(store.clone().run_worker());
+
+        let reconciler = store.clone();
+        let period = Duration::from_secs(config.reconcile_interval_hours.max(1) * 60 * 60);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = reconciler.reconcile().await {
+                    tracing::error!("Storage replica reconciliation failed: {:#}", e);
+                }
+            }
+        });
+
+        tracing::info!("Replicating storage to {}", store.replica_location);
+        store
+    }
+
+    pub fn stats(&self) -> ReplicationStats {
+        let queue = self.queue.lock().unwrap();
+        let oldest = queue.pending.values().map(|pending| pending.queued_at).min();
+
+        let mut failures: Vec<_> = queue.failed.values().cloned().collect();
+        failures.sort_by(|a, b| a.key.cmp(&b.key));
+
+        ReplicationStats {
+            replica: self.replica_location.clone(),
+            pending: queue.pending.len(),
+            lag_seconds: oldest.map_or(0, |oldest| (Utc::now() - oldest).num_seconds().max(0) as u64),
+            copied: self.stats.copied.load(Ordering::Relaxed),
+            deleted: self.stats.deleted.load(Ordering::Relaxed),
+            bytes_copied: self.stats.bytes_copied.load(Ordering::Relaxed),
+            retries: self.stats.retries.load(Ordering::Relaxed),
+            failovers: self.stats.failovers.load(Ordering::Relaxed),
+            failures,
+            last_reconciled_at: *self.last_reconciled_at.lock().unwrap(),
+        }
+    }
+
+    /// Queues `key` to be synced. A key already waiting keeps its place, and
+    /// its retry count starts over.
+    fn enqueue(&self, key: &str) {
+        if !is_replicated(key) {
+            return;
+        }
+
+        {
+            let mut queue = self.queue.lock().unwrap();
+            queue.failed.remove(key);
+            let pending = queue.pending.entry(key.to_string()).or_insert_with(|| PendingSync {
+                queued_at: Utc::now(),
+                attempts: 0,
+                next_attempt: Instant::now(),
+                generation: 0,
+            });
+            pending.attempts = 0;
+            pending.next_attempt = Instant::now();
+            pending.generation += 1;
+        }
+        self.wake.notify_one();
+    }
+
+    /// Queues every key the replica is missing, or holds an older or
+    /// different-sized copy of. Keys only the replica has are left alone:
+    /// after a primary failure they may be the only copy.
+    pub async fn reconcile(&self) -> Result<usize> {
+        let mut queued = 0;
+
+        for prefix in RECONCILED_PREFIXES {
+            let replicated: HashMap<String, ArtifactMeta> = self.replica
+                .list(prefix)
+                .await?
+                .into_iter()
+                .map(|object| (object.key.clone(), object))
+                .collect();
+
+            for object in self.primary.list(prefix).await? {
+                let up_to_date = replicated.get(&object.key).is_some_and(|copy| {
+                    copy.size == object.size && copy.last_modified >= object.last_modified
+                });
+                if !up_to_date {
+                    self.enqueue(&object.key);
+                    queued += 1;
+                }
+            }
+        }
+
+        *self.last_reconciled_at.lock().unwrap() = Some(Utc::now());
+        if queued > 0 {
+            tracing::info!("Queued {} object(s) missing from the storage replica", queued);
+        }
+        Ok(queued)
+    }
+
+    /// Syncs queued keys one at a time, oldest first, as their retry delay
+    /// allows.
+    async fn run_worker(self: Arc<Self>) {
+        loop {
+            let next = {
+                let queue = self.queue.lock().unwrap();
+                queue.pending
+                    .iter()
+                    .min_by_key(|(_, pending)| (pending.next_attempt, pending.queued_at))
+                    .map(|(key, pending)| (key.clone(), pending.next_attempt, pending.generation))
+            };
+
+            let (key, generation) = match next {
+                Some((key, next_attempt, generation)) if next_attempt <= Instant::now() => (key, generation),
+                Some((_, next_attempt, _)) => {
+                    let delay = next_attempt.saturating_duration_since(Instant::now());
+                    let _ = tokio::time::timeout(delay, self.wake.notified()).await;
+                    continue;
+                }
+                None => {
+                    self.wake.notified().await;
+                    continue;
+                }
+            };
+
+            let result = self.sync(&key).await;
+            self.finish(&key, generation, result);
+        }
+    }
+
+    async fn sync(&self, key: &str) -> Result<()> {
+        let Some(mut artifact) = self.primary.get(key).await? else {
+            self.replica.delete(key).await?;
+            self.stats.deleted.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        };
+
+        // Spooled to disk so large objects reach the replica as multipart uploads
+        let temp_path = std::env::temp_dir().join(format!("ghostcrate-replica-{}", uuid::Uuid::new_v4().simple()));
+        let result: Result<u64> = async {
+            let mut file = fs::File::create(&temp_path).await?;
+            let size = tokio::io::copy(&mut artifact.reader, &mut file).await?;
+            file.sync_all().await?;
+            drop(file);
+
+            self.replica.put_file(key, &temp_path).await?;
+            match self.replica.head(key).await? {
+                Some(copy) if copy.size == size => Ok(size),
+                Some(copy) => anyhow::bail!("replica holds {} bytes, expected {}", copy.size, size),
+                None => anyhow::bail!("object is missing from the replica after writing it"),
+            }
+        }
+        .await;
+        let _ = fs::remove_file(&temp_path).await;
+
+        let size = result?;
+        self.stats.copied.fetch_add(1, Ordering::Relaxed);
+        self.stats.bytes_copied.fetch_add(size, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Removes a synced key from the queue, or schedules a retry. A key that
+    /// changed again during the sync stays queued as it is.
+    fn finish(&self, key: &str, generation: u64, result: Result<()>) {
+        let mut queue = self.queue.lock().unwrap();
+        let Some(pending) = queue.pending.get_mut(key) else { return };
+        if pending.generation != generation {
+            return;
+        }
+
+        let e = match result {
+            Ok(()) => {
+                queue.pending.remove(key);
+                tracing::debug!("Replicated {}", key);
+                return;
+            }
+            Err(e) => e,
+        };
+
+        pending.attempts += 1;
+        if pending.attempts < self.config.max_attempts {
+            let delay = Duration::from_secs(self.config.retry_delay_secs.max(1))
+                .saturating_mul(2u32.saturating_pow(pending.attempts - 1))
+                .min(MAX_RETRY_DELAY);
+            pending.next_attempt = Instant::now() + delay;
+            self.stats.retries.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                "Failed to replicate {} (attempt {}), retrying in {}s: {:#}",
+                key, pending.attempts, delay.as_secs(), e
+            );
+            return;
+        }
+
+        let attempts = pending.attempts;
+        queue.pending.remove(key);
+        tracing::error!("Giving up replicating {} after {} attempts: {:#}", key, attempts, e);
+        queue.failed.insert(key.to_string(), ReplicationFailure {
+            key: key.to_string(),
+            attempts,
+            error: format!("{:#}", e),
+            failed_at: Utc::now(),
+        });
+    }
+
+    fn fail_over(&self, key: &str, e: &anyhow::Error) -> bool {
+        if !self.config.failover_reads {
+            return false;
+        }
+        tracing::warn!("Primary storage failed to read {}, reading from the replica: {:#}", key, e);
+        self.stats.failovers.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for ReplicatedStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.primary.put(key, data).await?;
+        self.enqueue(key);
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        self.primary.put_file(key, path).await?;
+        self.enqueue(key);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<ArtifactStream>> {
+        match self.primary.get(key).await {
+            Err(e) if self.fail_over(key, &e) => self.replica.get(key).await,
+            result => result,
+        }
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ArtifactMeta>> {
+        match self.primary.head(key).await {
+            Err(e) if self.fail_over(key, &e) => self.replica.head(key).await,
+            result => result,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.primary.delete(key).await?;
+        self.enqueue(key);
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ArtifactMeta>> {
+        self.primary.list(prefix).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.primary.rename(from, to).await?;
+        self.enqueue(from);
+        self.enqueue(to);
+        Ok(())
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>> {
+        self.primary.presigned_url(key, expires_in).await
+    }
+}
@@ -1,24 +1,36 @@
+use std::path::Path;
+use std::time::Duration;
 use anyhow::Result;
-use aws_config::{BehaviorVersion, Region};
-use aws_sdk_s3::{Client, Config as S3Config};
-use aws_sdk_s3::config::{Credentials, SharedCredentialsProvider};
-use bytes::Bytes;
-use std::path::PathBuf;
-use tracing::{debug, info, error};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::{
+    Client,
+    config::{Credentials, Region},
+    presigning::PresigningConfig,
+    primitives::{ByteStream, Length},
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+use tracing::{debug, info, warn};
 
-use crate::config::S3Config as S3ConfigStruct;
+use super::{ArtifactMeta, ArtifactStore, ArtifactStream};
+use crate::config::S3Config;
 
-pub struct S3Storage {
+/// Files up to this size are uploaded in one request, larger ones as a
+/// multipart upload in parts of this size. S3 requires parts of at least 5 MiB.
+const MULTIPART_PART_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Stores artifacts as objects in an S3-compatible bucket (AWS, MinIO, ...).
+pub struct S3Store {
     client: Client,
+    /// Signs download URLs for `public_url` when it differs from the endpoint
+    presign_client: Option<Client>,
     bucket: String,
-    config: S3ConfigStruct,
 }
 
-impl S3Storage {
-    pub async fn new(config: S3ConfigStruct) -> Result<Self> {
-        info!("Initializing S3 storage with bucket: {}", config.bucket);
-        
-        // Create credentials
+impl S3Store {
+    /// Builds a client for the bucket and checks that it is reachable.
+    pub async fn connect(config: &S3Config) -> Result<Self> {
         let credentials = Credentials::new(
             &config.access_key,
             &config.secret_key,
@@ -27,182 +39,377 @@ impl S3Storage {
             "ghostcrate"
         );
 
-        // Build S3 config
-        let mut s3_config_builder = S3Config::builder()
-            .region(Region::new(config.region.clone()))
-            .credentials_provider(SharedCredentialsProvider::new(credentials));
+        let mut config_builder = aws_config::defaults(BehaviorVersion::latest())
+            .credentials_provider(credentials)
+            .region(Region::new(config.region.clone()));
 
-        // Configure for MinIO or custom S3 endpoint
-        if let Some(ref endpoint) = config.endpoint {
+        // Handle custom endpoint (MinIO, etc.)
+        if let Some(endpoint) = &config.endpoint {
             info!("Using custom S3 endpoint: {}", endpoint);
-            s3_config_builder = s3_config_builder.endpoint_url(endpoint);
-            
-            // Force path style for MinIO compatibility
-            if config.path_style {
-                s3_config_builder = s3_config_builder.force_path_style(true);
-                debug!("Using path-style addressing for S3 requests");
-            }
+            config_builder = config_builder.endpoint_url(endpoint);
+        }
+
+        let aws_config = config_builder.load().await;
+
+        let mut s3_config_builder = aws_sdk_s3::config::Builder::from(&aws_config);
+
+        // Force path style for MinIO compatibility
+        if config.path_style {
+            s3_config_builder = s3_config_builder.force_path_style(true);
+            debug!("Using path-style addressing for S3 requests");
         }
 
-        let s3_config = s3_config_builder.build();
-        let client = Client::from_conf(s3_config);
+        let s3_client_config = s3_config_builder.build();
 
-        let storage = Self {
-            client,
+        // Presigned URLs are signed for a host, so sign them for the one clients will use
+        let presign_client = config.public_url.as_ref().map(|public_url| {
+            Client::from_conf(s3_client_config.to_builder().endpoint_url(public_url).build())
+        });
+
+        let store = Self {
+            client: Client::from_conf(s3_client_config),
+            presign_client,
             bucket: config.bucket.clone(),
-            config,
         };
 
-        // Test connection
-        storage.test_connection().await?;
-        
-        Ok(storage)
-    }
+        store.client
+            .head_bucket()
+            .bucket(&store.bucket)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to connect to S3 bucket: {}", e))?;
 
-    async fn test_connection(&self) -> Result<()> {
-        debug!("Testing S3 connection to bucket: {}", self.bucket);
-        
-        match self.client.head_bucket().bucket(&self.bucket).send().await {
-            Ok(_) => {
-                info!("Successfully connected to S3 bucket: {}", self.bucket);
-                Ok(())
-            }
-            Err(e) => {
-                error!("Failed to connect to S3 bucket {}: {}", self.bucket, e);
-                Err(anyhow::anyhow!("S3 connection test failed: {}", e))
-            }
-        }
+        info!("S3 storage initialized for bucket: {} (MinIO compatible: {})",
+              config.bucket, config.path_style);
+
+        Ok(store)
     }
 
-    pub async fn store_crate(&self, name: &str, version: &str, data: &[u8]) -> Result<String> {
-        let key = format!("crates/{}/{}-{}.crate", name, name, version);
-        debug!("Storing crate to S3: {}", key);
+    /// Uploads the file in `MULTIPART_PART_SIZE` parts, reading one part into
+    /// memory at a time, and completes the upload.
+    async fn upload_parts(&self, key: &str, upload_id: &str, path: &Path, size: u64) -> Result<()> {
+        let mut parts = Vec::new();
+        let mut offset = 0;
+        let mut part_number = 1;
+
+        while offset < size {
+            let length = MULTIPART_PART_SIZE.min(size - offset);
+            let body = ByteStream::read_from()
+                .path(path)
+                .offset(offset)
+                .length(Length::Exact(length))
+                .build()
+                .await?;
+
+            let part = self.client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to upload part {} to S3: {}", part_number, e))?;
+
+            parts.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(part.e_tag().map(String::from))
+                    .build(),
+            );
+            offset += length;
+            part_number += 1;
+        }
 
         self.client
-            .put_object()
+            .complete_multipart_upload()
             .bucket(&self.bucket)
-            .key(&key)
-            .body(Bytes::from(data.to_vec()).into())
-            .content_type("application/x-tar")
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
             .send()
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to upload crate to S3: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("Failed to complete S3 multipart upload: {}", e))?;
 
-        info!("Successfully stored crate {}-{} to S3", name, version);
-        Ok(key)
+        debug!("Uploaded {} in {} parts", key, part_number - 1);
+        Ok(())
     }
+}
 
-    pub async fn get_crate(&self, name: &str, version: &str) -> Result<Vec<u8>> {
-        let key = format!("crates/{}/{}-{}.crate", name, name, version);
-        debug!("Retrieving crate from S3: {}", key);
-
-        let result = self.client
-            .get_object()
+#[async_trait]
+impl ArtifactStore for S3Store {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let mut request = self.client
+            .put_object()
             .bucket(&self.bucket)
-            .key(&key)
+            .key(key)
+            .body(ByteStream::from(data.to_vec()));
+
+        if key.ends_with(".crate") {
+            request = request.content_type("application/x-tar");
+        }
+
+        request
             .send()
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to download crate from S3: {}", e))?;
-
-        let data = result.body.collect().await
-            .map_err(|e| anyhow::anyhow!("Failed to read crate data from S3: {}", e))?
-            .into_bytes()
-            .to_vec();
+            .map_err(|e| anyhow::anyhow!("Failed to upload to S3: {}", e))?;
 
-        debug!("Successfully retrieved crate {}-{} from S3 ({} bytes)", name, version, data.len());
-        Ok(data)
+        Ok(())
     }
 
-    pub async fn crate_exists(&self, name: &str, version: &str) -> bool {
-        let key = format!("crates/{}/{}-{}.crate", name, name, version);
-        
-        match self.client.head_object().bucket(&self.bucket).key(&key).send().await {
-            Ok(_) => {
-                debug!("Crate exists in S3: {}", key);
-                true
-            }
-            Err(_) => {
-                debug!("Crate does not exist in S3: {}", key);
-                false
-            }
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        let size = tokio::fs::metadata(path).await?.len();
+        let content_type = key.ends_with(".crate").then_some("application/x-tar");
+
+        if size <= MULTIPART_PART_SIZE {
+            let body = ByteStream::from_path(path).await?;
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .set_content_type(content_type.map(String::from))
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to upload to S3: {}", e))?;
+
+            return Ok(());
         }
-    }
 
-    pub async fn get_crate_size(&self, name: &str, version: &str) -> Result<u64> {
-        let key = format!("crates/{}/{}-{}.crate", name, name, version);
-        
-        let result = self.client
-            .head_object()
+        let upload = self.client
+            .create_multipart_upload()
             .bucket(&self.bucket)
-            .key(&key)
+            .key(key)
+            .set_content_type(content_type.map(String::from))
             .send()
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to get crate metadata from S3: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("Failed to start S3 multipart upload: {}", e))?;
+        let upload_id = upload
+            .upload_id()
+            .ok_or_else(|| anyhow::anyhow!("S3 did not return a multipart upload id"))?;
 
-        Ok(result.content_length.unwrap_or(0) as u64)
+        if let Err(e) = self.upload_parts(key, upload_id, path, size).await {
+            // Uploaded parts are kept (and billed) until the upload is aborted
+            if let Err(abort) = self.client
+                .abort_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await
+            {
+                warn!("Failed to abort S3 multipart upload for {}: {}", key, abort);
+            }
+            return Err(e);
+        }
+
+        Ok(())
     }
 
-    pub fn get_download_url(&self, name: &str, version: &str) -> String {
-        let key = format!("crates/{}/{}-{}.crate", name, name, version);
-        
-        // If public URL is configured (for MinIO), use it
-        if let Some(ref public_url) = self.config.public_url {
-            format!("{}/{}/{}", public_url.trim_end_matches('/'), self.bucket, key)
-        } else if let Some(ref endpoint) = self.config.endpoint {
-            // For custom endpoints like MinIO
-            let protocol = if self.config.use_ssl { "https" } else { "http" };
-            if self.config.path_style {
-                format!("{}{}/{}/{}", protocol, endpoint.trim_start_matches("http://").trim_start_matches("https://"), self.bucket, key)
-            } else {
-                format!("{}{}.{}/{}", protocol, self.bucket, endpoint.trim_start_matches("http://").trim_start_matches("https://"), key)
+    async fn get(&self, key: &str) -> Result<Option<ArtifactStream>> {
+        match self.client.get_object().bucket(&self.bucket).key(key).send().await {
+            Ok(response) => {
+                let size = response.content_length().and_then(|len| u64::try_from(len).ok());
+                let reader = Box::pin(response.body.into_async_read());
+                Ok(Some(ArtifactStream { reader, size }))
             }
-        } else {
-            // Standard AWS S3 URL
-            format!("https://{}.s3.{}.amazonaws.com/{}", self.bucket, self.config.region, key)
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to get from S3: {}", e)),
         }
     }
 
-    pub async fn list_crates(&self, prefix: Option<&str>) -> Result<Vec<String>> {
-        let mut list_request = self.client
-            .list_objects_v2()
-            .bucket(&self.bucket);
-
-        if let Some(prefix) = prefix {
-            list_request = list_request.prefix(format!("crates/{}/", prefix));
-        } else {
-            list_request = list_request.prefix("crates/");
+    async fn head(&self, key: &str) -> Result<Option<ArtifactMeta>> {
+        match self.client.head_object().bucket(&self.bucket).key(key).send().await {
+            Ok(response) => Ok(Some(ArtifactMeta {
+                key: key.to_string(),
+                size: response.content_length().and_then(|len| u64::try_from(len).ok()).unwrap_or(0),
+                last_modified: response.last_modified().and_then(to_utc),
+            })),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to get S3 object metadata: {}", e)),
         }
+    }
 
-        let result = list_request.send().await
-            .map_err(|e| anyhow::anyhow!("Failed to list crates from S3: {}", e))?;
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to delete from S3: {}", e))?;
 
-        let mut crates = Vec::new();
-        if let Some(contents) = result.contents {
-            for object in contents {
-                if let Some(key) = object.key {
-                    if key.ends_with(".crate") {
-                        crates.push(key);
-                    }
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ArtifactMeta>> {
+        let mut pages = self.client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+
+        let mut objects = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| anyhow::anyhow!("Failed to list S3 objects: {}", e))?;
+            for object in page.contents() {
+                if let Some(key) = object.key() {
+                    objects.push(ArtifactMeta {
+                        key: key.to_string(),
+                        size: object.size().and_then(|size| u64::try_from(size).ok()).unwrap_or(0),
+                        last_modified: object.last_modified().and_then(to_utc),
+                    });
                 }
             }
         }
 
-        Ok(crates)
+        Ok(objects)
     }
 
-    pub async fn delete_crate(&self, name: &str, version: &str) -> Result<()> {
-        let key = format!("crates/{}/{}-{}.crate", name, name, version);
-        debug!("Deleting crate from S3: {}", key);
-
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
         self.client
-            .delete_object()
+            .copy_object()
             .bucket(&self.bucket)
-            .key(&key)
+            .copy_source(format!("{}/{}", self.bucket, urlencoding::encode(from)))
+            .key(to)
             .send()
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to delete crate from S3: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("Failed to copy S3 object: {}", e))?;
 
-        info!("Successfully deleted crate {}-{} from S3", name, version);
-        Ok(())
+        self.delete(from).await
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>> {
+        let request = self.presign_client
+            .as_ref()
+            .unwrap_or(&self.client)
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .presigned(PresigningConfig::expires_in(expires_in)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to presign S3 download: {}", e))?;
+
+        Ok(Some(request.uri().to_string()))
+    }
+}
+
+fn to_utc(time: &aws_sdk_s3::primitives::DateTime) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(time.secs(), time.subsec_nanos())
+}
+
+/// Round trips against a real S3 API, ignored by default. Run them with
+/// `cargo test -- --ignored` and `MINIO_ENDPOINT` pointing at a MinIO (or
+/// other S3-compatible) server, e.g. `docker run -p 9000:9000 minio/minio
+/// server /data`. `MINIO_ACCESS_KEY`, `MINIO_SECRET_KEY` and `MINIO_BUCKET`
+/// default to `minioadmin`, `minioadmin` and `ghostcrate-test`; the bucket
+/// is created if it is missing.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sha256_hex;
+    use tokio::io::AsyncReadExt;
+
+    async fn minio_store() -> (S3Store, String) {
+        let endpoint = std::env::var("MINIO_ENDPOINT").expect("MINIO_ENDPOINT must point at an S3-compatible server");
+        let env_or = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+
+        let config = S3Config {
+            bucket: env_or("MINIO_BUCKET", "ghostcrate-test"),
+            region: "us-east-1".to_string(),
+            endpoint: Some(endpoint.clone()),
+            access_key: env_or("MINIO_ACCESS_KEY", "minioadmin"),
+            secret_key: env_or("MINIO_SECRET_KEY", "minioadmin"),
+            path_style: true,
+            use_ssl: endpoint.starts_with("https://"),
+            public_url: None,
+            download_redirect: true,
+            presign_expiry_secs: 60,
+        };
+
+        // `connect` insists on an existing bucket
+        let bootstrap = Client::from_conf(
+            aws_sdk_s3::config::Builder::new()
+                .behavior_version(BehaviorVersion::latest())
+                .credentials_provider(Credentials::new(&config.access_key, &config.secret_key, None, None, "ghostcrate-test"))
+                .region(Region::new(config.region.clone()))
+                .endpoint_url(&endpoint)
+                .force_path_style(true)
+                .build(),
+        );
+        let _ = bootstrap.create_bucket().bucket(&config.bucket).send().await;
+
+        let store = S3Store::connect(&config).await.expect("failed to connect to MinIO");
+        (store, format!("test-{}/", uuid::Uuid::new_v4()))
+    }
+
+    async fn read(store: &S3Store, key: &str) -> Option<Vec<u8>> {
+        let mut artifact = store.get(key).await.unwrap()?;
+        let mut data = Vec::new();
+        artifact.reader.read_to_end(&mut data).await.unwrap();
+        Some(data)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs MINIO_ENDPOINT"]
+    async fn put_get_list_rename_delete() {
+        let (store, prefix) = minio_store().await;
+        let key = format!("{}a.crate", prefix);
+        let renamed = format!("{}b.crate", prefix);
+
+        store.put(&key, b"crate contents").await.unwrap();
+        assert_eq!(read(&store, &key).await.as_deref(), Some(&b"crate contents"[..]));
+        assert_eq!(store.head(&key).await.unwrap().map(|meta| meta.size), Some(14));
+
+        let listed: Vec<String> = store.list(&prefix).await.unwrap().into_iter().map(|meta| meta.key).collect();
+        assert_eq!(listed, vec![key.clone()]);
+
+        store.rename(&key, &renamed).await.unwrap();
+        assert!(store.head(&key).await.unwrap().is_none());
+        assert_eq!(read(&store, &renamed).await.as_deref(), Some(&b"crate contents"[..]));
+
+        store.delete(&renamed).await.unwrap();
+        assert!(read(&store, &renamed).await.is_none());
+        assert!(store.list(&prefix).await.unwrap().is_empty());
+
+        // Deleting a missing key is not an error
+        store.delete(&renamed).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs MINIO_ENDPOINT"]
+    async fn put_file_uses_multipart_above_part_size() {
+        let (store, prefix) = minio_store().await;
+        let key = format!("{}large.crate", prefix);
+
+        let data: Vec<u8> = (0..MULTIPART_PART_SIZE + 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("ghostcrate-s3-test-{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, &data).await.unwrap();
+
+        let stored = store.put_file(&key, &path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        stored.unwrap();
+
+        let read_back = read(&store, &key).await.unwrap();
+        assert_eq!(sha256_hex(&read_back), sha256_hex(&data));
+
+        store.delete(&key).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs MINIO_ENDPOINT"]
+    async fn presigned_url_serves_the_object() {
+        let (store, prefix) = minio_store().await;
+        let key = format!("{}presigned.crate", prefix);
+        store.put(&key, b"presigned contents").await.unwrap();
+
+        let url = store.presigned_url(&key, Duration::from_secs(60)).await.unwrap().unwrap();
+        let response = reqwest::get(&url).await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(&response.bytes().await.unwrap()[..], b"presigned contents");
+
+        store.delete(&key).await.unwrap();
     }
 }
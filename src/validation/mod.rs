@@ -0,0 +1,362 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use std::path::{Component, Path};
+
+use crate::models::PublishRequest;
+
+/// Upper bound on the unpacked size of a `.crate` file, matching crates.io.
+/// Anything beyond this is treated as a decompression bomb.
+pub const MAX_UNPACKED_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Largest `Cargo.toml` we are willing to parse.
+const MAX_MANIFEST_SIZE: u64 = 1024 * 1024;
+
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Names that can't be used as file names on Windows, or that would shadow
+/// the standard library.
+const RESERVED_NAMES: &[&str] = &[
+    "alloc", "core", "proc_macro", "std", "test",
+    "aux", "con", "nul", "prn",
+    "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Why a publish was rejected. The message is shown to the user by Cargo.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct ValidationError(pub String);
+
+fn invalid(message: impl Into<String>) -> ValidationError {
+    ValidationError(message.into())
+}
+
+/// Checks the publish metadata on its own: crate and dependency names,
+/// the version and dependency requirements.
+pub fn validate_metadata(metadata: &PublishRequest) -> Result<(), ValidationError> {
+    validate_crate_name(&metadata.name)?;
+
+    semver::Version::parse(&metadata.vers)
+        .map_err(|e| invalid(format!("invalid version `{}`: {}", metadata.vers, e)))?;
+
+    for dep in &metadata.deps {
+        validate_dependency_name(&dep.name)?;
+        semver::VersionReq::parse(&dep.version_req).map_err(|e| {
+            invalid(format!(
+                "invalid version requirement `{}` for dependency `{}`: {}",
+                dep.version_req, dep.name, e
+            ))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Applies crates.io's naming rules: ASCII letters, digits, `-` and `_`,
+/// starting with a letter, at most 64 characters and not a reserved name.
+pub fn validate_crate_name(name: &str) -> Result<(), ValidationError> {
+    if name.is_empty() {
+        return Err(invalid("crate name cannot be empty"));
+    }
+
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(invalid(format!(
+            "crate name `{}` is too long (max {} characters)",
+            name, MAX_NAME_LENGTH
+        )));
+    }
+
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Err(invalid(format!("crate name `{}` must start with a letter", name)));
+    }
+
+    if let Some(c) = name.chars().find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_') {
+        return Err(invalid(format!(
+            "invalid character `{}` in crate name `{}`: only letters, numbers, `-` and `_` are allowed",
+            c, name
+        )));
+    }
+
+    if RESERVED_NAMES.contains(&canonical_crate_name(name).as_str()) {
+        return Err(invalid(format!("crate name `{}` is reserved", name)));
+    }
+
+    Ok(())
+}
+
+/// Dependencies may live in other registries with looser rules, so only
+/// reject names that could never be valid crate names.
+fn validate_dependency_name(name: &str) -> Result<(), ValidationError> {
+    let is_valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !is_valid {
+        return Err(invalid(format!("invalid dependency name `{}`", name)));
+    }
+
+    Ok(())
+}
+
+/// The form Cargo compares crate names in: case-insensitive, and with `-`
+/// and `_` treated as the same character.
+pub fn canonical_crate_name(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+/// Unpacks the gzipped tarball as a stream, without extracting it, and
+/// checks that it is a well-formed package for `metadata`:
+///
+/// - it unpacks to at most `max_unpacked_size` bytes,
+/// - every entry is a plain file or directory under `{name}-{vers}/`,
+///   with no absolute paths or `..` components,
+/// - it contains a `Cargo.toml` whose package name and version match the metadata.
+pub fn validate_crate_file(
+    metadata: &PublishRequest,
+    crate_file: impl Read,
+    max_unpacked_size: u64,
+) -> Result<(), ValidationError> {
+    let prefix = format!("{}-{}", metadata.name, metadata.vers);
+
+    let decoder = LimitedReader {
+        inner: GzDecoder::new(crate_file),
+        remaining: max_unpacked_size,
+    };
+    let mut archive = tar::Archive::new(decoder);
+
+    let entries = archive
+        .entries()
+        .map_err(|e| invalid(format!("failed to read crate file: {}", e)))?;
+
+    let mut manifest = None;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| invalid(format!("failed to read crate file: {}", e)))?;
+
+        // Extended headers carry metadata for the entry that follows them
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_pax_global_extensions() || entry_type.is_pax_local_extensions() {
+            continue;
+        }
+
+        let path = entry
+            .path()
+            .map_err(|e| invalid(format!("invalid path in crate file: {}", e)))?
+            .into_owned();
+
+        validate_entry_path(&path, &prefix)?;
+
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            return Err(invalid(format!(
+                "crate file entry `{}` is not a regular file or directory",
+                path.display()
+            )));
+        }
+
+        if path == Path::new(&prefix).join("Cargo.toml") {
+            if entry.size() > MAX_MANIFEST_SIZE {
+                return Err(invalid("Cargo.toml in crate file is too large"));
+            }
+
+            let mut contents = String::new();
+            entry
+                .read_to_string(&mut contents)
+                .map_err(|e| invalid(format!("failed to read Cargo.toml from crate file: {}", e)))?;
+            manifest = Some(contents);
+        }
+    }
+
+    // Read anything left over (tar padding, trailing data) so it counts towards the limit
+    std::io::copy(&mut archive.into_inner(), &mut std::io::sink())
+        .map_err(|e| invalid(format!("failed to read crate file: {}", e)))?;
+
+    let manifest = manifest.ok_or_else(|| {
+        invalid(format!("crate file does not contain `{}/Cargo.toml`", prefix))
+    })?;
+
+    validate_manifest(metadata, &manifest)
+}
+
+/// Fails reads once more than `remaining` bytes have come out of `inner`.
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+
+        if read as u64 > self.remaining {
+            return Err(std::io::Error::other("crate file is too large when unpacked"));
+        }
+
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+fn validate_entry_path(path: &Path, prefix: &str) -> Result<(), ValidationError> {
+    let mut components = path.components();
+
+    let is_under_prefix = matches!(components.next(), Some(Component::Normal(first)) if first == prefix);
+    let is_plain = components.all(|c| matches!(c, Component::Normal(_)));
+
+    if !is_under_prefix || !is_plain {
+        return Err(invalid(format!(
+            "invalid path `{}` in crate file: all files must be inside `{}/`",
+            path.display(),
+            prefix
+        )));
+    }
+
+    Ok(())
+}
+
+fn validate_manifest(metadata: &PublishRequest, manifest: &str) -> Result<(), ValidationError> {
+    let manifest: toml::Table = manifest
+        .parse()
+        .map_err(|e| invalid(format!("failed to parse Cargo.toml in crate file: {}", e)))?;
+
+    let package = manifest
+        .get("package")
+        .and_then(|package| package.as_table())
+        .ok_or_else(|| invalid("Cargo.toml in crate file has no [package] section"))?;
+
+    let name = package.get("name").and_then(|name| name.as_str());
+    if name != Some(metadata.name.as_str()) {
+        return Err(invalid(format!(
+            "package name in Cargo.toml ({}) does not match the published name `{}`",
+            name.map(|n| format!("`{}`", n)).unwrap_or_else(|| "missing".to_string()),
+            metadata.name
+        )));
+    }
+
+    let version = package.get("version").and_then(|version| version.as_str());
+    if version != Some(metadata.vers.as_str()) {
+        return Err(invalid(format!(
+            "package version in Cargo.toml ({}) does not match the published version `{}`",
+            version.map(|v| format!("`{}`", v)).unwrap_or_else(|| "missing".to_string()),
+            metadata.vers
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::{EntryType, Header};
+
+    const MANIFEST: &str = "[package]\nname = \"demo\"\nversion = \"1.0.0\"\n";
+
+    fn metadata() -> PublishRequest {
+        serde_json::from_value(serde_json::json!({
+            "name": "demo", "vers": "1.0.0", "deps": [], "features": {}, "authors": [],
+            "keywords": [], "categories": [], "badges": {},
+        }))
+        .unwrap()
+    }
+
+    /// A tarball entry. The path is written into the header as is, since
+    /// `tar::Builder` would refuse the malicious ones.
+    struct Entry<'a> {
+        path: &'a str,
+        entry_type: EntryType,
+        data: &'a [u8],
+        link: Option<&'a str>,
+    }
+
+    fn file<'a>(path: &'a str, data: &'a [u8]) -> Entry<'a> {
+        Entry { path, entry_type: EntryType::Regular, data, link: None }
+    }
+
+    fn crate_file(entries: &[Entry]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for entry in entries {
+            let mut header = Header::new_gnu();
+            let name = &mut header.as_old_mut().name;
+            name[..entry.path.len()].copy_from_slice(entry.path.as_bytes());
+            header.set_entry_type(entry.entry_type);
+            header.set_size(entry.data.len() as u64);
+            header.set_mode(0o644);
+            if let Some(link) = entry.link {
+                header.set_link_name(link).unwrap();
+            }
+            header.set_cksum();
+            builder.append(&header, entry.data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn validate(entries: &[Entry]) -> Result<(), ValidationError> {
+        validate_crate_file(&metadata(), &crate_file(entries)[..], MAX_UNPACKED_SIZE)
+    }
+
+    fn assert_rejected(result: Result<(), ValidationError>, message: &str) {
+        match result {
+            Ok(()) => panic!("accepted, expected an error containing `{}`", message),
+            Err(e) => assert!(e.0.contains(message), "`{}` does not contain `{}`", e.0, message),
+        }
+    }
+
+    #[test]
+    fn accepts_a_well_formed_crate() {
+        validate(&[
+            file("demo-1.0.0/Cargo.toml", MANIFEST.as_bytes()),
+            file("demo-1.0.0/src/lib.rs", b"pub fn demo() {}\n"),
+        ])
+        .unwrap();
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_package_directory() {
+        for path in ["demo-1.0.0/../escape.rs", "/etc/passwd", "other-1.0.0/src/lib.rs", "demo-1.0.1/src/lib.rs", "lib.rs"] {
+            assert_rejected(
+                validate(&[file("demo-1.0.0/Cargo.toml", MANIFEST.as_bytes()), file(path, b"")]),
+                "all files must be inside `demo-1.0.0/`",
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_links() {
+        for entry_type in [EntryType::Symlink, EntryType::Link] {
+            let link = Entry { path: "demo-1.0.0/src/lib.rs", entry_type, data: b"", link: Some("/etc/passwd") };
+            assert_rejected(
+                validate(&[file("demo-1.0.0/Cargo.toml", MANIFEST.as_bytes()), link]),
+                "is not a regular file or directory",
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_crates_that_unpack_past_the_limit() {
+        let zeros = vec![0; 1024 * 1024];
+        let packed = crate_file(&[file("demo-1.0.0/Cargo.toml", MANIFEST.as_bytes()), file("demo-1.0.0/zeros", &zeros)]);
+        assert!(packed.len() < 64 * 1024, "the test needs a small gzip stream");
+
+        assert_rejected(validate_crate_file(&metadata(), &packed[..], 64 * 1024), "too large when unpacked");
+        validate_crate_file(&metadata(), &packed[..], 2 * 1024 * 1024).unwrap();
+    }
+
+    #[test]
+    fn rejects_manifests_that_do_not_match_the_metadata() {
+        let cases = [
+            ("[package]\nname = \"other\"\nversion = \"1.0.0\"\n", "does not match the published name `demo`"),
+            ("[package]\nname = \"demo\"\nversion = \"1.0.1\"\n", "does not match the published version `1.0.0`"),
+            ("[package]\nversion = \"1.0.0\"\n", "package name in Cargo.toml (missing)"),
+            ("[dependencies]\n", "has no [package] section"),
+        ];
+        for (manifest, message) in cases {
+            assert_rejected(validate(&[file("demo-1.0.0/Cargo.toml", manifest.as_bytes())]), message);
+        }
+
+        assert_rejected(
+            validate(&[file("demo-1.0.0/src/lib.rs", b"")]),
+            "does not contain `demo-1.0.0/Cargo.toml`",
+        );
+    }
+}
@@ -1,14 +1,24 @@
 use axum::{
-    extract::{Query, State, Path},
+    extract::State,
     http::StatusCode,
     response::{Json, Html},
     Extension,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use sqlx::Row;
 
-use crate::models::{User, UserResponse};
+use anyhow::Context;
+
+use crate::models::{IntegrityIssue, User, UserResponse};
+use crate::error::{ApiError, ApiResult, ApiPath, ApiQuery};
+use crate::storage::{
+    Storage,
+    gc::{collect_garbage, GcOptions, GcReport},
+    integrity::{scrub_storage, MirrorUpstream, ScrubProgress},
+    migration::{migrate_storage, MigrationProgress},
+    replication::ReplicationStats,
+};
+use crate::db;
 use crate::AppState;
 
 #[derive(Deserialize)]
@@ -17,6 +27,21 @@ pub struct AdminQuery {
     pub per_page: Option<u32>,
 }
 
+/// Overrides for a garbage collection started from the admin API; anything
+/// left out comes from the configuration.
+#[derive(Deserialize, Default)]
+pub struct GcRequest {
+    pub dry_run: Option<bool>,
+    pub grace_period_hours: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct IntegrityReport {
+    pub scrub: ScrubProgress,
+    /// Artifacts that failed their most recent check
+    pub issues: Vec<IntegrityIssue>,
+}
+
 #[derive(Serialize)]
 pub struct AdminStats {
     pub total_users: i64,
@@ -28,10 +53,10 @@ pub struct AdminStats {
 #[cfg(feature = "ssr")]
 pub async fn admin_dashboard_handler(
     Extension(user): Extension<User>,
-) -> Result<Html<String>, StatusCode> {
+) -> ApiResult<Html<String>> {
     // Check if user is admin
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("this page requires an administrator"));
     }
 
     let html = format!(r#"
@@ -167,57 +192,34 @@ pub async fn admin_dashboard_handler(
 pub async fn admin_stats_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-) -> Result<Json<AdminStats>, StatusCode> {
+) -> ApiResult<Json<AdminStats>> {
     // Check if user is admin
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("this page requires an administrator"));
     }
 
     // Get total users
-    let total_users = sqlx::query("SELECT COUNT(*) as count FROM users")
-        .fetch_one(&app_state.pool)
+    let total_users = db::count_total_users(&app_state.pool)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .get::<i64, _>("count");
+        .context("failed to count users")?;
 
     // Get total crates
-    let total_crates = sqlx::query("SELECT COUNT(*) as count FROM crates")
-        .fetch_one(&app_state.pool)
+    let total_crates = db::count_total_crates(&app_state.pool)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .get::<i64, _>("count");
+        .context("failed to count crates")?;
 
     // Get total downloads
-    let total_downloads = sqlx::query("SELECT COALESCE(SUM(downloads), 0) as total FROM crates")
-        .fetch_one(&app_state.pool)
+    let total_downloads = db::count_total_downloads(&app_state.pool)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .get::<i64, _>("total");
+        .context("failed to count downloads")?;
 
     // Get recent users
-    let recent_users_rows = sqlx::query(
-        "SELECT id, username, email, password_hash, is_admin, created_at, updated_at FROM users ORDER BY created_at DESC LIMIT 10"
-    )
-    .fetch_all(&app_state.pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let mut recent_users = Vec::new();
-    for row in recent_users_rows {
-        let user = User {
-            id: Uuid::parse_str(&row.get::<String, _>("id")).unwrap(),
-            username: row.get("username"),
-            email: row.get("email"),
-            password_hash: row.get("password_hash"),
-            is_admin: row.get("is_admin"),
-            github_id: row.get("github_id"),
-            github_username: row.get("github_username"),
-            avatar_url: row.get("avatar_url"),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at")).unwrap().with_timezone(&chrono::Utc),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at")).unwrap().with_timezone(&chrono::Utc),
-        };
-        recent_users.push(user.into());
-    }
+    let recent_users = db::list_users(&app_state.pool, 10, 0)
+        .await
+        .context("failed to list recent users")?
+        .into_iter()
+        .map(UserResponse::from)
+        .collect();
 
     Ok(Json(AdminStats {
         total_users,
@@ -231,42 +233,23 @@ pub async fn admin_stats_handler(
 pub async fn admin_users_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Query(params): Query<AdminQuery>,
-) -> Result<Json<Vec<UserResponse>>, StatusCode> {
+    ApiQuery(params): ApiQuery<AdminQuery>,
+) -> ApiResult<Json<Vec<UserResponse>>> {
     // Check if user is admin
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("this page requires an administrator"));
     }
 
     let per_page = params.per_page.unwrap_or(50).min(100) as i64;
     let page = params.page.unwrap_or(1) as i64;
     let offset = (page - 1) * per_page;
 
-    let rows = sqlx::query(
-        "SELECT id, username, email, password_hash, is_admin, github_id, github_username, avatar_url, created_at, updated_at FROM users ORDER BY created_at DESC LIMIT ?1 OFFSET ?2"
-    )
-    .bind(per_page)
-    .bind(offset)
-    .fetch_all(&app_state.pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let mut users = Vec::new();
-    for row in rows {
-        let user = User {
-            id: Uuid::parse_str(&row.get::<String, _>("id")).unwrap(),
-            username: row.get("username"),
-            email: row.get("email"),
-            password_hash: row.get("password_hash"),
-            is_admin: row.get("is_admin"),
-            github_id: row.get("github_id"),
-            github_username: row.get("github_username"),
-            avatar_url: row.get("avatar_url"),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at")).unwrap().with_timezone(&chrono::Utc),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<String, _>("updated_at")).unwrap().with_timezone(&chrono::Utc),
-        };
-        users.push(user.into());
-    }
+    let users = db::list_users(&app_state.pool, per_page, offset)
+        .await
+        .context("failed to list users")?
+        .into_iter()
+        .map(UserResponse::from)
+        .collect();
 
     Ok(Json(users))
 }
@@ -275,23 +258,206 @@ pub async fn admin_users_handler(
 pub async fn admin_delete_user_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Path(user_id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+    ApiPath(user_id): ApiPath<Uuid>,
+) -> ApiResult<StatusCode> {
     // Check if user is admin
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("this page requires an administrator"));
     }
 
     // Prevent self-deletion
     if user.id == user_id {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request("you cannot delete your own account"));
     }
 
-    sqlx::query("DELETE FROM users WHERE id = ?1")
-        .bind(user_id.to_string())
-        .execute(&app_state.pool)
+    db::delete_user(&app_state.pool, user_id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .context("failed to delete user")?;
 
     Ok(StatusCode::OK)
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "ssr")]
+pub async fn admin_storage_migration_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<Json<MigrationProgress>> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    Ok(Json(app_state.storage_migration.snapshot()))
+}
+
+/// Starts copying every artifact to the configured migration target in the
+/// background. Poll `GET` on the same route for progress.
+#[cfg(feature = "ssr")]
+pub async fn admin_start_storage_migration_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<(StatusCode, Json<MigrationProgress>)> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    let target_config = app_state.config.storage_migration_target.clone().ok_or_else(|| {
+        ApiError::bad_request("no storage migration target is configured (set MIGRATION_TARGET_STORAGE_BACKEND)")
+    })?;
+    if target_config.location() == app_state.storage.config().location() {
+        return Err(ApiError::bad_request("the migration target is the storage already in use"));
+    }
+
+    let target = Storage::new(target_config)
+        .await
+        .context("failed to connect to the storage migration target")?
+        .with_encryption(&app_state.config.storage_encryption)
+        .await
+        .context("failed to set up encryption for the storage migration target")?;
+
+    let tracker = app_state.storage_migration.clone();
+    if !tracker.try_start(&app_state.storage, &target) {
+        return Err(ApiError::conflict("a storage migration is already running"));
+    }
+
+    let pool = app_state.pool.clone();
+    let source = app_state.storage.clone();
+    let task_tracker = tracker.clone();
+    tokio::spawn(async move {
+        migrate_storage(&pool, &source, &target, &task_tracker).await;
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(tracker.snapshot())))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn admin_integrity_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<Json<IntegrityReport>> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    let issues = db::get_integrity_issues(&app_state.pool).await?;
+
+    Ok(Json(IntegrityReport {
+        scrub: app_state.integrity_scrub.snapshot(),
+        issues,
+    }))
+}
+
+/// Starts an integrity scrub now instead of waiting for the next scheduled one.
+#[cfg(feature = "ssr")]
+pub async fn admin_start_integrity_scrub_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<(StatusCode, Json<ScrubProgress>)> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    let tracker = app_state.integrity_scrub.clone();
+    if !tracker.try_start() {
+        return Err(ApiError::conflict("an integrity scrub is already running"));
+    }
+
+    let upstream = MirrorUpstream::from_config(&app_state.config, &app_state.upstream_client);
+    let pool = app_state.pool.clone();
+    let storage = app_state.storage.clone();
+    let task_tracker = tracker.clone();
+    tokio::spawn(async move {
+        scrub_storage(&pool, &storage, upstream.as_ref(), &task_tracker).await;
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(tracker.snapshot())))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn admin_storage_gc_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<Json<GcReport>> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    Ok(Json(app_state.storage_gc.snapshot()))
+}
+
+/// Starts collecting orphaned artifacts in the background.
+#[cfg(feature = "ssr")]
+pub async fn admin_start_storage_gc_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    request: Option<Json<GcRequest>>,
+) -> ApiResult<(StatusCode, Json<GcReport>)> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let defaults = GcOptions::from(&app_state.config.storage_gc);
+    let options = GcOptions {
+        grace_period_hours: request.grace_period_hours.unwrap_or(defaults.grace_period_hours),
+        dry_run: request.dry_run.unwrap_or(defaults.dry_run),
+    };
+
+    let tracker = app_state.storage_gc.clone();
+    if !tracker.try_start(&options) {
+        return Err(ApiError::conflict("a storage garbage collection is already running"));
+    }
+
+    let pool = app_state.pool.clone();
+    let storage = app_state.storage.clone();
+    let task_tracker = tracker.clone();
+    tokio::spawn(async move {
+        collect_garbage(&pool, &storage, &options, &task_tracker).await;
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(tracker.snapshot())))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn admin_storage_replication_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<Json<ReplicationStats>> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    let stats = app_state.storage
+        .replication_stats()
+        .ok_or_else(|| ApiError::not_found("storage replication is not enabled"))?;
+    Ok(Json(stats))
+}
+
+/// Compares the replica with the primary now and queues whatever it is
+/// missing, including changes that previously ran out of attempts.
+#[cfg(feature = "ssr")]
+pub async fn admin_reconcile_storage_replica_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<Json<ReplicationStats>> {
+    // Check if user is admin
+    if !user.is_admin {
+        return Err(ApiError::forbidden("this page requires an administrator"));
+    }
+
+    if app_state.storage.replication_stats().is_none() {
+        return Err(ApiError::not_found("storage replication is not enabled"));
+    }
+    app_state.storage
+        .reconcile_replica()
+        .await
+        .context("failed to reconcile the storage replica")?;
+
+    admin_storage_replication_handler(State(app_state), Extension(user)).await
+}
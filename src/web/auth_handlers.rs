@@ -6,31 +6,38 @@ use axum::{
 };
 
 use crate::auth::{authenticate_user, register_user};
+use crate::error::{ApiError, ApiResult, ApiJson};
 use crate::models::{LoginRequest, CreateUserRequest, LoginResponse, UserResponse};
 
 #[cfg(feature = "ssr")]
 pub async fn login_handler(
     State(app_state): State<crate::AppState>,
-    Json(login_request): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
+    ApiJson(login_request): ApiJson<LoginRequest>,
+) -> ApiResult<Json<LoginResponse>> {
     match authenticate_user(&app_state.pool, login_request, &app_state.config.auth).await {
         Ok(response) => Ok(Json(response)),
-        Err(_) => Err(StatusCode::UNAUTHORIZED),
+        Err(e) => {
+            tracing::debug!("Login failed: {}", e);
+            Err(ApiError::unauthorized("invalid username or password"))
+        }
     }
 }
 
 #[cfg(feature = "ssr")]
 pub async fn register_handler(
     State(app_state): State<crate::AppState>,
-    Json(create_request): Json<CreateUserRequest>,
-) -> Result<Json<UserResponse>, StatusCode> {
+    ApiJson(create_request): ApiJson<CreateUserRequest>,
+) -> ApiResult<Json<UserResponse>> {
     if !app_state.config.registry.public_registration {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("registration is disabled on this registry"));
     }
 
     match register_user(&app_state.pool, create_request, &app_state.config.auth).await {
         Ok(user) => Ok(Json(user)),
-        Err(_) => Err(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            tracing::debug!("Registration failed: {}", e);
+            Err(ApiError::bad_request("could not register user; the username or email may already be taken"))
+        }
     }
 }
 
@@ -38,7 +45,7 @@ pub async fn register_handler(
 pub async fn logout_handler(
     State(_app_state): State<crate::AppState>,
     Extension(_user): Extension<crate::models::User>,
-) -> Result<StatusCode, StatusCode> {
+) -> ApiResult<StatusCode> {
     // TODO: Invalidate the specific token by deleting from sessions table
     // For now, we'll just return success
     Ok(StatusCode::OK)
@@ -47,6 +54,6 @@ pub async fn logout_handler(
 pub async fn me_handler(
     State(_app_state): State<crate::AppState>,
     Extension(user): Extension<crate::models::User>,
-) -> Result<Json<UserResponse>, StatusCode> {
+) -> ApiResult<Json<UserResponse>> {
     Ok(Json(user.into()))
 }
\ No newline at end of file
@@ -1,24 +1,37 @@
 use axum::{
-    extract::{Path, Query, State, Multipart},
-    http::{StatusCode, HeaderMap},
+    extract::{FromRequest, Request, State, Multipart},
+    http::{header, StatusCode},
     response::{Json, Response},
     body::Body,
     Extension,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
-use std::collections::HashMap;
+use anyhow::Context;
+use std::collections::{BTreeMap, HashMap};
+use futures::TryStreamExt;
+use tokio::io::AsyncReadExt;
+use tokio_util::io::{ReaderStream, StreamReader};
 use uuid::Uuid;
-use chrono::Utc;
-use sha2::{Sha256, Digest};
-use tokio_util::io::ReaderStream;
 
-use crate::models::{PublishRequest, PublishResponse, PublishWarnings, SearchResponse, SearchMeta, CrateResponse, User, VersionResponse, LinksResponse, VersionLinksResponse, UserLinkResponse};
-use crate::{AppState, db};
+use crate::models::{
+    numeric_id, AuthContext, TokenScope, Crate, CrateVersion, DependentVersion, IndexConfig, PublishDependency, PublishRequest, PublishResponse, PublishWarnings,
+    CrateSearch, SearchSort, SearchResponse, SearchMeta, CrateResponse, User, VersionResponse, LinksResponse, VersionLinksResponse,
+    UserLinkResponse, CrateDetailResponse, KeywordResponse, CategoryResponse, VersionsResponse,
+    SingleVersionResponse, DependenciesResponse, DependencyResponse, ReverseDependenciesResponse,
+    VersionDownloadsResponse, VersionDownloadResponse, AuthorsResponse, AuthorsMeta,
+};
+use crate::{AppState, db, index, validation};
+use crate::error::{ApiError, ApiResult, ApiPath, ApiQuery};
+use crate::storage::{ArtifactStream, CrateDownload, SpooledUpload, StagedCrate};
+use crate::index::git::CommitAuthor;
 
 #[derive(Deserialize)]
 pub struct SearchQuery {
     pub q: Option<String>,
+    pub keyword: Option<String>,
+    pub category: Option<String>,
+    pub sort: Option<SearchSort>,
     pub per_page: Option<u32>,
     pub page: Option<u32>,
 }
@@ -28,239 +41,534 @@ pub struct DownloadQuery {
     // No query parameters for download currently
 }
 
+/// Largest metadata JSON accepted in a publish request.
+const MAX_PUBLISH_METADATA_SIZE: u32 = 2 * 1024 * 1024;
+
+/// Body limit for multipart publishes, which are read into memory: the
+/// largest accepted crate plus room for its metadata.
+pub fn publish_body_limit(max_crate_size: u64) -> usize {
+    max_crate_size as usize + MAX_PUBLISH_METADATA_SIZE as usize
+}
+
 #[cfg(feature = "ssr")]
-pub async fn config_handler() -> Json<serde_json::Value> {
-    Json(json!({
-        "dl": "http://localhost:8080/api/v1/crates/{crate}/{version}/download",
-        "api": "http://localhost:8080",
-        "auth-required": true
-    }))
+pub async fn config_handler(
+    State(app_state): State<AppState>,
+) -> Json<IndexConfig> {
+    Json(index::registry_config(&app_state.config))
 }
 
 #[cfg(feature = "ssr")]
 pub async fn publish_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    mut multipart: Multipart,
-) -> Result<Json<PublishResponse>, StatusCode> {
-    let mut crate_file: Option<Vec<u8>> = None;
-    let mut metadata: Option<PublishRequest> = None;
+    Extension(auth): Extension<AuthContext>,
+    request: Request,
+) -> ApiResult<Json<PublishResponse>> {
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("multipart/form-data"))
+        .unwrap_or(false);
 
-    // Parse multipart form data
-    while let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? {
-        let name = field.name().unwrap_or("").to_string();
-        
-        match name.as_str() {
-            "crate" => {
-                let data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
-                crate_file = Some(data.to_vec());
-            }
-            "metadata" => {
-                let data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
-                let metadata_str = String::from_utf8(data.to_vec()).map_err(|_| StatusCode::BAD_REQUEST)?;
-                metadata = Some(serde_json::from_str(&metadata_str).map_err(|_| StatusCode::BAD_REQUEST)?);
-            }
-            _ => {} // Ignore unknown fields
+    let max_crate_size = app_state.config.registry.max_crate_size;
+
+    // `cargo publish` sends the binary format; multipart is kept for older clients
+    let (metadata, upload) = if is_multipart {
+        let multipart = Multipart::from_request(request, &app_state)
+            .await
+            .map_err(|e| ApiError::bad_request(format!("invalid multipart upload: {}", e)))?;
+        let (metadata, crate_file) = read_multipart_publish(multipart).await?;
+        if crate_file.len() as u64 > max_crate_size {
+            return Err(crate_too_large(max_crate_size));
         }
+        let upload = SpooledUpload::receive(&mut &crate_file[..], crate_file.len() as u64)
+            .await
+            .context("failed to spool upload")?;
+        (metadata, upload)
+    } else {
+        read_publish_body(request.into_body(), max_crate_size).await?
+    };
+
+    // Reject malformed or malicious uploads before anything is stored
+    validation::validate_metadata(&metadata)?;
+    let metadata = validate_spooled_crate(metadata, &upload).await?;
+
+    let conflicting_name = db::find_conflicting_crate_name(&app_state.pool, &metadata.name).await?;
+    if let Some(existing) = conflicting_name {
+        return Err(ApiError::bad_request(format!(
+            "crate `{}` conflicts with existing crate `{}`; names differing only in case or `-`/`_` are the same crate",
+            metadata.name, existing
+        )));
     }
 
-    let crate_file = crate_file.ok_or(StatusCode::BAD_REQUEST)?;
-    let metadata = metadata.ok_or(StatusCode::BAD_REQUEST)?;
+    // Hashed while the upload was received
+    let checksum = upload.checksum.clone();
 
-    // Calculate checksum
-    let mut hasher = Sha256::new();
-    hasher.update(&crate_file);
-    let checksum = format!("{:x}", hasher.finalize());
+    // Check permissions and the existing version before touching storage
+    let existing_crate = db::get_crate_by_name(&app_state.pool, &metadata.name).await?;
 
-    // Store the crate file
-    let _filename = app_state.storage
-        .store_crate(&metadata.name, &metadata.vers, &crate_file)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Check if crate exists, create if not
-    let crate_model = match db::get_crate_by_name(&app_state.pool, &metadata.name).await {
-        Ok(Some(existing_crate)) => {
-            // Check ownership
-            if existing_crate.owner_id != user.id {
-                return Err(StatusCode::FORBIDDEN);
+    match &existing_crate {
+        Some(existing_crate) => {
+            if !auth.permits(TokenScope::PublishUpdate, &existing_crate.name) {
+                return Err(ApiError::forbidden(format!(
+                    "this token does not have permission to publish new versions of `{}`",
+                    existing_crate.name
+                )));
+            }
+            ensure_can_publish(&app_state, existing_crate, &user).await?;
+
+            if check_existing_version(&app_state, existing_crate, &metadata.vers, &checksum).await? {
+                return Ok(Json(publish_response()));
             }
-            existing_crate
         }
-        Ok(None) => {
-            // Create new crate
-            db::create_crate(&app_state.pool, &metadata, user.id)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        None => {
+            if !auth.permits(TokenScope::PublishNew, &metadata.name) {
+                return Err(ApiError::forbidden(format!(
+                    "this token does not have permission to publish the new crate `{}`",
+                    metadata.name
+                )));
+            }
         }
-        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-    };
+    }
+
+    // Phase one: stage the upload where nothing can download it yet
+    let staged = app_state.storage
+        .stage_crate(&upload)
+        .await
+        .with_context(|| format!("failed to stage upload of {} {}", metadata.name, metadata.vers))?;
 
-    // Create new version
-    let _version = db::create_crate_version(
-        &app_state.pool,
-        crate_model.id,
+    // Phase two: promote the artifact, then record the release
+    let committed = commit_publish(
+        &app_state,
+        existing_crate.as_ref(),
         &metadata,
         &checksum,
-        crate_file.len() as i64,
-    )
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        upload.size as i64,
+        &user,
+        &staged,
+    ).await;
+
+    if let Err(error) = committed {
+        if let Err(e) = app_state.storage.discard_staged(&staged).await {
+            tracing::warn!("Failed to discard staged upload of {} {}: {}", metadata.name, metadata.vers, e);
+        }
+
+        // Lost a race with an identical publish of the same version. On a
+        // first publish the crate itself may have been created meanwhile.
+        let current_crate = match existing_crate {
+            Some(existing_crate) => Some(existing_crate),
+            None => db::get_crate_by_name(&app_state.pool, &metadata.name).await?,
+        };
+        if let Some(current_crate) = &current_crate {
+            if check_existing_version(&app_state, current_crate, &metadata.vers, &checksum).await? {
+                return Ok(Json(publish_response()));
+            }
+        }
+
+        return Err(error);
+    }
+
+    // The database is the source of truth; a failed commit is healed by the next sync
+    if let Some(git_index) = &app_state.git_index {
+        let author = CommitAuthor { name: &user.username, email: &user.email };
+        let message = format!("Publish {} {}", metadata.name, metadata.vers);
+        if let Err(e) = git_index.update_crate(&app_state.pool, &metadata.name, &message, &author).await {
+            tracing::warn!("Failed to update git index for {}: {}", metadata.name, e);
+        }
+    }
     
     tracing::info!(
         "Published crate {} version {} by user {} ({} bytes, checksum: {})",
         metadata.name,
         metadata.vers,
         user.username,
-        crate_file.len(),
+        upload.size,
         checksum
     );
 
-    Ok(Json(PublishResponse {
+    Ok(Json(publish_response()))
+}
+
+/// Moves the staged artifact into the blob store, then inserts the crate (if
+/// new) and version rows in one transaction. Storage I/O happens before the
+/// transaction starts, so a slow backend never holds the database's write
+/// lock. The blob stays locked until the commit, so the garbage collector
+/// can't remove it in between; if the rows aren't committed, a blob this
+/// publish stored is removed again. Losing a race to create the same crate
+/// or version is reported as a conflict.
+#[cfg(feature = "ssr")]
+async fn commit_publish(
+    app_state: &AppState,
+    existing_crate: Option<&Crate>,
+    metadata: &PublishRequest,
+    checksum: &str,
+    file_size: i64,
+    user: &User,
+    staged: &StagedCrate,
+) -> ApiResult<()> {
+    let _blob_lock = app_state.storage.lock_blob(checksum).await;
+
+    let stored = app_state.storage
+        .promote_staged(staged, checksum)
+        .await
+        .with_context(|| format!("failed to store {} {}", metadata.name, metadata.vers))?;
+    if !app_state.storage.blob_exists(checksum).await {
+        return Err(anyhow::anyhow!("{} {} is missing from storage after it was stored", metadata.name, metadata.vers).into());
+    }
+
+    let recorded = record_publish(app_state, existing_crate, metadata, checksum, file_size, user).await;
+    if recorded.is_err() && stored {
+        if let Err(e) = app_state.storage.delete_blob(checksum).await {
+            tracing::warn!("Failed to remove blob of uncommitted publish {} {}: {}", metadata.name, metadata.vers, e);
+        }
+    }
+    recorded
+}
+
+/// The database half of [`commit_publish`]: the rows and the search index,
+/// committed together.
+#[cfg(feature = "ssr")]
+async fn record_publish(
+    app_state: &AppState,
+    existing_crate: Option<&Crate>,
+    metadata: &PublishRequest,
+    checksum: &str,
+    file_size: i64,
+    user: &User,
+) -> ApiResult<()> {
+    let concurrently_created = |what: String| ApiError::conflict(format!(
+        "{} was created by another publish at the same time; try publishing again",
+        what
+    ));
+
+    let mut tx = app_state.pool.begin().await.context("failed to start publish transaction")?;
+
+    let crate_id = match existing_crate {
+        Some(existing_crate) => existing_crate.id,
+        None => match db::create_crate(&mut tx, metadata, user.id).await {
+            Ok(crate_model) => crate_model.id,
+            Err(e) if db::is_unique_violation(&e) => return Err(concurrently_created(format!("crate `{}`", metadata.name))),
+            Err(e) => return Err(e.context(format!("failed to create crate {}", metadata.name)).into()),
+        },
+    };
+
+    match db::create_crate_version(&mut *tx, crate_id, metadata, checksum, file_size, user.id).await {
+        Ok(_) => {}
+        Err(e) if db::is_unique_violation(&e) => {
+            return Err(concurrently_created(format!("crate version `{}@{}`", metadata.name, metadata.vers)));
+        }
+        Err(e) => return Err(e.context(format!("failed to create version {} {}", metadata.name, metadata.vers)).into()),
+    }
+
+    db::refresh_crate_search(&mut tx, crate_id)
+        .await
+        .with_context(|| format!("failed to index {} for search", metadata.name))?;
+
+    tx.commit()
+        .await
+        .with_context(|| format!("failed to commit publish of {} {}", metadata.name, metadata.vers))?;
+
+    Ok(())
+}
+
+/// Returns `true` if the version is already published with this checksum, so
+/// the publish can succeed without doing anything. A different checksum for
+/// an existing version is a conflict: published versions are immutable.
+#[cfg(feature = "ssr")]
+async fn check_existing_version(
+    app_state: &AppState,
+    crate_model: &Crate,
+    version: &str,
+    checksum: &str,
+) -> ApiResult<bool> {
+    let existing = db::get_crate_version(&app_state.pool, crate_model.id, version).await?;
+
+    match existing {
+        Some(existing) if existing.checksum == checksum => {
+            tracing::info!("Crate {} version {} already published with the same checksum", crate_model.name, version);
+            Ok(true)
+        }
+        Some(_) => Err(ApiError::conflict(format!(
+            "crate version `{}@{}` is already uploaded with different contents",
+            crate_model.name, version
+        ))),
+        None => Ok(false),
+    }
+}
+
+fn publish_response() -> PublishResponse {
+    PublishResponse {
         warnings: PublishWarnings {
             invalid_categories: vec![],
             invalid_badges: vec![],
             other: vec![],
         },
-    }))
+    }
+}
+
+/// Reads Cargo's publish wire format: a little-endian u32 length followed by
+/// the JSON metadata, then a little-endian u32 length followed by the `.crate`
+/// file. The crate is streamed to a temporary file, and its declared length is
+/// checked against `max_crate_size` before any of it is read.
+#[cfg(feature = "ssr")]
+async fn read_publish_body(body: Body, max_crate_size: u64) -> ApiResult<(PublishRequest, SpooledUpload)> {
+    let invalid_body = || ApiError::bad_request("invalid publish request body");
+    let mut body = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+
+    let metadata_len = body.read_u32_le().await.map_err(|_| invalid_body())?;
+    if metadata_len > MAX_PUBLISH_METADATA_SIZE {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("publish metadata is larger than {} bytes", MAX_PUBLISH_METADATA_SIZE),
+        ));
+    }
+    let mut metadata = vec![0; metadata_len as usize];
+    body.read_exact(&mut metadata).await.map_err(|_| invalid_body())?;
+    let metadata: PublishRequest = serde_json::from_slice(&metadata)
+        .map_err(|e| ApiError::bad_request(format!("invalid publish metadata: {}", e)))?;
+
+    let crate_len = body.read_u32_le().await.map_err(|_| invalid_body())? as u64;
+    if crate_len > max_crate_size {
+        return Err(crate_too_large(max_crate_size));
+    }
+
+    let upload = SpooledUpload::receive(&mut body, crate_len).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => invalid_body(),
+        _ => anyhow::Error::new(e).context("failed to receive upload").into(),
+    })?;
+
+    // Nothing may follow the crate file
+    if body.read(&mut [0; 1]).await.map_err(|_| invalid_body())? != 0 {
+        return Err(invalid_body());
+    }
+
+    Ok((metadata, upload))
+}
+
+/// Runs [`validation::validate_crate_file`] over the spooled file on the
+/// blocking pool, since unpacking can take a while for large crates.
+#[cfg(feature = "ssr")]
+async fn validate_spooled_crate(metadata: PublishRequest, upload: &SpooledUpload) -> ApiResult<PublishRequest> {
+    let file = std::fs::File::open(upload.path()).context("failed to open spooled upload")?;
+
+    let (metadata, validated) = tokio::task::spawn_blocking(move || {
+        let validated = validation::validate_crate_file(&metadata, std::io::BufReader::new(file), validation::MAX_UNPACKED_SIZE);
+        (metadata, validated)
+    })
+    .await
+    .context("crate validation panicked")?;
+
+    validated?;
+    Ok(metadata)
+}
+
+fn crate_too_large(max_crate_size: u64) -> ApiError {
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("max crate size is {} bytes", max_crate_size),
+    )
+}
+
+#[cfg(feature = "ssr")]
+async fn read_multipart_publish(mut multipart: Multipart) -> ApiResult<(PublishRequest, Vec<u8>)> {
+    let mut crate_file: Option<Vec<u8>> = None;
+    let mut metadata: Option<PublishRequest> = None;
+    let invalid_upload = |e: axum::extract::multipart::MultipartError| {
+        ApiError::bad_request(format!("invalid multipart upload: {}", e))
+    };
+
+    // Parse multipart form data
+    while let Some(field) = multipart.next_field().await.map_err(invalid_upload)? {
+        let name = field.name().unwrap_or("").to_string();
+        
+        match name.as_str() {
+            "crate" => {
+                let data = field.bytes().await.map_err(invalid_upload)?;
+                crate_file = Some(data.to_vec());
+            }
+            "metadata" => {
+                let data = field.bytes().await.map_err(invalid_upload)?;
+                metadata = Some(serde_json::from_slice(&data).map_err(|e| {
+                    ApiError::bad_request(format!("invalid publish metadata: {}", e))
+                })?);
+            }
+            _ => {} // Ignore unknown fields
+        }
+    }
+
+    let crate_file = crate_file.ok_or_else(|| ApiError::bad_request("missing `crate` field"))?;
+    let metadata = metadata.ok_or_else(|| ApiError::bad_request("missing `metadata` field"))?;
+
+    Ok((metadata, crate_file))
 }
 
 #[cfg(feature = "ssr")]
 pub async fn download_handler(
     State(app_state): State<AppState>,
-    Path((crate_name, version)): Path<(String, String)>,
-) -> Result<Response<Body>, StatusCode> {
-    let file_path = app_state.storage.get_crate_path(&crate_name, &version).await;
-    
-    if !file_path.exists() {
-        return Err(StatusCode::NOT_FOUND);
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Response<Body>> {
+    let not_found = || ApiError::not_found(format!("crate `{}` version `{}` does not exist", crate_name, version));
+
+    // The version's checksum is the digest its blob is stored under
+    let crate_model = db::get_crate_by_name(&app_state.pool, &crate_name)
+        .await?
+        .ok_or_else(not_found)?;
+    let crate_version = db::get_crate_version(&app_state.pool, crate_model.id, &version)
+        .await?
+        .ok_or_else(not_found)?;
+
+    let download = app_state.storage
+        .open_download(&crate_version.checksum)
+        .await
+        .with_context(|| format!("failed to open {} {} for download", crate_name, version))?
+        .ok_or_else(|| anyhow::anyhow!("artifact for {} {} is missing from storage", crate_name, version))?;
+
+    // Increment download counters
+    if let Err(e) = db::increment_download_count(&app_state.pool, crate_model.id).await {
+        tracing::warn!("Failed to increment download count: {}", e);
+    }
+    if let Err(e) = db::record_version_download(&app_state.pool, crate_model.id, &version).await {
+        tracing::warn!("Failed to record version download: {}", e);
     }
 
-    // Get crate info and increment download counter
-    if let Ok(Some(crate_model)) = db::get_crate_by_name(&app_state.pool, &crate_name).await {
-        if let Err(e) = db::increment_download_count(&app_state.pool, crate_model.id).await {
-            tracing::warn!("Failed to increment download count: {}", e);
+    let response = match download {
+        CrateDownload::Stream(ArtifactStream { reader, size }) => {
+            let mut builder = Response::builder()
+                .header("Content-Type", "application/x-tar")
+                .header("Content-Disposition", format!("attachment; filename=\"{}-{}.crate\"", crate_name, version));
+            if let Some(size) = size {
+                builder = builder.header(header::CONTENT_LENGTH, size);
+            }
+            builder.body(Body::from_stream(ReaderStream::new(reader)))
         }
+        CrateDownload::Redirect(url) => Response::builder()
+            .status(StatusCode::FOUND)
+            .header(header::LOCATION, url)
+            .body(Body::empty()),
     }
-
-    let file = tokio::fs::File::open(file_path).await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    
-    let stream = ReaderStream::new(file);
-    let body = Body::from_stream(stream);
-    
-    let response = Response::builder()
-        .header("Content-Type", "application/x-tar")
-        .header("Content-Disposition", format!("attachment; filename=\"{}-{}.crate\"", crate_name, version))
-        .body(body)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .context("failed to build download response")?;
     
     tracing::info!("Downloaded crate {} version {}", crate_name, version);
     
     Ok(response)
 }
 
+#[cfg(feature = "ssr")]
+pub async fn yank_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthContext>,
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    set_yanked(&app_state, &user, &auth, &crate_name, &version, true).await
+}
+
+#[cfg(feature = "ssr")]
+pub async fn unyank_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthContext>,
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    set_yanked(&app_state, &user, &auth, &crate_name, &version, false).await
+}
+
+#[cfg(feature = "ssr")]
+async fn set_yanked(
+    app_state: &AppState,
+    user: &User,
+    auth: &AuthContext,
+    crate_name: &str,
+    version: &str,
+    yanked: bool,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !auth.permits(TokenScope::Yank, crate_name) {
+        return Err(ApiError::forbidden(format!(
+            "this token does not have permission to yank versions of `{}`",
+            crate_name
+        )));
+    }
+
+    let crate_model = find_crate(app_state, crate_name).await?;
+
+    ensure_can_publish(app_state, &crate_model, user).await?;
+
+    let updated = db::set_version_yanked(&app_state.pool, crate_model.id, version, yanked).await?;
+
+    if !updated {
+        return Err(version_not_found(&crate_model, version));
+    }
+
+    let action = if yanked { "Yank" } else { "Unyank" };
+
+    if let Some(git_index) = &app_state.git_index {
+        let author = CommitAuthor { name: &user.username, email: &user.email };
+        let message = format!("{} {} {}", action, crate_model.name, version);
+        if let Err(e) = git_index.update_crate(&app_state.pool, &crate_model.name, &message, &author).await {
+            tracing::warn!("Failed to update git index for {}: {}", crate_model.name, e);
+        }
+    }
+
+    tracing::info!("{} crate {} version {} by user {}", action, crate_model.name, version, user.username);
+
+    Ok(Json(json!({ "ok": true })))
+}
+
+/// Publishing and yanking are open to the crate's owners, including members
+/// of owning organizations.
+#[cfg(feature = "ssr")]
+async fn ensure_can_publish(app_state: &AppState, crate_model: &Crate, user: &User) -> ApiResult<()> {
+    let allowed = db::user_can_publish(&app_state.pool, crate_model.id, user.id)
+        .await
+        .with_context(|| format!("failed to check owners of crate {}", crate_model.name))?;
+
+    if !allowed {
+        return Err(ApiError::forbidden(format!(
+            "this crate exists but you don't seem to be an owner. If you believe this is a mistake, ask an owner of `{}` to add you",
+            crate_model.name
+        )));
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "ssr")]
 pub async fn search_handler(
     State(app_state): State<AppState>,
-    Query(params): Query<SearchQuery>,
-) -> Result<Json<SearchResponse>, StatusCode> {
-    let query = params.q.unwrap_or_default();
-    let per_page = params.per_page.unwrap_or(10).min(100) as i64;
-    let page = params.page.unwrap_or(1) as i64;
+    ApiQuery(params): ApiQuery<SearchQuery>,
+) -> ApiResult<Json<SearchResponse>> {
+    let search = CrateSearch::parse(params.q.as_deref().unwrap_or_default(), params.sort.unwrap_or_default())
+        .with_keyword(params.keyword.as_deref())
+        .with_category(params.category.as_deref());
+    let per_page = params.per_page.unwrap_or(10).clamp(1, 100) as i64;
+    let page = params.page.unwrap_or(1).max(1) as i64;
     let offset = (page - 1) * per_page;
 
-    let crates = db::search_crates(&app_state.pool, &query, per_page, offset)
+    let (crates, total) = db::search_crates(&app_state.pool, &search, per_page, offset)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    
-    let total = db::count_search_results(&app_state.pool, &query)
+        .context("failed to search crates")?;
+
+    // Everything else the page needs comes from two batched queries, whatever
+    // its size; search results carry no version list, as on crates.io
+    let crate_ids: Vec<Uuid> = crates.iter().map(|c| c.id).collect();
+    let versions = db::get_version_summaries(&app_state.pool, &crate_ids)
+        .await
+        .context("failed to load versions of search results")?;
+    let recent_downloads = db::get_recent_downloads(&app_state.pool, &crate_ids)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .context("failed to load recent downloads of search results")?;
 
-    let mut crate_responses = Vec::new();
-    for crate_model in crates {
-        // Get versions for each crate
-        let versions = db::get_crate_versions(&app_state.pool, crate_model.id)
-            .await
-            .unwrap_or_default();
-        
-        let version_responses: Vec<VersionResponse> = versions.iter().map(|v| {
-            let features: HashMap<String, Vec<String>> = v.features
-                .as_ref()
-                .and_then(|f| serde_json::from_str(f).ok())
-                .unwrap_or_default();
-            
-            VersionResponse {
-                id: 1, // TODO: Use proper ID
-                num: v.version.clone(),
-                dl_path: format!("/api/v1/crates/{}/{}/download", crate_model.name, v.version),
-                readme_path: format!("/api/v1/crates/{}/{}/readme", crate_model.name, v.version),
-                updated_at: v.created_at,
-                created_at: v.created_at,
-                downloads: 0, // TODO: Track per-version downloads
-                features,
-                yanked: v.yanked,
-                license: v.license.clone(),
-                links: VersionLinksResponse {
-                    dependencies: format!("/api/v1/crates/{}/{}/dependencies", crate_model.name, v.version),
-                    version_downloads: format!("/api/v1/crates/{}/{}/downloads", crate_model.name, v.version),
-                    authors: format!("/api/v1/crates/{}/{}/authors", crate_model.name, v.version),
-                },
-                crate_size: Some(v.file_size),
-                published_by: Some(UserLinkResponse {
-                    id: 1,
-                    login: "user".to_string(), // TODO: Get actual user info
-                    name: None,
-                    avatar: None,
-                    url: "/users/user".to_string(),
-                }),
-                audit_actions: vec![],
-            }
-        }).collect();
-        
-        let keywords: Vec<String> = crate_model.keywords
-            .as_ref()
-            .and_then(|k| serde_json::from_str(k).ok())
-            .unwrap_or_default();
-        
-        let categories: Vec<String> = crate_model.categories
-            .as_ref()
-            .and_then(|c| serde_json::from_str(c).ok())
-            .unwrap_or_default();
-        
-        let max_version = versions.first().map(|v| v.version.clone()).unwrap_or_default();
-        
-        let crate_response = CrateResponse {
-            id: crate_model.id.to_string(),
-            name: crate_model.name.clone(),
-            updated_at: crate_model.updated_at,
-            versions: version_responses,
-            keywords,
-            categories,
-            badges: vec![],
-            created_at: crate_model.created_at,
-            downloads: crate_model.downloads,
-            recent_downloads: Some(crate_model.downloads),
-            max_version,
-            max_stable_version: None,
-            description: crate_model.description,
-            homepage: crate_model.homepage,
-            documentation: crate_model.documentation,
-            repository: crate_model.repository,
-            links: LinksResponse {
-                version_downloads: format!("/api/v1/crates/{}/downloads", crate_model.name),
-                versions: format!("/api/v1/crates/{}/versions", crate_model.name),
-                owners: format!("/api/v1/crates/{}/owners", crate_model.name),
-                owner_team: format!("/api/v1/crates/{}/owner_team", crate_model.name),
-                owner_user: format!("/api/v1/crates/{}/owner_user", crate_model.name),
-                reverse_dependencies: format!("/api/v1/crates/{}/reverse_dependencies", crate_model.name),
-            },
-            exact_match: crate_model.name.to_lowercase() == query.to_lowercase(),
-        };
-        
-        crate_responses.push(crate_response);
-    }
+    let crate_responses = crates
+        .into_iter()
+        .map(|crate_model| {
+            let crate_versions = versions.get(&crate_model.id).into_iter().flatten();
+            let max = max_versions(crate_versions.map(|(v, yanked)| (v.as_str(), *yanked)));
+            let recent = recent_downloads.get(&crate_model.id).copied().unwrap_or(0);
+            let exact_match = crate_model.name.to_lowercase() == search.text.to_lowercase();
+            crate_response(crate_model, max, recent, exact_match)
+        })
+        .collect();
 
     Ok(Json(SearchResponse {
         crates: crate_responses,
@@ -270,9 +578,546 @@ pub async fn search_handler(
 
 #[cfg(feature = "ssr")]
 pub async fn crate_info_handler(
-    State(_app_state): State<AppState>,
-    Path(crate_name): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    // TODO: Implement crate info lookup
-    Err(StatusCode::NOT_FOUND)
-}
\ No newline at end of file
+    State(app_state): State<AppState>,
+    ApiPath(crate_name): ApiPath<String>,
+) -> ApiResult<Json<CrateDetailResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let versions = find_versions(&app_state, &crate_model).await?;
+    let context = load_version_context(&app_state, &crate_model, &versions).await?;
+
+    let version_responses: Vec<VersionResponse> = versions
+        .iter()
+        .map(|v| version_response(&crate_model.name, v, &context))
+        .collect();
+
+    let keywords = json_list(&crate_model.keywords)
+        .into_iter()
+        .map(|keyword| KeywordResponse { id: keyword.clone(), keyword })
+        .collect();
+
+    let categories = json_list(&crate_model.categories)
+        .into_iter()
+        .map(|category| CategoryResponse {
+            id: category.clone(),
+            slug: category.to_lowercase(),
+            category,
+        })
+        .collect();
+
+    let recent_downloads = db::get_recent_downloads(&app_state.pool, &[crate_model.id])
+        .await
+        .with_context(|| format!("failed to load recent downloads of {}", crate_model.name))?
+        .remove(&crate_model.id)
+        .unwrap_or(0);
+
+    // The version list lives at the top level, as crates.io does it
+    let max = max_versions(versions.iter().map(|v| (v.version.as_str(), v.yanked)));
+    let crate_data = crate_response(crate_model, max, recent_downloads, true);
+
+    Ok(Json(CrateDetailResponse {
+        crate_data,
+        versions: version_responses,
+        keywords,
+        categories,
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn versions_handler(
+    State(app_state): State<AppState>,
+    ApiPath(crate_name): ApiPath<String>,
+) -> ApiResult<Json<VersionsResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let versions = find_versions(&app_state, &crate_model).await?;
+    let context = load_version_context(&app_state, &crate_model, &versions).await?;
+
+    Ok(Json(VersionsResponse {
+        meta: SearchMeta { total: versions.len() as i64 },
+        versions: versions
+            .iter()
+            .map(|v| version_response(&crate_model.name, v, &context))
+            .collect(),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn version_info_handler(
+    State(app_state): State<AppState>,
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Json<SingleVersionResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let crate_version = find_version(&app_state, &crate_model, &version).await?;
+    let context = load_version_context(&app_state, &crate_model, std::slice::from_ref(&crate_version)).await?;
+
+    Ok(Json(SingleVersionResponse {
+        version: version_response(&crate_model.name, &crate_version, &context),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn version_dependencies_handler(
+    State(app_state): State<AppState>,
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Json<DependenciesResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let crate_version = find_version(&app_state, &crate_model, &version).await?;
+
+    Ok(Json(DependenciesResponse {
+        dependencies: dependency_responses(&crate_version)
+            .into_iter()
+            .map(|(_, dep)| dep)
+            .collect(),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn crate_downloads_handler(
+    State(app_state): State<AppState>,
+    ApiPath(crate_name): ApiPath<String>,
+) -> ApiResult<Json<VersionDownloadsResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let versions = find_versions(&app_state, &crate_model).await?;
+
+    recent_downloads(&app_state, &crate_model, &versions, None).await
+}
+
+#[cfg(feature = "ssr")]
+pub async fn version_downloads_handler(
+    State(app_state): State<AppState>,
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Json<VersionDownloadsResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let crate_version = find_version(&app_state, &crate_model, &version).await?;
+
+    recent_downloads(&app_state, &crate_model, std::slice::from_ref(&crate_version), Some(&version)).await
+}
+
+#[cfg(feature = "ssr")]
+pub async fn version_authors_handler(
+    State(app_state): State<AppState>,
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Json<AuthorsResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let crate_version = find_version(&app_state, &crate_model, &version).await?;
+
+    Ok(Json(AuthorsResponse {
+        users: vec![],
+        meta: AuthorsMeta {
+            names: json_list(&crate_version.authors),
+        },
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn version_readme_handler(
+    State(app_state): State<AppState>,
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<Response<Body>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let crate_version = find_version(&app_state, &crate_model, &version).await?;
+    let readme = crate_version.readme.ok_or_else(|| {
+        ApiError::not_found(format!("`{}` version `{}` has no readme", crate_model.name, version))
+    })?;
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/markdown; charset=utf-8")
+        .body(Body::from(readme))
+        .context("failed to build readme response")?)
+}
+
+#[cfg(feature = "ssr")]
+pub async fn reverse_dependencies_handler(
+    State(app_state): State<AppState>,
+    ApiPath(crate_name): ApiPath<String>,
+) -> ApiResult<Json<ReverseDependenciesResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+
+    let candidates = db::get_dependent_versions(&app_state.pool, crate_model.id, &crate_model.name)
+        .await
+        .with_context(|| format!("failed to find reverse dependencies of {}", crate_model.name))?;
+
+    // Like crates.io, only a dependent crate's latest release counts
+    let mut latest: BTreeMap<&str, (semver::Version, &DependentVersion)> = BTreeMap::new();
+    for candidate in candidates.iter().filter(|candidate| !candidate.version.yanked) {
+        let Ok(number) = semver::Version::parse(&candidate.version.version) else {
+            continue;
+        };
+        match latest.get(candidate.crate_name.as_str()) {
+            Some((highest, _)) if *highest >= number => {}
+            _ => {
+                latest.insert(&candidate.crate_name, (number, candidate));
+            }
+        }
+    }
+
+    let mut dependencies = Vec::new();
+    let mut versions = Vec::new();
+    for (_, dependent) in latest.into_values() {
+        let matching: Vec<DependencyResponse> = dependency_responses(&dependent.version)
+            .into_iter()
+            .filter(|(registry, dep)| registry.is_none() && dep.crate_id == crate_model.name)
+            .map(|(_, dep)| dep)
+            .collect();
+
+        if matching.is_empty() {
+            continue;
+        }
+
+        let publisher = dependent.version.published_by
+            .zip(dependent.publisher_login.clone())
+            .map(|(id, login)| (id, user_link(&id, login, dependent.publisher_avatar.clone())));
+        let context = VersionContext {
+            downloads: HashMap::from([(dependent.version.version.clone(), dependent.downloads)]),
+            publishers: publisher.into_iter().collect(),
+        };
+
+        dependencies.extend(matching);
+        versions.push(version_response(&dependent.crate_name, &dependent.version, &context));
+    }
+
+    Ok(Json(ReverseDependenciesResponse {
+        meta: SearchMeta { total: versions.len() as i64 },
+        dependencies,
+        versions,
+    }))
+}
+
+/// Per-version data that lives outside `crate_versions`.
+#[cfg(feature = "ssr")]
+struct VersionContext {
+    downloads: HashMap<String, i64>,
+    publishers: HashMap<Uuid, UserLinkResponse>,
+}
+
+#[cfg(feature = "ssr")]
+async fn load_version_context(
+    app_state: &AppState,
+    crate_model: &Crate,
+    versions: &[CrateVersion],
+) -> ApiResult<VersionContext> {
+    let downloads = db::get_version_download_totals(&app_state.pool, crate_model.id)
+        .await
+        .with_context(|| format!("failed to load download counts for {}", crate_model.name))?;
+
+    let mut publishers = HashMap::new();
+    for user_id in versions.iter().filter_map(|v| v.published_by) {
+        if publishers.contains_key(&user_id) {
+            continue;
+        }
+        let publisher = db::get_user_by_id(&app_state.pool, user_id)
+            .await
+            .with_context(|| format!("failed to load publisher of {}", crate_model.name))?;
+        if let Some(publisher) = publisher {
+            publishers.insert(user_id, user_link(&publisher.id, publisher.username, publisher.avatar_url));
+        }
+    }
+
+    Ok(VersionContext { downloads, publishers })
+}
+
+#[cfg(feature = "ssr")]
+async fn find_crate(app_state: &AppState, crate_name: &str) -> ApiResult<Crate> {
+    db::get_crate_by_name(&app_state.pool, crate_name)
+        .await
+        .with_context(|| format!("failed to look up crate {}", crate_name))?
+        .ok_or_else(|| ApiError::not_found(format!("crate `{}` does not exist", crate_name)))
+}
+
+#[cfg(feature = "ssr")]
+async fn find_versions(app_state: &AppState, crate_model: &Crate) -> ApiResult<Vec<CrateVersion>> {
+    Ok(db::get_crate_versions(&app_state.pool, crate_model.id)
+        .await
+        .with_context(|| format!("failed to load versions of {}", crate_model.name))?)
+}
+
+#[cfg(feature = "ssr")]
+async fn find_version(app_state: &AppState, crate_model: &Crate, version: &str) -> ApiResult<CrateVersion> {
+    db::get_crate_version(&app_state.pool, crate_model.id, version)
+        .await
+        .with_context(|| format!("failed to look up {} {}", crate_model.name, version))?
+        .ok_or_else(|| version_not_found(crate_model, version))
+}
+
+fn version_not_found(crate_model: &Crate, version: &str) -> ApiError {
+    ApiError::not_found(format!("crate `{}` does not have a version `{}`", crate_model.name, version))
+}
+
+/// Daily downloads over the last 90 days, the window crates.io reports.
+#[cfg(feature = "ssr")]
+async fn recent_downloads(
+    app_state: &AppState,
+    crate_model: &Crate,
+    versions: &[CrateVersion],
+    version: Option<&str>,
+) -> ApiResult<Json<VersionDownloadsResponse>> {
+    let since = (chrono::Utc::now() - chrono::Duration::days(90)).format("%Y-%m-%d").to_string();
+
+    let daily = db::get_daily_downloads(&app_state.pool, crate_model.id, version, &since)
+        .await
+        .with_context(|| format!("failed to load downloads for {}", crate_model.name))?;
+
+    let version_downloads = daily
+        .into_iter()
+        .filter_map(|(num, date, downloads)| {
+            // Metrics for versions that no longer exist are dropped
+            let version = versions.iter().find(|v| v.version == num)?;
+            Some(VersionDownloadResponse {
+                version: version.id.to_string(),
+                downloads,
+                date,
+            })
+        })
+        .collect();
+
+    Ok(Json(VersionDownloadsResponse { version_downloads }))
+}
+
+#[cfg(feature = "ssr")]
+fn user_link(id: &Uuid, login: String, avatar: Option<String>) -> UserLinkResponse {
+    UserLinkResponse {
+        id: numeric_id(id) as i64,
+        url: format!("/users/{}", login),
+        login,
+        name: None,
+        avatar,
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn version_response(crate_name: &str, v: &CrateVersion, context: &VersionContext) -> VersionResponse {
+    let features: HashMap<String, Vec<String>> = v.features
+        .as_ref()
+        .and_then(|f| serde_json::from_str(f).ok())
+        .unwrap_or_default();
+
+    let published_by = v.published_by
+        .and_then(|user_id| context.publishers.get(&user_id))
+        .cloned();
+
+    VersionResponse {
+        id: v.id.to_string(),
+        crate_name: crate_name.to_string(),
+        num: v.version.clone(),
+        dl_path: format!("/api/v1/crates/{}/{}/download", crate_name, v.version),
+        readme_path: format!("/api/v1/crates/{}/{}/readme", crate_name, v.version),
+        updated_at: v.created_at,
+        created_at: v.created_at,
+        downloads: context.downloads.get(&v.version).copied().unwrap_or(0),
+        features,
+        yanked: v.yanked,
+        license: v.license.clone(),
+        links: VersionLinksResponse {
+            dependencies: format!("/api/v1/crates/{}/{}/dependencies", crate_name, v.version),
+            version_downloads: format!("/api/v1/crates/{}/{}/downloads", crate_name, v.version),
+            authors: format!("/api/v1/crates/{}/{}/authors", crate_name, v.version),
+        },
+        crate_size: Some(v.file_size),
+        checksum: v.checksum.clone(),
+        rust_version: v.rust_version.clone(),
+        published_by,
+        audit_actions: vec![],
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn crate_response(
+    crate_model: Crate,
+    (max_version, max_stable_version): (String, Option<String>),
+    recent_downloads: i64,
+    exact_match: bool,
+) -> CrateResponse {
+    CrateResponse {
+        id: crate_model.id.to_string(),
+        updated_at: crate_model.updated_at,
+        versions: vec![],
+        keywords: json_list(&crate_model.keywords),
+        categories: json_list(&crate_model.categories),
+        badges: vec![],
+        created_at: crate_model.created_at,
+        downloads: crate_model.downloads,
+        recent_downloads: Some(recent_downloads),
+        max_version,
+        max_stable_version,
+        description: crate_model.description,
+        homepage: crate_model.homepage,
+        documentation: crate_model.documentation,
+        repository: crate_model.repository,
+        links: LinksResponse {
+            version_downloads: format!("/api/v1/crates/{}/downloads", crate_model.name),
+            versions: format!("/api/v1/crates/{}/versions", crate_model.name),
+            owners: format!("/api/v1/crates/{}/owners", crate_model.name),
+            owner_team: format!("/api/v1/crates/{}/owner_team", crate_model.name),
+            owner_user: format!("/api/v1/crates/{}/owner_user", crate_model.name),
+            reverse_dependencies: format!("/api/v1/crates/{}/reverse_dependencies", crate_model.name),
+        },
+        name: crate_model.name,
+        exact_match,
+    }
+}
+
+/// Highest version and highest non-prerelease version by semver precedence.
+/// Yanked releases shouldn't be advertised unless nothing else is left.
+fn max_versions<'a>(versions: impl IntoIterator<Item = (&'a str, bool)>) -> (String, Option<String>) {
+    let parsed: Vec<(semver::Version, bool)> = versions
+        .into_iter()
+        .filter_map(|(version, yanked)| Some((semver::Version::parse(version).ok()?, yanked)))
+        .collect();
+
+    let available: Vec<&semver::Version> = match parsed.iter().any(|(_, yanked)| !yanked) {
+        true => parsed.iter().filter(|(_, yanked)| !yanked).map(|(v, _)| v).collect(),
+        false => parsed.iter().map(|(v, _)| v).collect(),
+    };
+
+    let max_version = available
+        .iter()
+        .max()
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+
+    let max_stable_version = available
+        .iter()
+        .filter(|v| v.pre.is_empty())
+        .max()
+        .map(|v| v.to_string());
+
+    (max_version, max_stable_version)
+}
+
+/// Dependencies of a version in API form, each paired with the registry it
+/// comes from (`None` for this registry).
+fn dependency_responses(v: &CrateVersion) -> Vec<(Option<String>, DependencyResponse)> {
+    let deps: Vec<PublishDependency> = v.dependencies
+        .as_ref()
+        .and_then(|d| serde_json::from_str(d).ok())
+        .unwrap_or_default();
+
+    deps.into_iter()
+        .enumerate()
+        .map(|(i, dep)| {
+            (dep.registry, DependencyResponse {
+                id: format!("{}-{}", v.id, i),
+                version_id: v.id.to_string(),
+                crate_id: dep.name,
+                req: dep.version_req,
+                optional: dep.optional,
+                default_features: dep.default_features,
+                features: dep.features,
+                target: dep.target,
+                kind: dep.kind,
+                downloads: 0,
+            })
+        })
+        .collect()
+}
+
+fn json_list(value: &Option<String>) -> Vec<String> {
+    value
+        .as_ref()
+        .and_then(|v| serde_json::from_str(v).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sha256_hex;
+
+    const MAX_CRATE_SIZE: u64 = 1024;
+
+    fn metadata() -> Vec<u8> {
+        json!({
+            "name": "demo",
+            "vers": "0.1.0",
+            "deps": [],
+            "features": {},
+            "authors": [],
+            "description": "Demo crate",
+            "keywords": [],
+            "categories": [],
+            "badges": {},
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    /// Builds a body the way Cargo frames it.
+    fn publish_body(metadata: &[u8], crate_file: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        body.extend_from_slice(metadata);
+        body.extend_from_slice(&(crate_file.len() as u32).to_le_bytes());
+        body.extend_from_slice(crate_file);
+        body
+    }
+
+    async fn read(body: Vec<u8>) -> ApiResult<(PublishRequest, SpooledUpload)> {
+        read_publish_body(Body::from(body), MAX_CRATE_SIZE).await
+    }
+
+    async fn status_of(body: Vec<u8>) -> StatusCode {
+        match read(body).await {
+            Ok(_) => panic!("the body should be refused"),
+            Err(e) => e.status(),
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_a_well_formed_body() {
+        let crate_file = b"not really a tarball";
+        let (metadata, upload) = read(publish_body(&metadata(), crate_file)).await.unwrap();
+
+        assert_eq!((metadata.name.as_str(), metadata.vers.as_str()), ("demo", "0.1.0"));
+        assert_eq!(upload.size, crate_file.len() as u64);
+        assert_eq!(upload.checksum, sha256_hex(crate_file));
+        assert_eq!(std::fs::read(upload.path()).unwrap(), crate_file);
+    }
+
+    #[tokio::test]
+    async fn lengths_are_little_endian() {
+        // Read as little-endian, a big-endian length is far past the limit
+        let metadata = metadata();
+        let mut body = publish_body(&metadata, b"crate");
+        body[..4].copy_from_slice(&(metadata.len() as u32).to_be_bytes());
+        assert_eq!(status_of(body).await, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn metadata_is_capped() {
+        let mut body = (MAX_PUBLISH_METADATA_SIZE + 1).to_le_bytes().to_vec();
+        body.extend_from_slice(&metadata());
+        assert_eq!(status_of(body).await, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Metadata right at the limit is read and then parsed
+        let padded = vec![b' '; MAX_PUBLISH_METADATA_SIZE as usize];
+        assert_eq!(status_of(publish_body(&padded, b"crate")).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn crates_over_the_limit_are_refused_before_reading_them() {
+        let mut body = publish_body(&metadata(), b"");
+        let len_at = body.len() - 4;
+        body[len_at..].copy_from_slice(&(MAX_CRATE_SIZE as u32 + 1).to_le_bytes());
+        assert_eq!(status_of(body).await, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let at_limit = vec![0; MAX_CRATE_SIZE as usize];
+        assert!(read(publish_body(&metadata(), &at_limit)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn truncated_bodies_are_refused() {
+        let body = publish_body(&metadata(), b"crate file");
+        let metadata_end = 4 + metadata().len();
+
+        // Inside each length prefix, the metadata and the crate file
+        for cut in [0, 2, 4, metadata_end - 1, metadata_end, metadata_end + 2, body.len() - 1] {
+            assert_eq!(status_of(body[..cut].to_vec()).await, StatusCode::BAD_REQUEST, "cut at {}", cut);
+        }
+    }
+
+    #[tokio::test]
+    async fn trailing_bytes_are_refused() {
+        let mut body = publish_body(&metadata(), b"crate file");
+        body.push(0);
+        assert_eq!(status_of(body).await, StatusCode::BAD_REQUEST);
+    }
+}
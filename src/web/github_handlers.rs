@@ -1,15 +1,17 @@
 use axum::{
-    extract::{Query, State},
-    http::{StatusCode, HeaderMap},
+    extract::State,
+    http::StatusCode,
     response::{Json, Redirect},
     Extension,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use uuid::Uuid;
 use chrono::Utc;
 use tracing::{info, error, debug};
+use anyhow::Context;
 
 use crate::models::{GitHubUser, GitHubOAuthToken, LoginResponse, User, UserResponse};
+use crate::error::{ApiError, ApiResult, ApiQuery};
 use crate::{AppState, db};
 
 #[derive(Debug, Deserialize)]
@@ -28,7 +30,7 @@ pub struct GitHubErrorQuery {
 #[cfg(feature = "ssr")]
 pub async fn github_login_handler(
     State(app_state): State<AppState>,
-) -> Result<Redirect, StatusCode> {
+) -> ApiResult<Redirect> {
     if let Some(github_oauth) = &app_state.config.auth.github_oauth {
         let auth_url = format!(
             "https://github.com/login/oauth/authorize?client_id={}&redirect_uri={}&scope=user:email&state={}",
@@ -41,25 +43,25 @@ pub async fn github_login_handler(
         Ok(Redirect::permanent(&auth_url))
     } else {
         error!("GitHub OAuth not configured");
-        Err(StatusCode::NOT_IMPLEMENTED)
+        Err(github_not_configured())
     }
 }
 
 #[cfg(feature = "ssr")]
 pub async fn github_callback_handler(
     State(app_state): State<AppState>,
-    Query(params): Query<GitHubAuthQuery>,
-) -> Result<Json<LoginResponse>, StatusCode> {
+    ApiQuery(params): ApiQuery<GitHubAuthQuery>,
+) -> ApiResult<Json<LoginResponse>> {
     let github_oauth = app_state.config.auth.github_oauth
         .as_ref()
-        .ok_or(StatusCode::NOT_IMPLEMENTED)?;
+        .ok_or_else(github_not_configured)?;
 
     // Exchange code for access token
     let token = exchange_code_for_token(&params.code, github_oauth)
         .await
         .map_err(|e| {
             error!("Failed to exchange GitHub code for token: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::bad_gateway("failed to complete GitHub sign-in")
         })?;
 
     // Get user info from GitHub
@@ -67,16 +69,19 @@ pub async fn github_callback_handler(
         .await
         .map_err(|e| {
             error!("Failed to get GitHub user info: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::bad_gateway("failed to fetch the GitHub account")
         })?;
 
     // Check if user exists or create new user
-    let user = match db::get_user_by_github_id(&app_state.pool, github_user.id as i64).await {
-        Ok(Some(user)) => {
+    let user = match db::get_user_by_github_id(&app_state.pool, github_user.id as i64)
+        .await
+        .context("failed to look up GitHub user")?
+    {
+        Some(user) => {
             info!("Existing GitHub user logged in: {}", github_user.login);
             user
         }
-        Ok(None) => {
+        None => {
             // Create new user from GitHub info
             let username = ensure_unique_username(&app_state.pool, &github_user.login).await?;
             let email = github_user.email.clone().unwrap_or_else(|| {
@@ -90,18 +95,11 @@ pub async fn github_callback_handler(
                 github_user.id as i64,
                 github_user.name.as_deref(),
                 Some(&github_user.avatar_url),
-            ).await.map_err(|e| {
-                error!("Failed to create GitHub user: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+            ).await.context("failed to create GitHub user")?;
 
             info!("Created new user from GitHub: {}", username);
             user
         }
-        Err(e) => {
-            error!("Database error during GitHub login: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
     };
 
     // Create session token
@@ -110,10 +108,7 @@ pub async fn github_callback_handler(
 
     db::create_session(&app_state.pool, user.id, &session_token, expires_at)
         .await
-        .map_err(|e| {
-            error!("Failed to create session: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to create session")?;
 
     Ok(Json(LoginResponse {
         token: session_token,
@@ -162,27 +157,28 @@ async fn get_github_user(
 }
 
 async fn ensure_unique_username(
-    pool: &sqlx::SqlitePool,
+    pool: &db::DbPool,
     preferred_username: &str,
-) -> Result<String, StatusCode> {
+) -> ApiResult<String> {
     let mut username = preferred_username.to_string();
     let mut counter = 1;
 
     loop {
-        match db::get_user_by_username(pool, &username).await {
-            Ok(None) => return Ok(username),
-            Ok(Some(_)) => {
-                username = format!("{}{}", preferred_username, counter);
-                counter += 1;
-                if counter > 100 {
-                    error!("Too many attempts to find unique username for: {}", preferred_username);
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
-                }
-            }
-            Err(e) => {
-                error!("Database error checking username uniqueness: {}", e);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
+        let existing = db::get_user_by_username(pool, &username)
+            .await
+            .context("failed to check username uniqueness")?;
+
+        if existing.is_none() {
+            return Ok(username);
+        }
+
+        username = format!("{}{}", preferred_username, counter);
+        counter += 1;
+        if counter > 100 {
+            return Err(ApiError::conflict(format!(
+                "could not find a free username based on `{}`",
+                preferred_username
+            )));
         }
     }
 }
@@ -191,13 +187,10 @@ async fn ensure_unique_username(
 pub async fn github_disconnect_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-) -> Result<StatusCode, StatusCode> {
+) -> ApiResult<StatusCode> {
     db::disconnect_github_user(&app_state.pool, user.id)
         .await
-        .map_err(|e| {
-            error!("Failed to disconnect GitHub account: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to disconnect GitHub account")?;
 
     info!("User {} disconnected GitHub account", user.username);
     Ok(StatusCode::OK)
@@ -208,30 +201,29 @@ pub async fn github_disconnect_handler(
 pub async fn github_link_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Query(params): Query<GitHubAuthQuery>,
-) -> Result<Json<UserResponse>, StatusCode> {
+    ApiQuery(params): ApiQuery<GitHubAuthQuery>,
+) -> ApiResult<Json<UserResponse>> {
     let github_oauth = app_state.config.auth.github_oauth
         .as_ref()
-        .ok_or(StatusCode::NOT_IMPLEMENTED)?;
+        .ok_or_else(github_not_configured)?;
 
     let token = exchange_code_for_token(&params.code, github_oauth)
         .await
         .map_err(|e| {
             error!("Failed to exchange GitHub code for token: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::bad_gateway("failed to complete GitHub sign-in")
         })?;
 
     let github_user = get_github_user(&token.access_token, &app_state.config.github.user_agent)
         .await
         .map_err(|e| {
             error!("Failed to get GitHub user info: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::bad_gateway("failed to fetch the GitHub account")
         })?;
 
     // Check if GitHub account is already linked to another user
-    if let Ok(Some(_)) = db::get_user_by_github_id(&app_state.pool, github_user.id as i64).await {
-        error!("GitHub account already linked to another user");
-        return Err(StatusCode::CONFLICT);
+    if db::get_user_by_github_id(&app_state.pool, github_user.id as i64).await?.is_some() {
+        return Err(ApiError::conflict("this GitHub account is already linked to another user"));
     }
 
     // Link GitHub account to current user
@@ -241,11 +233,12 @@ pub async fn github_link_handler(
         github_user.id as i64,
         github_user.name.as_deref(),
         Some(&github_user.avatar_url),
-    ).await.map_err(|e| {
-        error!("Failed to link GitHub account: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    ).await.context("failed to link GitHub account")?;
 
     info!("User {} linked GitHub account: {}", user.username, github_user.login);
     Ok(Json(updated_user.into()))
 }
+
+fn github_not_configured() -> ApiError {
+    ApiError::not_implemented("GitHub sign-in is not configured on this registry")
+}
@@ -7,17 +7,18 @@ use axum::{
 use serde_json::json;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{info, error};
+use anyhow::Context;
 
 use crate::models::{
-    User, HealthStatus, HealthComponent, ComponentStatus, RegistryStats, 
-    MetricsCollector, TopCrateStats
+    User, HealthStatus, HealthComponent, ComponentStatus, RegistryStats,
 };
+use crate::error::{ApiError, ApiResult};
 use crate::{AppState, db};
 
 #[cfg(feature = "ssr")]
 pub async fn health_handler(
     State(app_state): State<AppState>,
-) -> Result<Json<HealthStatus>, StatusCode> {
+) -> ApiResult<Json<HealthStatus>> {
     let start_time = std::time::Instant::now();
 
     // Test database connection
@@ -33,7 +34,7 @@ pub async fn health_handler(
             HealthComponent {
                 status: ComponentStatus::Unhealthy,
                 response_time_ms: Some(db_start.elapsed().as_millis() as u64),
-                details: Some(format!("Database error: {}", e)),
+                details: Some("Database unavailable".to_string()),
             }
         }
     };
@@ -51,17 +52,44 @@ pub async fn health_handler(
             HealthComponent {
                 status: ComponentStatus::Unhealthy,
                 response_time_ms: Some(storage_start.elapsed().as_millis() as u64),
-                details: Some(format!("Storage error: {}", e)),
+                details: Some("Storage unavailable".to_string()),
+            }
+        }
+    };
+
+    // Check the results of the integrity scrubber
+    let integrity_status = match db::count_integrity_issues(&app_state.pool).await {
+        Ok(0) => HealthComponent {
+            status: ComponentStatus::Healthy,
+            response_time_ms: None,
+            details: Some(match app_state.integrity_scrub.snapshot().finished_at {
+                Some(at) => format!("All artifacts verified by the scrub finished at {}", at.to_rfc3339()),
+                None => "No integrity problems recorded".to_string(),
+            }),
+        },
+        Ok(count) => HealthComponent {
+            status: ComponentStatus::Degraded,
+            response_time_ms: None,
+            details: Some(format!("{} artifact(s) missing or corrupt", count)),
+        },
+        Err(e) => {
+            error!("Integrity health check failed: {:#}", e);
+            HealthComponent {
+                status: ComponentStatus::Unhealthy,
+                response_time_ms: None,
+                details: Some("Integrity status unavailable".to_string()),
             }
         }
     };
 
     // Determine overall status
-    let overall_status = if matches!(database_status.status, ComponentStatus::Healthy) 
-        && matches!(storage_status.status, ComponentStatus::Healthy) {
-        "healthy"
-    } else {
+    let overall_status = if !matches!(database_status.status, ComponentStatus::Healthy) 
+        || !matches!(storage_status.status, ComponentStatus::Healthy) {
         "unhealthy"
+    } else if !matches!(integrity_status.status, ComponentStatus::Healthy) {
+        "degraded"
+    } else {
+        "healthy"
     };
 
     // Get system metrics
@@ -73,6 +101,7 @@ pub async fn health_handler(
         version: env!("CARGO_PKG_VERSION").to_string(),
         database: database_status,
         storage: storage_status,
+        integrity: integrity_status,
         uptime_seconds: uptime,
         memory_usage_mb: memory_usage,
     };
@@ -84,11 +113,14 @@ pub async fn health_handler(
 #[cfg(feature = "ssr")]
 pub async fn readiness_handler(
     State(app_state): State<AppState>,
-) -> Result<StatusCode, StatusCode> {
+) -> ApiResult<StatusCode> {
     // Simple readiness check - just verify database is accessible
     match sqlx::query("SELECT 1").fetch_one(&app_state.pool).await {
         Ok(_) => Ok(StatusCode::OK),
-        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+        Err(e) => {
+            error!("Readiness check failed: {}", e);
+            Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "database unavailable"))
+        }
     }
 }
 
@@ -101,23 +133,20 @@ pub async fn liveness_handler() -> StatusCode {
 #[cfg(feature = "ssr")]
 pub async fn metrics_handler(
     State(app_state): State<AppState>,
-) -> Result<Json<RegistryStats>, StatusCode> {
+) -> ApiResult<Json<RegistryStats>> {
     let stats = gather_registry_stats(&app_state).await
-        .map_err(|e| {
-            error!("Failed to gather registry stats: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to gather registry stats")?;
 
     Ok(Json(stats))
 }
 
 #[cfg(feature = "ssr")]
 pub async fn prometheus_metrics_handler(
-    State(_app_state): State<AppState>,
-) -> Result<String, StatusCode> {
+    State(app_state): State<AppState>,
+) -> ApiResult<String> {
     // TODO: Implement Prometheus metrics export
     // For now, return basic metrics in Prometheus format
-    let metrics = format!(
+    let mut metrics = format!(
         "# HELP ghostcrate_info Information about GhostCrate instance\n\
          # TYPE ghostcrate_info gauge\n\
          ghostcrate_info{{version=\"{}\"}} 1\n\
@@ -129,6 +158,86 @@ pub async fn prometheus_metrics_handler(
         get_uptime_seconds()
     );
 
+    if let Some(cache) = app_state.storage.cache_stats() {
+        metrics.push_str(&format!(
+            "\n\
+             # HELP ghostcrate_storage_cache_hits_total Downloads served from the local storage cache\n\
+             # TYPE ghostcrate_storage_cache_hits_total counter\n\
+             ghostcrate_storage_cache_hits_total {}\n\
+             \n\
+             # HELP ghostcrate_storage_cache_misses_total Downloads fetched from the storage backend\n\
+             # TYPE ghostcrate_storage_cache_misses_total counter\n\
+             ghostcrate_storage_cache_misses_total {}\n\
+             \n\
+             # HELP ghostcrate_storage_cache_evictions_total Cached crates evicted to stay within the budget\n\
+             # TYPE ghostcrate_storage_cache_evictions_total counter\n\
+             ghostcrate_storage_cache_evictions_total {}\n\
+             \n\
+             # HELP ghostcrate_storage_cache_invalid_total Crates fetched for the cache that failed checksum validation\n\
+             # TYPE ghostcrate_storage_cache_invalid_total counter\n\
+             ghostcrate_storage_cache_invalid_total {}\n\
+             \n\
+             # HELP ghostcrate_storage_cache_entries Crates currently cached\n\
+             # TYPE ghostcrate_storage_cache_entries gauge\n\
+             ghostcrate_storage_cache_entries {}\n\
+             \n\
+             # HELP ghostcrate_storage_cache_bytes Bytes currently cached\n\
+             # TYPE ghostcrate_storage_cache_bytes gauge\n\
+             ghostcrate_storage_cache_bytes {}\n\
+             \n\
+             # HELP ghostcrate_storage_cache_max_bytes Configured cache budget\n\
+             # TYPE ghostcrate_storage_cache_max_bytes gauge\n\
+             ghostcrate_storage_cache_max_bytes {}\n",
+            cache.hits,
+            cache.misses,
+            cache.evictions,
+            cache.invalid,
+            cache.entries,
+            cache.bytes,
+            cache.max_bytes
+        ));
+    }
+
+    if let Some(replication) = app_state.storage.replication_stats() {
+        metrics.push_str(&format!(
+            "\n\
+             # HELP ghostcrate_storage_replication_pending Changes waiting to be copied to the storage replica\n\
+             # TYPE ghostcrate_storage_replication_pending gauge\n\
+             ghostcrate_storage_replication_pending {}\n\
+             \n\
+             # HELP ghostcrate_storage_replication_lag_seconds Age of the oldest change not yet replicated\n\
+             # TYPE ghostcrate_storage_replication_lag_seconds gauge\n\
+             ghostcrate_storage_replication_lag_seconds {}\n\
+             \n\
+             # HELP ghostcrate_storage_replication_failed Changes that ran out of replication attempts\n\
+             # TYPE ghostcrate_storage_replication_failed gauge\n\
+             ghostcrate_storage_replication_failed {}\n\
+             \n\
+             # HELP ghostcrate_storage_replication_copied_total Objects copied to the storage replica\n\
+             # TYPE ghostcrate_storage_replication_copied_total counter\n\
+             ghostcrate_storage_replication_copied_total {}\n\
+             \n\
+             # HELP ghostcrate_storage_replication_bytes_total Bytes copied to the storage replica\n\
+             # TYPE ghostcrate_storage_replication_bytes_total counter\n\
+             ghostcrate_storage_replication_bytes_total {}\n\
+             \n\
+             # HELP ghostcrate_storage_replication_retries_total Failed replication attempts that were retried\n\
+             # TYPE ghostcrate_storage_replication_retries_total counter\n\
+             ghostcrate_storage_replication_retries_total {}\n\
+             \n\
+             # HELP ghostcrate_storage_replication_failovers_total Reads served by the replica after the primary failed\n\
+             # TYPE ghostcrate_storage_replication_failovers_total counter\n\
+             ghostcrate_storage_replication_failovers_total {}\n",
+            replication.pending,
+            replication.lag_seconds,
+            replication.failures.len(),
+            replication.copied,
+            replication.bytes_copied,
+            replication.retries,
+            replication.failovers
+        ));
+    }
+
     Ok(metrics)
 }
 
@@ -136,17 +245,14 @@ pub async fn prometheus_metrics_handler(
 pub async fn admin_stats_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-) -> Result<Json<RegistryStats>, StatusCode> {
+) -> ApiResult<Json<RegistryStats>> {
     // Check if user is admin
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("this endpoint requires an administrator"));
     }
 
     let stats = gather_registry_stats(&app_state).await
-        .map_err(|e| {
-            error!("Failed to gather admin stats: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to gather admin stats")?;
 
     Ok(Json(stats))
 }
@@ -201,7 +307,7 @@ async fn gather_registry_stats(app_state: &AppState) -> Result<RegistryStats, an
     let new_users_last_30_days = db::count_new_users_last_days(&app_state.pool, 30).await.unwrap_or(0);
 
     // Storage size (approximate)
-    let storage_size_bytes = estimate_storage_size(&app_state).await.unwrap_or(0);
+    let storage_size_bytes = estimate_storage_size(app_state).await.unwrap_or(0);
 
     // Top crates
     let top_crates = db::get_top_crates(&app_state.pool, 10).await.unwrap_or_default();
@@ -235,7 +341,9 @@ async fn estimate_storage_size(app_state: &AppState) -> Result<i64, Box<dyn std:
     }
 }
 
-fn get_directory_size(path: &std::path::Path) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<i64, Box<dyn std::error::Error + Send + Sync>>> + Send + '_>> {
+type DirectorySizeFuture<'a> = std::pin::Pin<Box<dyn std::future::Future<Output = Result<i64, Box<dyn std::error::Error + Send + Sync>>> + Send + 'a>>;
+
+fn get_directory_size(path: &std::path::Path) -> DirectorySizeFuture<'_> {
     Box::pin(async move {
         let mut total_size = 0i64;
         let mut entries = tokio::fs::read_dir(path).await?;
@@ -270,9 +378,9 @@ fn get_memory_usage_mb() -> u64 {
 #[cfg(feature = "ssr")]
 pub async fn system_info_handler(
     Extension(user): Extension<User>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> ApiResult<Json<serde_json::Value>> {
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("this endpoint requires an administrator"));
     }
 
     let info = json!({
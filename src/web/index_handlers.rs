@@ -0,0 +1,156 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha2::{Sha256, Digest};
+use std::io::Read;
+use tokio_util::io::ReaderStream;
+use anyhow::Context;
+
+use crate::error::{ApiError, ApiResult, ApiPath, ApiQuery};
+use crate::{AppState, index};
+
+#[cfg(feature = "ssr")]
+pub async fn sparse_index_handler(
+    State(app_state): State<AppState>,
+    ApiPath(path): ApiPath<String>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if path == "config.json" {
+        return Ok(Json(index::registry_config(&app_state.config)).into_response());
+    }
+
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let is_valid_name = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    // Only answer on the canonical path so each crate has exactly one index URL
+    if !is_valid_name || index::index_file_path(name) != path.to_lowercase() {
+        return Err(ApiError::not_found("not found"));
+    }
+
+    let file = index::render_index_file(&app_state.pool, name)
+        .await
+        .with_context(|| format!("failed to render index file for {}", name))?
+        .ok_or_else(|| ApiError::not_found(format!("crate `{}` does not exist", name)))?;
+
+    let etag = format!("\"{:x}\"", Sha256::digest(file.as_bytes()));
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value == etag)
+        .unwrap_or(false);
+
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (header::ETAG, etag),
+        ],
+        file,
+    ).into_response())
+}
+
+/// Largest upload-pack request accepted once decompressed. Negotiation
+/// requests are lists of object ids, so this leaves plenty of room.
+const MAX_UPLOAD_PACK_REQUEST_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct GitServiceQuery {
+    pub service: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+pub async fn git_info_refs_handler(
+    State(app_state): State<AppState>,
+    ApiQuery(params): ApiQuery<GitServiceQuery>,
+) -> ApiResult<Response> {
+    let git_index = app_state.git_index.as_ref().ok_or_else(git_index_disabled)?;
+
+    // The index is read-only over HTTP, and we only speak the smart protocol
+    if params.service.as_deref() != Some("git-upload-pack") {
+        return Err(ApiError::forbidden("the git index is read-only"));
+    }
+
+    let refs = git_index.advertise_refs().await.context("failed to advertise git index refs")?;
+
+    let mut body = pkt_line("# service=git-upload-pack\n");
+    body.extend_from_slice(b"0000");
+    body.extend_from_slice(&refs);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-git-upload-pack-advertisement"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        body,
+    ).into_response())
+}
+
+#[cfg(feature = "ssr")]
+pub async fn git_upload_pack_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Response> {
+    let git_index = app_state.git_index.as_ref().ok_or_else(git_index_disabled)?;
+
+    let is_gzip = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.eq_ignore_ascii_case("gzip"))
+        .unwrap_or(false);
+
+    let request = if is_gzip {
+        // Inflating is CPU-bound and blocking, so it stays off the async workers
+        tokio::task::spawn_blocking(move || decode_gzip_request(&body))
+            .await
+            .context("upload-pack request decoding panicked")??
+    } else {
+        body.to_vec()
+    };
+
+    let stdout = git_index.upload_pack(request).context("failed to run git upload-pack")?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-git-upload-pack-result"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        Body::from_stream(ReaderStream::new(stdout)),
+    ).into_response())
+}
+
+/// Inflates a gzipped upload-pack request, refusing anything that inflates
+/// past [`MAX_UPLOAD_PACK_REQUEST_SIZE`].
+#[cfg(feature = "ssr")]
+fn decode_gzip_request(body: &[u8]) -> ApiResult<Vec<u8>> {
+    // Read one byte past the limit so an oversized body can be told apart
+    let mut decoded = Vec::new();
+    GzDecoder::new(body)
+        .take(MAX_UPLOAD_PACK_REQUEST_SIZE + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| ApiError::bad_request(format!("invalid gzip request body: {}", e)))?;
+    if decoded.len() as u64 > MAX_UPLOAD_PACK_REQUEST_SIZE {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("decompressed request body is larger than {} bytes", MAX_UPLOAD_PACK_REQUEST_SIZE),
+        ));
+    }
+    Ok(decoded)
+}
+
+fn pkt_line(data: &str) -> Vec<u8> {
+    format!("{:04x}{}", data.len() + 4, data).into_bytes()
+}
+
+fn git_index_disabled() -> ApiError {
+    ApiError::not_found("the git index is not enabled on this registry")
+}
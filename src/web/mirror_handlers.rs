@@ -1,18 +1,21 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::State,
     http::StatusCode,
     response::Json,
     Extension,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tracing::{info, error, warn, debug};
+use anyhow::{anyhow, Context};
 use chrono::Utc;
 
 use crate::models::{
     User, MirrorStatus, MirrorSyncRequest, MirrorSyncProgress, 
-    CratesIoSearchResponse, CratesIoCrate, GitHubApiClient
+    CratesIoSearchResponse
 };
+use crate::error::{ApiError, ApiResult, ApiJson, ApiPath, ApiQuery};
 use crate::{AppState, db};
+use crate::storage::sha256_hex;
 
 #[derive(Debug, Deserialize)]
 pub struct MirrorQuery {
@@ -31,9 +34,9 @@ pub struct ProxyQuery {
 pub async fn mirror_status_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-) -> Result<Json<MirrorStatus>, StatusCode> {
+) -> ApiResult<Json<MirrorStatus>> {
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(admin_required());
     }
 
     if !app_state.config.registry.crates_io_mirror.enabled {
@@ -50,10 +53,7 @@ pub async fn mirror_status_handler(
     }
 
     let status = get_mirror_status(&app_state).await
-        .map_err(|e| {
-            error!("Failed to get mirror status: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(|e| anyhow!("failed to get mirror status: {}", e))?;
 
     Ok(Json(status))
 }
@@ -62,19 +62,19 @@ pub async fn mirror_status_handler(
 pub async fn start_mirror_sync_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Json(request): Json<MirrorSyncRequest>,
-) -> Result<Json<MirrorSyncProgress>, StatusCode> {
+    ApiJson(request): ApiJson<MirrorSyncRequest>,
+) -> ApiResult<Json<MirrorSyncProgress>> {
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(admin_required());
     }
 
     if !app_state.config.registry.crates_io_mirror.enabled {
-        return Err(StatusCode::NOT_IMPLEMENTED);
+        return Err(mirror_disabled());
     }
 
     // Check if sync is already in progress
     if is_sync_in_progress(&app_state).await {
-        return Err(StatusCode::CONFLICT);
+        return Err(ApiError::conflict("a mirror sync is already in progress"));
     }
 
     info!("Starting crates.io mirror sync requested by user: {}", user.username);
@@ -104,16 +104,13 @@ pub async fn start_mirror_sync_handler(
 pub async fn mirror_sync_progress_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-) -> Result<Json<MirrorSyncProgress>, StatusCode> {
+) -> ApiResult<Json<MirrorSyncProgress>> {
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(admin_required());
     }
 
     let progress = get_sync_progress(&app_state).await
-        .map_err(|e| {
-            error!("Failed to get sync progress: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(|e| anyhow!("failed to get sync progress: {}", e))?;
 
     Ok(Json(progress))
 }
@@ -121,10 +118,10 @@ pub async fn mirror_sync_progress_handler(
 #[cfg(feature = "ssr")]
 pub async fn proxy_crates_io_search_handler(
     State(app_state): State<AppState>,
-    Query(params): Query<ProxyQuery>,
-) -> Result<Json<CratesIoSearchResponse>, StatusCode> {
+    ApiQuery(params): ApiQuery<ProxyQuery>,
+) -> ApiResult<Json<CratesIoSearchResponse>> {
     if !app_state.config.registry.crates_io_mirror.enabled {
-        return Err(StatusCode::NOT_IMPLEMENTED);
+        return Err(mirror_disabled());
     }
 
     let query = params.q.unwrap_or_default();
@@ -142,7 +139,6 @@ pub async fn proxy_crates_io_search_handler(
     }
 
     // Fallback to proxying crates.io
-    let client = reqwest::Client::new();
     let url = format!(
         "{}/api/v1/crates?q={}&per_page={}&page={}",
         app_state.config.registry.crates_io_mirror.upstream_url,
@@ -151,14 +147,14 @@ pub async fn proxy_crates_io_search_handler(
         page
     );
 
-    let response = client
+    let response = app_state
+        .upstream_client
         .get(&url)
-        .header("User-Agent", &app_state.config.github.user_agent)
         .send()
         .await
         .map_err(|e| {
             error!("Failed to proxy crates.io search: {}", e);
-            StatusCode::BAD_GATEWAY
+            ApiError::bad_gateway("failed to reach the upstream registry")
         })?;
 
     let search_response: CratesIoSearchResponse = response
@@ -166,7 +162,7 @@ pub async fn proxy_crates_io_search_handler(
         .await
         .map_err(|e| {
             error!("Failed to parse crates.io search response: {}", e);
-            StatusCode::BAD_GATEWAY
+            ApiError::bad_gateway("the upstream registry returned an invalid response")
         })?;
 
     info!("Proxied search results from crates.io");
@@ -176,36 +172,35 @@ pub async fn proxy_crates_io_search_handler(
 #[cfg(feature = "ssr")]
 pub async fn proxy_crate_download_handler(
     State(app_state): State<AppState>,
-    Path((crate_name, version)): Path<(String, String)>,
-) -> Result<axum::response::Response, StatusCode> {
+    ApiPath((crate_name, version)): ApiPath<(String, String)>,
+) -> ApiResult<axum::response::Response> {
     if !app_state.config.registry.crates_io_mirror.enabled {
-        return Err(StatusCode::NOT_IMPLEMENTED);
+        return Err(mirror_disabled());
     }
 
     debug!("Proxying crate download: {}-{}", crate_name, version);
 
     // First, check if we have it in local storage
-    if app_state.storage.crate_exists(&crate_name, &version).await {
-        info!("Serving crate from local mirror: {}-{}", crate_name, version);
-        
-        // Serve from local storage
-        let data = app_state.storage.get_crate_data(&crate_name, &version).await
-            .map_err(|e| {
-                error!("Failed to read crate from storage: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-
-        let response = axum::response::Response::builder()
-            .header("Content-Type", "application/x-tar")
-            .header("Content-Disposition", format!("attachment; filename=\"{}-{}.crate\"", crate_name, version))
-            .body(axum::body::Body::from(data))
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        return Ok(response);
+    let cached = db::get_mirror_artifact(&app_state.pool, &crate_name, &version).await?;
+    if let Some(cached) = cached {
+        if app_state.storage.blob_exists(&cached.checksum).await {
+            info!("Serving crate from local mirror: {}-{}", crate_name, version);
+
+            // Serve from local storage
+            let data = app_state.storage.get_blob_data(&cached.checksum).await
+                .with_context(|| format!("failed to read {} {} from storage", crate_name, version))?;
+
+            let response = axum::response::Response::builder()
+                .header("Content-Type", "application/x-tar")
+                .header("Content-Disposition", format!("attachment; filename=\"{}-{}.crate\"", crate_name, version))
+                .body(axum::body::Body::from(data))
+                .context("failed to build download response")?;
+
+            return Ok(response);
+        }
     }
 
     // Proxy from crates.io
-    let client = reqwest::Client::new();
     let url = format!(
         "{}/api/v1/crates/{}/{}/download",
         app_state.config.registry.crates_io_mirror.upstream_url,
@@ -213,29 +208,33 @@ pub async fn proxy_crate_download_handler(
         version
     );
 
-    let response = client
+    let response = app_state
+        .upstream_client
         .get(&url)
-        .header("User-Agent", &app_state.config.github.user_agent)
         .send()
         .await
         .map_err(|e| {
             error!("Failed to proxy crate download: {}", e);
-            StatusCode::BAD_GATEWAY
+            ApiError::bad_gateway("failed to reach the upstream registry")
         })?;
 
     if !response.status().is_success() {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::not_found(format!(
+            "crate `{}` version `{}` does not exist upstream",
+            crate_name, version
+        )));
     }
 
     let data = response.bytes().await
         .map_err(|e| {
             error!("Failed to read crate data from crates.io: {}", e);
-            StatusCode::BAD_GATEWAY
+            ApiError::bad_gateway("failed to download the crate from the upstream registry")
         })?;
 
-    // Optionally cache the crate for future requests
-    if let Err(e) = app_state.storage.store_crate(&crate_name, &version, &data).await {
-        warn!("Failed to cache crate locally: {}", e);
+    // Optionally cache the crate for future requests, remembering its
+    // checksum so the integrity scrubber can verify it later
+    if let Err(e) = cache_mirrored_crate(&app_state, &crate_name, &version, &data).await {
+        warn!("Failed to cache crate locally: {:#}", e);
     } else {
         debug!("Cached crate locally: {}-{}", crate_name, version);
     }
@@ -246,11 +245,19 @@ pub async fn proxy_crate_download_handler(
         .header("Content-Type", "application/x-tar")
         .header("Content-Disposition", format!("attachment; filename=\"{}-{}.crate\"", crate_name, version))
         .body(axum::body::Body::from(data))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .context("failed to build download response")?;
 
     Ok(response)
 }
 
+async fn cache_mirrored_crate(app_state: &AppState, crate_name: &str, version: &str, data: &[u8]) -> anyhow::Result<()> {
+    // Held until the row is written, so the garbage collector cannot take
+    // the blob for an orphan in between
+    let _blob_lock = app_state.storage.lock_blob(&sha256_hex(data)).await;
+    let digest = app_state.storage.store_blob(data).await?;
+    db::record_mirror_artifact(&app_state.pool, crate_name, version, &digest, data.len() as i64).await
+}
+
 async fn get_mirror_status(app_state: &AppState) -> Result<MirrorStatus, Box<dyn std::error::Error + Send + Sync>> {
     // This would typically read from a database table or redis
     // For now, return a basic status
@@ -316,13 +323,13 @@ async fn perform_mirror_sync(
 pub async fn clear_mirror_cache_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-) -> Result<StatusCode, StatusCode> {
+) -> ApiResult<StatusCode> {
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(admin_required());
     }
 
     if !app_state.config.registry.crates_io_mirror.enabled {
-        return Err(StatusCode::NOT_IMPLEMENTED);
+        return Err(mirror_disabled());
     }
 
     // TODO: Implement cache clearing
@@ -336,9 +343,9 @@ pub async fn clear_mirror_cache_handler(
 pub async fn mirror_config_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> ApiResult<Json<serde_json::Value>> {
     if !user.is_admin {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(admin_required());
     }
 
     let config = serde_json::json!({
@@ -350,3 +357,11 @@ pub async fn mirror_config_handler(
 
     Ok(Json(config))
 }
+
+fn admin_required() -> ApiError {
+    ApiError::forbidden("mirror management requires an administrator")
+}
+
+fn mirror_disabled() -> ApiError {
+    ApiError::not_implemented("the crates.io mirror is disabled on this registry")
+}
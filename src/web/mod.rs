@@ -7,13 +7,19 @@ pub mod oidc_handlers;
 pub mod organization_handlers;
 pub mod health_handlers;
 pub mod mirror_handlers;
+pub mod index_handlers;
+pub mod owner_handlers;
+pub mod token_handlers;
 
 pub use auth_handlers::*;
 pub use app::*;
 pub use cargo_handlers::*;
-pub use admin_handlers::*;
+pub use admin_handlers::{admin_dashboard_handler, admin_users_handler, admin_delete_user_handler, admin_storage_migration_handler, admin_start_storage_migration_handler, admin_integrity_handler, admin_start_integrity_scrub_handler, admin_storage_gc_handler, admin_start_storage_gc_handler, AdminQuery, GcRequest, AdminStats, IntegrityReport};
 pub use github_handlers::*;
 pub use oidc_handlers::*;
 pub use organization_handlers::*;
 pub use health_handlers::*;
-pub use mirror_handlers::*;
\ No newline at end of file
+pub use mirror_handlers::*;
+pub use index_handlers::*;
+pub use owner_handlers::*;
+pub use token_handlers::*;
@@ -1,18 +1,18 @@
 use axum::{
-    extract::{Query, State, Path},
-    http::{StatusCode, Uri},
+    extract::State,
     response::{Json, Redirect},
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use uuid::Uuid;
 use chrono::Utc;
 use tracing::{info, error, debug};
-use anyhow::Result;
+use anyhow::Context;
 
 use crate::models::{
     User, LoginResponse, 
     EntraIdConfig, GitHubOidcConfig,
 };
+use crate::error::{ApiError, ApiResult, ApiPath, ApiQuery};
 use crate::{AppState, auth, db};
 
 #[derive(Debug, Deserialize)]
@@ -32,13 +32,13 @@ pub struct OidcLoginRequest {
 #[cfg(feature = "ssr")]
 pub async fn oidc_login_handler(
     State(app_state): State<AppState>,
-    Path(provider): Path<String>,
-) -> Result<Redirect, StatusCode> {
+    ApiPath(provider): ApiPath<String>,
+) -> ApiResult<Redirect> {
     
     // Get OIDC configuration
     let oidc_config = app_state.config.auth.oidc
         .as_ref()
-        .ok_or(StatusCode::NOT_IMPLEMENTED)?;
+        .ok_or_else(oidc_not_configured)?;
 
     match provider.as_str() {
         "entra" | "entraid" => {
@@ -47,10 +47,7 @@ pub async fn oidc_login_handler(
         "github" => {
             handle_github_oidc_login(&app_state, oidc_config.github.as_ref()).await
         }
-        _ => {
-            error!("Unsupported OIDC provider: {}", provider);
-            Err(StatusCode::BAD_REQUEST)
-        }
+        _ => Err(unsupported_provider(&provider)),
     }
 }
 
@@ -58,13 +55,13 @@ pub async fn oidc_login_handler(
 #[cfg(feature = "ssr")]
 pub async fn oidc_callback_handler(
     State(app_state): State<AppState>,
-    Path(provider): Path<String>,
-    Query(params): Query<OidcAuthQuery>,
-) -> Result<Json<LoginResponse>, StatusCode> {
+    ApiPath(provider): ApiPath<String>,
+    ApiQuery(params): ApiQuery<OidcAuthQuery>,
+) -> ApiResult<Json<LoginResponse>> {
     
     let oidc_config = app_state.config.auth.oidc
         .as_ref()
-        .ok_or(StatusCode::NOT_IMPLEMENTED)?;
+        .ok_or_else(oidc_not_configured)?;
 
     match provider.as_str() {
         "entra" | "entraid" => {
@@ -73,7 +70,7 @@ pub async fn oidc_callback_handler(
         "github" => {
             handle_github_oidc_callback(&app_state, &params, oidc_config.github.as_ref()).await
         }
-        _ => Err(StatusCode::BAD_REQUEST),
+        _ => Err(unsupported_provider(&provider)),
     }
 }
 
@@ -81,8 +78,8 @@ pub async fn oidc_callback_handler(
 async fn handle_entra_id_login(
     _app_state: &AppState,
     entra_config: Option<&EntraIdConfig>,
-) -> Result<Redirect, StatusCode> {
-    let config = entra_config.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+) -> ApiResult<Redirect> {
+    let config = entra_config.ok_or_else(oidc_not_configured)?;
     
     let auth_url = format!(
         "https://login.microsoftonline.com/{}/oauth2/v2.0/authorize?client_id={}&response_type=code&redirect_uri={}&scope={}&state={}",
@@ -101,8 +98,8 @@ async fn handle_entra_id_login(
 async fn handle_github_oidc_login(
     _app_state: &AppState,
     github_config: Option<&GitHubOidcConfig>,
-) -> Result<Redirect, StatusCode> {
-    let config = github_config.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+) -> ApiResult<Redirect> {
+    let config = github_config.ok_or_else(oidc_not_configured)?;
     
     let auth_url = format!(
         "https://github.com/login/oauth/authorize?client_id={}&redirect_uri={}&scope={}&state={}",
@@ -121,8 +118,8 @@ async fn handle_entra_id_callback(
     app_state: &AppState,
     params: &OidcAuthQuery,
     entra_config: Option<&EntraIdConfig>,
-) -> Result<Json<LoginResponse>, StatusCode> {
-    let config = entra_config.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+) -> ApiResult<Json<LoginResponse>> {
+    let config = entra_config.ok_or_else(oidc_not_configured)?;
     
     // Exchange code for access token
     let client = reqwest::Client::new();
@@ -145,17 +142,20 @@ async fn handle_entra_id_callback(
         .await
         .map_err(|e| {
             error!("Failed to exchange Entra ID code for token: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            provider_failed()
         })?;
 
     let token_data: serde_json::Value = token_response
         .json()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            error!("OIDC provider request failed: {}", e);
+            provider_failed()
+        })?;
 
     let access_token = token_data["access_token"]
         .as_str()
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        .ok_or_else(provider_failed)?;
 
     // Get user info from Microsoft Graph
     let user_response = client
@@ -163,12 +163,18 @@ async fn handle_entra_id_callback(
         .header("Authorization", format!("Bearer {}", access_token))
         .send()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            error!("OIDC provider request failed: {}", e);
+            provider_failed()
+        })?;
 
     let entra_user: serde_json::Value = user_response
         .json()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            error!("OIDC provider request failed: {}", e);
+            provider_failed()
+        })?;
 
     let email = entra_user["mail"]
         .as_str()
@@ -186,7 +192,7 @@ async fn handle_entra_id_callback(
 
     // Create JWT token
     let token = auth::create_jwt_token(&user, &app_state.config.auth)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .context("failed to create session token")?;
 
     let expires_at = chrono::Utc::now() + chrono::Duration::hours(app_state.config.auth.session_duration_hours);
 
@@ -202,8 +208,8 @@ async fn handle_github_oidc_callback(
     app_state: &AppState,
     params: &OidcAuthQuery,
     github_config: Option<&GitHubOidcConfig>,
-) -> Result<Json<LoginResponse>, StatusCode> {
-    let config = github_config.ok_or(StatusCode::NOT_IMPLEMENTED)?;
+) -> ApiResult<Json<LoginResponse>> {
+    let config = github_config.ok_or_else(oidc_not_configured)?;
     
     // Exchange code for access token (similar to existing GitHub handler)
     let client = reqwest::Client::new();
@@ -223,17 +229,20 @@ async fn handle_github_oidc_callback(
         .await
         .map_err(|e| {
             error!("Failed to exchange GitHub code for token: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            provider_failed()
         })?;
 
     let token_data: serde_json::Value = token_response
         .json()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            error!("OIDC provider request failed: {}", e);
+            provider_failed()
+        })?;
 
     let access_token = token_data["access_token"]
         .as_str()
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        .ok_or_else(provider_failed)?;
 
     // Get user info from GitHub
     let user_response = client
@@ -242,12 +251,18 @@ async fn handle_github_oidc_callback(
         .header("User-Agent", "GhostCrate/0.2.0")
         .send()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            error!("OIDC provider request failed: {}", e);
+            provider_failed()
+        })?;
 
     let github_user: serde_json::Value = user_response
         .json()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            error!("OIDC provider request failed: {}", e);
+            provider_failed()
+        })?;
 
     let email = github_user["email"]
         .as_str()
@@ -264,7 +279,7 @@ async fn handle_github_oidc_callback(
 
     // Create JWT token
     let token = auth::create_jwt_token(&user, &app_state.config.auth)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .context("failed to create session token")?;
 
     let expires_at = chrono::Utc::now() + chrono::Duration::hours(app_state.config.auth.session_duration_hours);
 
@@ -282,7 +297,7 @@ async fn create_or_update_oidc_user(
     provider: &str,
     email: &str,
     name: Option<String>,
-) -> Result<User, StatusCode> {
+) -> ApiResult<User> {
     // Check if user already exists with this OIDC link
     if let Ok(Some(existing_user)) = db::get_user_by_oidc_link(&app_state.pool, external_id, provider).await {
         info!("User {} logged in via OIDC ({})", existing_user.username, provider);
@@ -292,10 +307,9 @@ async fn create_or_update_oidc_user(
     // Check if user exists by email
     if let Ok(Some(existing_user)) = db::get_user_by_email(&app_state.pool, email).await {
         // Link existing user to OIDC provider
-        if let Err(e) = db::create_oidc_user_link(&app_state.pool, existing_user.id, external_id, provider, email, name.as_deref()).await {
-            error!("Failed to create OIDC link for existing user: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        db::create_oidc_user_link(&app_state.pool, existing_user.id, external_id, provider, email, name.as_deref())
+            .await
+            .context("failed to create OIDC link for existing user")?;
         info!("Linked existing user {} to OIDC provider {}", existing_user.username, provider);
         return Ok(existing_user);
     }
@@ -318,21 +332,17 @@ async fn create_or_update_oidc_user(
     };
 
     // Create user in database
-    match db::create_oidc_user(&app_state.pool, &new_user).await {
-        Ok(_) => {
-            // Create OIDC link
-            if let Err(e) = db::create_oidc_user_link(&app_state.pool, user_id, external_id, provider, email, name.as_deref()).await {
-                error!("Failed to create OIDC link for new user: {}", e);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-            info!("Created new user {} via OIDC ({})", username, provider);
-            Ok(new_user)
-        }
-        Err(e) => {
-            error!("Failed to create user: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+    db::create_oidc_user(&app_state.pool, &new_user)
+        .await
+        .context("failed to create user")?;
+
+    // Create OIDC link
+    db::create_oidc_user_link(&app_state.pool, user_id, external_id, provider, email, name.as_deref())
+        .await
+        .context("failed to create OIDC link for new user")?;
+
+    info!("Created new user {} via OIDC ({})", username, provider);
+    Ok(new_user)
 }
 
 /// Generate username from email
@@ -344,3 +354,15 @@ fn generate_username_from_email(email: &str) -> String {
         .collect::<String>()
         .to_lowercase()
 }
+
+fn oidc_not_configured() -> ApiError {
+    ApiError::not_implemented("OIDC sign-in is not configured on this registry")
+}
+
+fn unsupported_provider(provider: &str) -> ApiError {
+    ApiError::bad_request(format!("unsupported OIDC provider `{}`", provider))
+}
+
+fn provider_failed() -> ApiError {
+    ApiError::bad_gateway("failed to complete sign-in with the identity provider")
+}
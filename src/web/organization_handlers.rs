@@ -1,20 +1,22 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::State,
     http::StatusCode,
     response::Json,
     Extension,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use uuid::Uuid;
 use validator::Validate;
-use tracing::{info, error, warn};
+use tracing::info;
+use anyhow::Context;
 
 use crate::models::{
-    User, Organization, OrganizationMember, OrganizationRole, OrganizationInvite,
+    User, Organization,
     CreateOrganizationRequest, UpdateOrganizationRequest, InviteUserRequest,
     OrganizationResponse, OrganizationMemberResponse, OrganizationInviteResponse,
     BasicUserResponse, BasicOrganizationResponse
 };
+use crate::error::{ApiError, ApiResult, ApiJson, ApiPath, ApiQuery};
 use crate::{AppState, db};
 
 #[derive(Debug, Deserialize)]
@@ -32,29 +34,26 @@ pub struct AcceptInviteRequest {
 pub async fn create_organization_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Json(request): Json<CreateOrganizationRequest>,
-) -> Result<Json<OrganizationResponse>, StatusCode> {
+    ApiJson(request): ApiJson<CreateOrganizationRequest>,
+) -> ApiResult<Json<OrganizationResponse>> {
     if !app_state.config.registry.organizations_enabled {
-        return Err(StatusCode::NOT_IMPLEMENTED);
+        return Err(ApiError::not_implemented("organizations are disabled on this registry"));
     }
 
     // Validate request (only in SSR mode)
     #[cfg(feature = "ssr")]
-    request.validate().map_err(|_| StatusCode::BAD_REQUEST)?;
+    request.validate().map_err(invalid_request)?;
 
     // Check if organization name is available
-    if db::organization_exists(&app_state.pool, &request.name).await.unwrap_or(true) {
-        return Err(StatusCode::CONFLICT);
+    if db::organization_exists(&app_state.pool, &request.name).await? {
+        return Err(ApiError::conflict(format!("organization `{}` already exists", request.name)));
     }
 
     let organization = db::create_organization(
         &app_state.pool,
         &request,
         user.id,
-    ).await.map_err(|e| {
-        error!("Failed to create organization: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    ).await.context("failed to create organization")?;
 
     info!("User {} created organization: {}", user.username, organization.name);
 
@@ -72,20 +71,13 @@ pub async fn create_organization_handler(
 #[cfg(feature = "ssr")]
 pub async fn get_organization_handler(
     State(app_state): State<AppState>,
-    Path(org_name): Path<String>,
-) -> Result<Json<OrganizationResponse>, StatusCode> {
-    let organization = db::get_organization_by_name(&app_state.pool, &org_name)
-        .await
-        .map_err(|e| {
-            error!("Database error getting organization: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    ApiPath(org_name): ApiPath<String>,
+) -> ApiResult<Json<OrganizationResponse>> {
+    let organization = find_organization(&app_state, &org_name).await?;
 
     let owner = db::get_user_by_id(&app_state.pool, organization.owner_id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?
+        .context("organization owner does not exist")?;
 
     let member_count = db::get_organization_member_count(&app_state.pool, organization.id)
         .await
@@ -111,36 +103,32 @@ pub async fn get_organization_handler(
 pub async fn update_organization_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Path(org_name): Path<String>,
-    Json(request): Json<UpdateOrganizationRequest>,
-) -> Result<Json<OrganizationResponse>, StatusCode> {
-    request.validate().map_err(|_| StatusCode::BAD_REQUEST)?;
+    ApiPath(org_name): ApiPath<String>,
+    ApiJson(request): ApiJson<UpdateOrganizationRequest>,
+) -> ApiResult<Json<OrganizationResponse>> {
+    request.validate().map_err(invalid_request)?;
 
-    let organization = db::get_organization_by_name(&app_state.pool, &org_name)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    let organization = find_organization(&app_state, &org_name).await?;
 
     // Check if user has permission to update organization
-    if !db::user_can_manage_organization(&app_state.pool, user.id, organization.id).await.unwrap_or(false) {
-        return Err(StatusCode::FORBIDDEN);
+    if !db::user_can_manage_organization(&app_state.pool, user.id, organization.id).await? {
+        return Err(ApiError::forbidden(format!(
+            "you don't have permission to update organization `{}`",
+            organization.name
+        )));
     }
 
     let updated_organization = db::update_organization(
         &app_state.pool,
         organization.id,
         &request,
-    ).await.map_err(|e| {
-        error!("Failed to update organization: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    ).await.context("failed to update organization")?;
 
     info!("User {} updated organization: {}", user.username, organization.name);
 
     let owner = db::get_user_by_id(&app_state.pool, updated_organization.owner_id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?
+        .context("organization owner does not exist")?;
 
     let member_count = db::get_organization_member_count(&app_state.pool, updated_organization.id)
         .await
@@ -166,24 +154,21 @@ pub async fn update_organization_handler(
 pub async fn delete_organization_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Path(org_name): Path<String>,
-) -> Result<StatusCode, StatusCode> {
-    let organization = db::get_organization_by_name(&app_state.pool, &org_name)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    ApiPath(org_name): ApiPath<String>,
+) -> ApiResult<StatusCode> {
+    let organization = find_organization(&app_state, &org_name).await?;
 
     // Only owner can delete organization
     if organization.owner_id != user.id {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden(format!(
+            "only the owner of organization `{}` can delete it",
+            organization.name
+        )));
     }
 
     db::delete_organization(&app_state.pool, organization.id)
         .await
-        .map_err(|e| {
-            error!("Failed to delete organization: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to delete organization")?;
 
     info!("User {} deleted organization: {}", user.username, organization.name);
     Ok(StatusCode::NO_CONTENT)
@@ -192,13 +177,10 @@ pub async fn delete_organization_handler(
 #[cfg(feature = "ssr")]
 pub async fn get_organization_members_handler(
     State(app_state): State<AppState>,
-    Path(org_name): Path<String>,
-    Query(params): Query<OrganizationQuery>,
-) -> Result<Json<Vec<OrganizationMemberResponse>>, StatusCode> {
-    let organization = db::get_organization_by_name(&app_state.pool, &org_name)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    ApiPath(org_name): ApiPath<String>,
+    ApiQuery(params): ApiQuery<OrganizationQuery>,
+) -> ApiResult<Json<Vec<OrganizationMemberResponse>>> {
+    let organization = find_organization(&app_state, &org_name).await?;
 
     let per_page = params.per_page.unwrap_or(50).min(100) as i64;
     let page = params.page.unwrap_or(1) as i64;
@@ -206,10 +188,7 @@ pub async fn get_organization_members_handler(
 
     let members = db::get_organization_members(&app_state.pool, organization.id, per_page, offset)
         .await
-        .map_err(|e| {
-            error!("Failed to get organization members: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to get organization members")?;
 
     let mut member_responses = Vec::new();
     for (member, user) in members {
@@ -233,29 +212,29 @@ pub async fn get_organization_members_handler(
 pub async fn invite_user_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Path(org_name): Path<String>,
-    Json(request): Json<InviteUserRequest>,
-) -> Result<Json<OrganizationInviteResponse>, StatusCode> {
-    request.validate().map_err(|_| StatusCode::BAD_REQUEST)?;
+    ApiPath(org_name): ApiPath<String>,
+    ApiJson(request): ApiJson<InviteUserRequest>,
+) -> ApiResult<Json<OrganizationInviteResponse>> {
+    request.validate().map_err(invalid_request)?;
 
-    let organization = db::get_organization_by_name(&app_state.pool, &org_name)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    let organization = find_organization(&app_state, &org_name).await?;
 
     // Check if user has permission to invite
-    let user_role = db::get_user_organization_role(&app_state.pool, user.id, organization.id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::FORBIDDEN)?;
+    let user_role = db::get_user_organization_role(&app_state.pool, user.id, organization.id).await?;
 
-    if !user_role.can_invite() {
-        return Err(StatusCode::FORBIDDEN);
+    if !user_role.is_some_and(|role| role.can_invite()) {
+        return Err(ApiError::forbidden(format!(
+            "you don't have permission to invite members to organization `{}`",
+            organization.name
+        )));
     }
 
     // Check if user is already a member or has pending invite
-    if db::is_user_organization_member(&app_state.pool, &request.email, organization.id).await.unwrap_or(false) {
-        return Err(StatusCode::CONFLICT);
+    if db::is_user_organization_member(&app_state.pool, &request.email, organization.id).await? {
+        return Err(ApiError::conflict(format!(
+            "{} is already a member of organization `{}`",
+            request.email, organization.name
+        )));
     }
 
     let invite = db::create_organization_invite(
@@ -264,10 +243,7 @@ pub async fn invite_user_handler(
         &request.email,
         request.role,
         user.id,
-    ).await.map_err(|e| {
-        error!("Failed to create organization invite: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    ).await.context("failed to create organization invite")?;
 
     info!("User {} invited {} to organization {}", user.username, request.email, organization.name);
 
@@ -299,29 +275,25 @@ pub async fn invite_user_handler(
 pub async fn accept_invite_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Json(request): Json<AcceptInviteRequest>,
-) -> Result<Json<OrganizationMemberResponse>, StatusCode> {
+    ApiJson(request): ApiJson<AcceptInviteRequest>,
+) -> ApiResult<Json<OrganizationMemberResponse>> {
     let invite = db::get_organization_invite_by_token(&app_state.pool, &request.token)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+        .await?
+        .ok_or_else(|| ApiError::not_found("invitation not found"))?;
 
     // Check if invite has expired
     if invite.expires_at < chrono::Utc::now() {
-        return Err(StatusCode::GONE);
+        return Err(ApiError::new(StatusCode::GONE, "invitation has expired"));
     }
 
     // Check if the user's email matches the invite
     if user.email != invite.email {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::forbidden("this invitation was sent to a different email address"));
     }
 
     let member = db::accept_organization_invite(&app_state.pool, invite.id, user.id)
         .await
-        .map_err(|e| {
-            error!("Failed to accept organization invite: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to accept organization invite")?;
 
     info!("User {} accepted invitation to organization", user.username);
 
@@ -344,39 +316,32 @@ pub async fn accept_invite_handler(
 pub async fn remove_member_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Path((org_name, member_id)): Path<(String, Uuid)>,
-) -> Result<StatusCode, StatusCode> {
-    let organization = db::get_organization_by_name(&app_state.pool, &org_name)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    ApiPath((org_name, member_id)): ApiPath<(String, Uuid)>,
+) -> ApiResult<StatusCode> {
+    let organization = find_organization(&app_state, &org_name).await?;
 
     // Check if user has permission to remove members
-    let user_role = db::get_user_organization_role(&app_state.pool, user.id, organization.id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::FORBIDDEN)?;
+    let user_role = db::get_user_organization_role(&app_state.pool, user.id, organization.id).await?;
 
-    if !user_role.can_manage_members() {
-        return Err(StatusCode::FORBIDDEN);
+    if !user_role.is_some_and(|role| role.can_manage_members()) {
+        return Err(ApiError::forbidden(format!(
+            "you don't have permission to remove members from organization `{}`",
+            organization.name
+        )));
     }
 
     // Don't allow removing the organization owner
     let member = db::get_organization_member(&app_state.pool, member_id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+        .await?
+        .ok_or_else(|| ApiError::not_found("member not found"))?;
 
     if member.user_id == organization.owner_id {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request("the organization owner cannot be removed"));
     }
 
     db::remove_organization_member(&app_state.pool, member_id)
         .await
-        .map_err(|e| {
-            error!("Failed to remove organization member: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to remove organization member")?;
 
     info!("User {} removed member from organization {}", user.username, organization.name);
     Ok(StatusCode::NO_CONTENT)
@@ -386,30 +351,36 @@ pub async fn remove_member_handler(
 pub async fn leave_organization_handler(
     State(app_state): State<AppState>,
     Extension(user): Extension<User>,
-    Path(org_name): Path<String>,
-) -> Result<StatusCode, StatusCode> {
-    let organization = db::get_organization_by_name(&app_state.pool, &org_name)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    ApiPath(org_name): ApiPath<String>,
+) -> ApiResult<StatusCode> {
+    let organization = find_organization(&app_state, &org_name).await?;
 
     // Owner cannot leave their own organization
     if organization.owner_id == user.id {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request("the owner cannot leave their own organization"));
     }
 
     let member = db::get_user_organization_membership(&app_state.pool, user.id, organization.id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("you are not a member of organization `{}`", organization.name)))?;
 
     db::remove_organization_member(&app_state.pool, member.id)
         .await
-        .map_err(|e| {
-            error!("Failed to leave organization: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .context("failed to leave organization")?;
 
     info!("User {} left organization {}", user.username, organization.name);
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[cfg(feature = "ssr")]
+async fn find_organization(app_state: &AppState, org_name: &str) -> ApiResult<Organization> {
+    db::get_organization_by_name(&app_state.pool, org_name)
+        .await
+        .with_context(|| format!("failed to look up organization {}", org_name))?
+        .ok_or_else(|| ApiError::not_found(format!("organization `{}` does not exist", org_name)))
+}
+
+#[cfg(feature = "ssr")]
+fn invalid_request(errors: validator::ValidationErrors) -> ApiError {
+    ApiError::bad_request(format!("invalid request: {}", errors))
+}
@@ -0,0 +1,261 @@
+use axum::{
+    extract::State,
+    response::Json,
+    Extension,
+};
+use uuid::Uuid;
+use tracing::info;
+use anyhow::Context;
+
+use crate::models::{
+    AuthContext, TokenScope, Crate, CrateOwner, User, OwnerKind, OwnersRequest, OwnersResponse, TeamsResponse, OwnersChangeResponse,
+    TEAM_LOGIN_PREFIX,
+};
+use crate::error::{ApiError, ApiResult, ApiJson, ApiPath};
+use crate::{AppState, db};
+
+#[cfg(feature = "ssr")]
+pub async fn list_owners_handler(
+    State(app_state): State<AppState>,
+    ApiPath(crate_name): ApiPath<String>,
+) -> ApiResult<Json<OwnersResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let owners = load_owners(&app_state, &crate_model).await?;
+
+    Ok(Json(OwnersResponse {
+        users: owners.into_iter().map(Into::into).collect(),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn list_owner_users_handler(
+    State(app_state): State<AppState>,
+    ApiPath(crate_name): ApiPath<String>,
+) -> ApiResult<Json<OwnersResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let owners = load_owners(&app_state, &crate_model).await?;
+
+    Ok(Json(OwnersResponse {
+        users: owners
+            .into_iter()
+            .filter(|owner| owner.kind == OwnerKind::User)
+            .map(Into::into)
+            .collect(),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn list_owner_teams_handler(
+    State(app_state): State<AppState>,
+    ApiPath(crate_name): ApiPath<String>,
+) -> ApiResult<Json<TeamsResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    let owners = load_owners(&app_state, &crate_model).await?;
+
+    Ok(Json(TeamsResponse {
+        teams: owners
+            .into_iter()
+            .filter(|owner| owner.kind == OwnerKind::Team)
+            .map(Into::into)
+            .collect(),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn add_owners_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthContext>,
+    ApiPath(crate_name): ApiPath<String>,
+    ApiJson(request): ApiJson<OwnersRequest>,
+) -> ApiResult<Json<OwnersChangeResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    ensure_can_manage_owners(&app_state, &crate_model, &user, &auth).await?;
+
+    let mut additions = Vec::new();
+    for login in &request.users {
+        let (kind, owner_id) = resolve_owner(&app_state, &user, login).await?;
+        additions.push((login, kind, owner_id));
+    }
+
+    // All logins are added or none are
+    let mut tx = app_state.pool.begin().await.context("failed to start owner transaction")?;
+    let mut added = Vec::new();
+    for (login, kind, owner_id) in additions {
+        let was_added = match kind {
+            OwnerKind::User => db::add_crate_owner_user(&mut *tx, crate_model.id, owner_id, user.id).await,
+            OwnerKind::Team => db::add_crate_owner_team(&mut *tx, crate_model.id, owner_id, user.id).await,
+        }
+        .with_context(|| format!("failed to add owner {} to crate {}", login, crate_model.name))?;
+        added.push((login, was_added));
+    }
+    tx.commit().await.context("failed to commit owner changes")?;
+
+    let mut messages = Vec::new();
+    for (login, was_added) in added {
+        if was_added {
+            info!("User {} added {} as an owner of crate {}", user.username, login, crate_model.name);
+            messages.push(format!("{} has been added as an owner of crate {}", login, crate_model.name));
+        } else {
+            messages.push(format!("{} is already an owner of crate {}", login, crate_model.name));
+        }
+    }
+
+    Ok(Json(OwnersChangeResponse {
+        ok: true,
+        msg: messages.join(", "),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn remove_owners_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(auth): Extension<AuthContext>,
+    ApiPath(crate_name): ApiPath<String>,
+    ApiJson(request): ApiJson<OwnersRequest>,
+) -> ApiResult<Json<OwnersChangeResponse>> {
+    let crate_model = find_crate(&app_state, &crate_name).await?;
+    ensure_can_manage_owners(&app_state, &crate_model, &user, &auth).await?;
+
+    // Owners being removed are looked up among the crate's current owners, so
+    // an organization can be removed by owners who are not its members
+    let owners = load_owners(&app_state, &crate_model).await?;
+    let mut removals = Vec::new();
+    let mut messages = Vec::new();
+    for login in &request.users {
+        match find_current_owner(&owners, login) {
+            Some(owner) => removals.push((login, owner.kind, owner.owner_id)),
+            None => messages.push(format!("{} is not an owner of crate {}", login, crate_model.name)),
+        }
+    }
+
+    // The removals and the check that a user owner remains share a
+    // transaction, so concurrent removals cannot leave the crate unmanaged
+    let mut tx = app_state.pool.begin().await.context("failed to start owner transaction")?;
+    db::lock_crate_owners(&mut *tx, crate_model.id)
+        .await
+        .with_context(|| format!("failed to lock owners of crate {}", crate_model.name))?;
+
+    let mut removed = Vec::new();
+    for (login, kind, owner_id) in removals {
+        let was_removed = match kind {
+            OwnerKind::User => db::remove_crate_owner_user(&mut *tx, crate_model.id, owner_id).await,
+            OwnerKind::Team => db::remove_crate_owner_team(&mut *tx, crate_model.id, owner_id).await,
+        }
+        .with_context(|| format!("failed to remove owner {} from crate {}", login, crate_model.name))?;
+        removed.push((login, was_removed));
+    }
+
+    // A crate must keep at least one user owner, or nobody could manage it
+    let remaining_users = db::count_crate_user_owners(&mut *tx, crate_model.id)
+        .await
+        .with_context(|| format!("failed to count owners of crate {}", crate_model.name))?;
+    if remaining_users == 0 {
+        return Err(ApiError::bad_request(format!(
+            "cannot remove all individual owners of crate `{}`",
+            crate_model.name
+        )));
+    }
+    tx.commit().await.context("failed to commit owner changes")?;
+
+    for (login, was_removed) in removed {
+        if was_removed {
+            info!("User {} removed {} as an owner of crate {}", user.username, login, crate_model.name);
+            messages.push(format!("{} has been removed as an owner of crate {}", login, crate_model.name));
+        } else {
+            messages.push(format!("{} is not an owner of crate {}", login, crate_model.name));
+        }
+    }
+
+    Ok(Json(OwnersChangeResponse {
+        ok: true,
+        msg: messages.join(", "),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+async fn find_crate(app_state: &AppState, crate_name: &str) -> ApiResult<Crate> {
+    db::get_crate_by_name(&app_state.pool, crate_name)
+        .await
+        .with_context(|| format!("failed to look up crate {}", crate_name))?
+        .ok_or_else(|| ApiError::not_found(format!("crate `{}` does not exist", crate_name)))
+}
+
+#[cfg(feature = "ssr")]
+async fn load_owners(app_state: &AppState, crate_model: &Crate) -> ApiResult<Vec<CrateOwner>> {
+    Ok(db::get_crate_owners(&app_state.pool, crate_model.id)
+        .await
+        .with_context(|| format!("failed to get owners of crate {}", crate_model.name))?)
+}
+
+/// Owners are managed by the crate's user owners; being in an owning
+/// organization is enough to publish, but not to hand out ownership.
+#[cfg(feature = "ssr")]
+async fn ensure_can_manage_owners(
+    app_state: &AppState,
+    crate_model: &Crate,
+    user: &User,
+    auth: &AuthContext,
+) -> ApiResult<()> {
+    if !auth.permits(TokenScope::ChangeOwners, &crate_model.name) {
+        return Err(ApiError::forbidden(format!(
+            "this token does not have permission to change the owners of `{}`",
+            crate_model.name
+        )));
+    }
+
+    let is_owner = db::is_crate_user_owner(&app_state.pool, crate_model.id, user.id).await?;
+
+    if !is_owner && !user.is_admin {
+        return Err(ApiError::forbidden(format!(
+            "only owners of `{}` may change its owners",
+            crate_model.name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Finds the current owner a login refers to: a user, or an organization for
+/// `org:<name>`.
+fn find_current_owner<'a>(owners: &'a [CrateOwner], login: &str) -> Option<&'a CrateOwner> {
+    let (kind, login) = match login.strip_prefix(TEAM_LOGIN_PREFIX) {
+        Some(org_name) => (OwnerKind::Team, org_name),
+        None => (OwnerKind::User, login),
+    };
+
+    owners.iter().find(|owner| owner.kind == kind && owner.login == login)
+}
+
+/// Resolves an owner login to a user, or to an organization for `org:<name>`.
+/// Only members of an organization may make it an owner of a crate.
+#[cfg(feature = "ssr")]
+async fn resolve_owner(app_state: &AppState, user: &User, login: &str) -> ApiResult<(OwnerKind, Uuid)> {
+    if let Some(org_name) = login.strip_prefix(TEAM_LOGIN_PREFIX) {
+        if !app_state.config.registry.organizations_enabled {
+            return Err(ApiError::not_implemented("organizations are disabled on this registry"));
+        }
+
+        let organization = db::get_organization_by_name(&app_state.pool, org_name)
+            .await?
+            .ok_or_else(|| ApiError::not_found(format!("could not find organization `{}`", org_name)))?;
+
+        let role = db::get_user_organization_role(&app_state.pool, user.id, organization.id).await?;
+
+        if role.is_none() && !user.is_admin {
+            return Err(ApiError::forbidden(format!(
+                "only members of organization `{}` may add it as an owner",
+                org_name
+            )));
+        }
+
+        return Ok((OwnerKind::Team, organization.id));
+    }
+
+    let owner = db::get_user_by_username(&app_state.pool, login)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("could not find user with login `{}`", login)))?;
+
+    Ok((OwnerKind::User, owner.id))
+}
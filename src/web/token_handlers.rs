@@ -0,0 +1,99 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::Utc;
+use uuid::Uuid;
+use tracing::info;
+use anyhow::Context;
+
+use crate::auth::{generate_api_token, hash_api_token};
+use crate::models::{
+    User, TokenScope, CreateApiTokenRequest, ApiTokenResponse, CreatedApiTokenResponse,
+    is_valid_crate_pattern,
+};
+use crate::error::{ApiError, ApiResult, ApiJson, ApiPath};
+use crate::{AppState, db};
+
+#[cfg(feature = "ssr")]
+pub async fn list_api_tokens_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> ApiResult<Json<Vec<ApiTokenResponse>>> {
+    let tokens = db::list_user_api_tokens(&app_state.pool, user.id)
+        .await
+        .with_context(|| format!("failed to list API tokens for {}", user.username))?;
+
+    Ok(Json(tokens.into_iter().map(Into::into).collect()))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn create_api_token_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    ApiJson(request): ApiJson<CreateApiTokenRequest>,
+) -> ApiResult<Json<CreatedApiTokenResponse>> {
+    let name = request.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ApiError::bad_request("token name must be between 1 and 100 characters"));
+    }
+
+    let scopes = request.scopes.unwrap_or_else(TokenScope::all);
+    if scopes.is_empty() {
+        return Err(ApiError::bad_request("a token needs at least one scope"));
+    }
+
+    if let Some(patterns) = &request.crates {
+        if patterns.is_empty() {
+            return Err(ApiError::bad_request("crate scopes must not be empty; omit them to allow every crate"));
+        }
+        if let Some(pattern) = patterns.iter().find(|pattern| !is_valid_crate_pattern(pattern)) {
+            return Err(ApiError::bad_request(format!("invalid crate scope `{}`", pattern)));
+        }
+    }
+
+    if request.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(ApiError::bad_request("expiry date must be in the future"));
+    }
+
+    let token = generate_api_token();
+    let api_token = db::create_api_token(
+        &app_state.pool,
+        user.id,
+        name,
+        &hash_api_token(&token),
+        &scopes,
+        request.crates.as_deref(),
+        request.expires_at,
+    )
+    .await
+    .with_context(|| format!("failed to create API token for {}", user.username))?;
+
+    info!("User {} created API token {} ({})", user.username, api_token.name, api_token.id);
+
+    Ok(Json(CreatedApiTokenResponse {
+        token,
+        api_token: api_token.into(),
+    }))
+}
+
+#[cfg(feature = "ssr")]
+pub async fn revoke_api_token_handler(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    ApiPath(token_id): ApiPath<Uuid>,
+) -> ApiResult<StatusCode> {
+    let deleted = db::delete_api_token(&app_state.pool, user.id, token_id)
+        .await
+        .with_context(|| format!("failed to revoke API token {}", token_id))?;
+
+    if !deleted {
+        return Err(ApiError::not_found("API token not found"));
+    }
+
+    info!("User {} revoked API token {}", user.username, token_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}